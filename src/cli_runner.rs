@@ -9,6 +9,8 @@ use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::time::Duration;
 
+use crate::process::run_with_timeout;
+
 /// A forge CLI command to execute.
 #[derive(Debug, Clone)]
 pub struct ForgeCommand {
@@ -85,7 +87,7 @@ pub fn run_forge_command(cmd: &ForgeCommand, config: &RunnerConfig) -> Result<An
         command.current_dir(dir);
     }
 
-    let output = execute_with_timeout(&mut command, config.timeout).with_context(|| {
+    let output = run_with_timeout(&mut command, config.timeout).with_context(|| {
         format!(
             "Failed to execute: forge {} {}",
             cmd.cmd,
@@ -96,55 +98,46 @@ pub fn run_forge_command(cmd: &ForgeCommand, config: &RunnerConfig) -> Result<An
     parse_forge_output(&output)
 }
 
-#[allow(clippy::cast_possible_truncation)]
-fn execute_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output> {
-    use std::io::{BufRead, BufReader};
-    use std::thread;
-
-    let mut child = command
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .context("Failed to spawn forge process")?;
-
-    let timeout_ms = timeout.as_millis() as u64;
-    let start = std::time::Instant::now();
-
-    loop {
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                let stdout = child.stdout.take().map_or_else(Vec::new, |s| {
-                    BufReader::new(s)
-                        .lines()
-                        .map_while(Result::ok)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                        .into_bytes()
-                });
-                let stderr = child.stderr.take().map_or_else(Vec::new, |s| {
-                    BufReader::new(s)
-                        .lines()
-                        .map_while(Result::ok)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                        .into_bytes()
-                });
-                return Ok(Output {
-                    status,
-                    stdout,
-                    stderr,
-                });
-            }
-            Ok(None) => {
-                if start.elapsed() > timeout {
-                    let _ = child.kill();
-                    return Err(anyhow!("Command timed out after {timeout_ms}ms"));
-                }
-                thread::sleep(Duration::from_millis(10));
-            }
-            Err(e) => return Err(anyhow!("Error waiting for process: {e}")),
-        }
+/// Runs a forge command that writes JSON results to a file and returns the
+/// parsed output.
+///
+/// The command line is `forge <cmd> <fixture> <args...> -o <tmp.json>`.
+///
+/// # Errors
+///
+/// Returns an error if forge fails, exits non-zero or writes invalid JSON.
+pub fn run_forge_json(cmd: &ForgeCommand, config: &RunnerConfig) -> Result<serde_json::Value> {
+    // Use a temp file for JSON output to avoid console output mixing
+    let output_file = tempfile::Builder::new()
+        .suffix(".json")
+        .tempfile()
+        .context("Failed to create temp output file")?;
+
+    let mut command = Command::new(&config.forge_bin);
+    command.arg(&cmd.cmd);
+    command.arg(&cmd.fixture);
+    command.args(&cmd.args);
+    command.arg("-o");
+    command.arg(output_file.path());
+
+    if let Some(ref dir) = config.working_dir {
+        command.current_dir(dir);
     }
+
+    let output = run_with_timeout(&mut command, config.timeout)
+        .with_context(|| format!("Failed to run forge {}", cmd.cmd))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Forge exited with error: {}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        ));
+    }
+
+    let json_content =
+        std::fs::read_to_string(output_file.path()).context("Failed to read output file")?;
+    serde_json::from_str(&json_content).context("Failed to parse JSON")
 }
 
 fn parse_forge_output(output: &Output) -> Result<AnalyticsOutput> {
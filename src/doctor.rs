@@ -0,0 +1,205 @@
+//! Environment checks behind `forge-e2e-r doctor`.
+//!
+//! Each check reports what it found, or what is missing together with a
+//! command or setting that fixes it.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::cli_runner::find_forge_binary;
+use crate::process::run_with_timeout;
+use crate::r_validator::{check_r_available, check_r_package, validator_dependencies, RConfig};
+use crate::r_worker::WORKER_SCRIPT;
+
+/// Time allowed for `forge --version`.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of one check.
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// What was checked (e.g. `R package boot`).
+    pub name: String,
+    /// What was found.
+    pub outcome: Outcome,
+}
+
+/// Outcome of a [`Check`].
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// Present; details such as the version.
+    Ok(String),
+    /// Missing or broken, with a hint to fix it.
+    Failed { problem: String, fix: String },
+    /// Not checked because an earlier check failed.
+    Skipped(String),
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, details: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Ok(details.into()),
+        }
+    }
+
+    fn failed(name: impl Into<String>, problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Failed {
+                problem: problem.into(),
+                fix: fix.into(),
+            },
+        }
+    }
+
+    /// Returns true unless the check failed.
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        !matches!(self.outcome, Outcome::Failed { .. })
+    }
+}
+
+/// Checks the forge binary, Rscript, the validators directory and every R
+/// package the validators load.
+#[must_use]
+pub fn run_checks(binary: Option<&Path>, config: &RConfig) -> Vec<Check> {
+    let mut checks = vec![forge_check(binary), validators_check(config)];
+
+    let r = rscript_check(config);
+    let r_available = r.passed();
+    checks.push(r);
+
+    match validator_dependencies(&config.validators_dir) {
+        Ok(packages) => {
+            for (package, validators) in packages {
+                let name = format!("R package {package}");
+                checks.push(if r_available {
+                    package_check(name, &package, &validators, config)
+                } else {
+                    Check {
+                        name,
+                        outcome: Outcome::Skipped("Rscript not available".to_string()),
+                    }
+                });
+            }
+        }
+        Err(e) => checks.push(Check::failed(
+            "R packages",
+            format!("{e:#}"),
+            "Pass the validators directory with --validators",
+        )),
+    }
+    checks
+}
+
+fn forge_check(binary: Option<&Path>) -> Check {
+    let fix = "Set FORGE_BIN, pass --binary, or build forge in ../forge (cargo build --release)";
+    let Some(binary) = binary.map(Path::to_path_buf).or_else(find_forge_binary) else {
+        return Check::failed("forge", "forge binary not found", fix);
+    };
+    if binary != Path::new("forge") && !binary.exists() {
+        return Check::failed("forge", format!("{} does not exist", binary.display()), fix);
+    }
+
+    match run_with_timeout(Command::new(&binary).arg("--version"), VERSION_TIMEOUT) {
+        Ok(output) if output.status.success() => Check::ok(
+            "forge",
+            format!(
+                "{} ({})",
+                binary.display(),
+                String::from_utf8_lossy(&output.stdout).trim()
+            ),
+        ),
+        Ok(output) => Check::failed(
+            "forge",
+            format!(
+                "{} --version failed: {}",
+                binary.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            fix,
+        ),
+        Err(e) => Check::failed("forge", format!("{e:#}"), fix),
+    }
+}
+
+fn validators_check(config: &RConfig) -> Check {
+    let dir = &config.validators_dir;
+    if !dir.is_dir() {
+        return Check::failed(
+            "validators",
+            format!("{} not found", dir.display()),
+            "Run from the repository root or pass --validators",
+        );
+    }
+    if !dir.join(WORKER_SCRIPT).exists() {
+        return Check::failed(
+            "validators",
+            format!("{WORKER_SCRIPT} missing from {}", dir.display()),
+            "Restore it from git, or run with --no-r-worker",
+        );
+    }
+    Check::ok("validators", dir.display().to_string())
+}
+
+fn rscript_check(config: &RConfig) -> Check {
+    check_r_available(config).map_or_else(
+        |_| {
+            Check::failed(
+                "Rscript",
+                format!("{} not found", config.rscript_bin.display()),
+                "macOS: brew install r; Ubuntu: apt install r-base; Fedora: dnf install R",
+            )
+        },
+        |version| Check::ok("Rscript", version),
+    )
+}
+
+fn package_check(name: String, package: &str, validators: &[String], config: &RConfig) -> Check {
+    let used_by = validators.join(", ");
+    match check_r_package(package, config) {
+        Ok(true) => Check::ok(name, format!("used by {used_by}")),
+        Ok(false) => Check::failed(
+            name,
+            format!("not installed (used by {used_by})"),
+            format!(
+                "Rscript -e 'install.packages(\"{package}\")' or Rscript {}",
+                config.validators_dir.join("install_deps.R").display()
+            ),
+        ),
+        Err(e) => Check::failed(name, format!("{e:#}"), "Check that Rscript runs"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packages_are_skipped_without_rscript() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(WORKER_SCRIPT), "library(jsonlite)\n").unwrap();
+        std::fs::write(dir.path().join("boot_validator.R"), "library(boot)\n").unwrap();
+        let config = RConfig {
+            rscript_bin: dir.path().join("no-such-Rscript"),
+            validators_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let checks = run_checks(Some(&dir.path().join("no-such-forge")), &config);
+        let outcome = |name: &str| {
+            checks
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| c.outcome.clone())
+                .unwrap()
+        };
+
+        assert!(matches!(outcome("forge"), Outcome::Failed { .. }));
+        assert!(matches!(outcome("validators"), Outcome::Ok(_)));
+        assert!(matches!(outcome("Rscript"), Outcome::Failed { .. }));
+        assert!(matches!(outcome("R package boot"), Outcome::Skipped(_)));
+        assert!(matches!(outcome("R package jsonlite"), Outcome::Skipped(_)));
+    }
+}
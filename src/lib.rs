@@ -4,6 +4,14 @@
 //! against R validators at runtime.
 
 pub mod cli_runner;
+pub mod doctor;
+pub mod process;
+pub mod r_cache;
 pub mod r_validator;
+pub mod r_worker;
+pub mod record;
+pub mod report;
+pub mod select;
 pub mod stats;
+pub mod suite;
 pub mod types;
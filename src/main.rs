@@ -2,559 +2,748 @@
 //!
 //! Validates forge analytics against R.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use std::time::Instant;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use colored::Colorize;
-use tempfile::NamedTempFile;
 
-use forge_e2e_r::cli_runner::find_forge_binary;
-use forge_e2e_r::r_validator::{check_r_available, validate_with_r, RConfig, RParams};
-use forge_e2e_r::stats::{within_tolerance, Tolerance};
-use forge_e2e_r::types::{load_analytics_tests, AnalyticsTestSpec, TestResult};
+use forge_e2e_r::cli_runner::{find_forge_binary, RunnerConfig};
+use forge_e2e_r::doctor::{run_checks, Outcome};
+use forge_e2e_r::process::shell_quote;
+use forge_e2e_r::r_cache::{prune, RCache, DEFAULT_CACHE_DIR};
+use forge_e2e_r::r_validator::{check_r_available, RConfig};
+use forge_e2e_r::r_worker::RWorker;
+use forge_e2e_r::record::record_file;
+use forge_e2e_r::report::{write_reports, ReportTarget, Summary};
+use forge_e2e_r::select::{Selection, FORMULA_SUITE};
+use forge_e2e_r::stats::multiple::PAdjust;
+use forge_e2e_r::suite::formula::{formula_invocation, formula_r_params};
+use forge_e2e_r::suite::{adjust_reports, run_parallel, Oracle, RunContext, SuiteRegistry};
+use forge_e2e_r::types::{
+    load_analytics_tests, load_formula_suite, AnalyticsTestSpec, FormulaSuite, TestResult,
+};
 
 #[derive(Parser)]
 #[command(name = "forge-e2e-r")]
 #[command(about = "E2E validation of forge analytics against R")]
 #[command(version)]
 struct Cli {
-    /// Run all tests (headless mode with colored output).
-    #[arg(long)]
-    all: bool,
+    #[command(subcommand)]
+    command: Command,
 
     /// Path to test specs directory.
-    #[arg(short, long, default_value = "tests/analytics")]
+    #[arg(short, long, default_value = "tests/analytics", global = true)]
     tests: PathBuf,
 
     /// Path to forge binary (or set `FORGE_BIN` env var).
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     binary: Option<PathBuf>,
 
     /// Path to R validators directory.
-    #[arg(long, default_value = "validators/r")]
+    #[arg(long, default_value = "validators/r", global = true)]
     validators: PathBuf,
+
+    /// Launch Rscript for every test instead of using persistent R workers.
+    #[arg(long, global = true)]
+    no_r_worker: bool,
+
+    /// Directory of cached R validator results.
+    #[arg(long, default_value = DEFAULT_CACHE_DIR, global = true)]
+    cache_dir: PathBuf,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List suites and tests with their R validator.
+    List(SelectArgs),
+
+    /// Run the selected tests (all of them without filters).
+    Run {
+        #[command(flatten)]
+        select: SelectArgs,
+
+        #[command(flatten)]
+        options: RunArgs,
+    },
+
+    /// Check the forge binary, Rscript and the R packages the validators need.
+    Doctor,
+
+    /// Show the forge model, forge command and R payload of a test without running it.
+    Explain {
+        /// Test name (or formula assertion name).
+        test: String,
+    },
+
+    /// Rewrite the `r_expected` blocks in the spec files from live R output.
+    Record {
+        #[command(flatten)]
+        select: SelectArgs,
+
+        /// Only record the test with this name.
+        #[arg(long)]
+        test: Option<String>,
+    },
+
+    /// Manage the cache of R validator results.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+/// Test selection; a test must pass every filter given.
+#[derive(Args, Clone)]
+struct SelectArgs {
+    /// Test name globs, e.g. 'normal_*' (`*` and `?` wildcards).
+    patterns: Vec<String>,
+
+    /// Only tests of this suite (e.g. `monte_carlo`, `formula`); repeatable.
+    #[arg(long = "suite")]
+    suites: Vec<String>,
+
+    /// Only tests with this tag; repeatable.
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    /// Skip tests whose names match this glob; repeatable.
+    #[arg(long)]
+    exclude: Vec<String>,
+}
+
+impl From<SelectArgs> for Selection {
+    fn from(args: SelectArgs) -> Self {
+        Self {
+            patterns: args.patterns,
+            suites: args.suites,
+            tags: args.tags,
+            exclude: args.exclude,
+        }
+    }
+}
+
+/// Options of `run`.
+#[derive(Args)]
+struct RunArgs {
+    /// Number of tests to run concurrently (defaults to the CPU count).
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Check forge against live R, the recorded `r_expected` values (no R
+    /// needed), or both (also catches R results drifting from the record).
+    #[arg(long, value_enum, default_value_t = Oracle::Live)]
+    oracle: Oracle,
+
+    /// Always run R instead of reusing cached validator results.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Exit 0 when tests are inconclusive but none failed; by default an
+    /// inconclusive equivalence test fails the run like a failed one.
+    #[arg(long)]
+    allow_inconclusive: bool,
+
+    /// Write a `junit`, `json` or `tap` report to PATH; without `=PATH` it
+    /// goes to stdout in place of the console output. Repeatable.
+    #[arg(long = "report", value_name = "FORMAT[=PATH]")]
+    reports: Vec<ReportTarget>,
+
+    /// Adjust every p-value of the run together (goodness-of-fit and
+    /// equivalence tests): `holm` bounds the chance of any spurious
+    /// failure, `bh` (Benjamini-Hochberg) the share of spurious failures,
+    /// `none` judges each test alone.
+    #[arg(long, value_enum, default_value_t = PAdjust::Holm)]
+    p_adjust: PAdjust,
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Delete entries for changed validator scripts or another R version.
+    Prune {
+        /// Delete every entry.
+        #[arg(long)]
+        all: bool,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    // Find forge binary
+    match &cli.command {
+        Command::List(select) => list_mode(&cli, &Selection::from(select.clone())),
+        Command::Run { select, options } => {
+            run_mode(&cli, &Selection::from(select.clone()), options)
+        }
+        Command::Doctor => doctor_mode(&cli),
+        Command::Explain { test } => explain_mode(&cli, test),
+        Command::Record { select, test } => {
+            let mut selection = Selection::from(select.clone());
+            selection.patterns.extend(test.clone());
+            record_mode(&cli, &selection)
+        }
+        Command::Cache {
+            command: CacheCommand::Prune { all },
+        } => prune_mode(&cli, *all),
+    }
+}
+
+fn r_config(cli: &Cli) -> RConfig {
+    RConfig {
+        validators_dir: cli.validators.clone(),
+        ..Default::default()
+    }
+}
+
+/// Finds the forge binary from `--binary`, `FORGE_BIN` or the usual places.
+fn forge_binary(cli: &Cli) -> anyhow::Result<PathBuf> {
     let forge_binary = cli
         .binary
+        .clone()
         .or_else(find_forge_binary)
         .ok_or_else(|| anyhow::anyhow!("Forge binary not found. Set FORGE_BIN or use --binary"))?;
 
-    if !forge_binary.exists() {
+    if forge_binary != Path::new("forge") && !forge_binary.exists() {
         anyhow::bail!("Forge binary not found: {}", forge_binary.display());
     }
+    Ok(forge_binary)
+}
+
+/// Loads the specs under `--tests` and keeps the selected ones, printing
+/// how many were loaded or selected if `console` is set.
+fn load_selected(
+    cli: &Cli,
+    selection: &Selection,
+    registry: &SuiteRegistry,
+    console: bool,
+) -> anyhow::Result<(Vec<AnalyticsTestSpec>, Vec<FormulaSuite>)> {
+    let (all_tests, all_formula_suites) = load_tests(&cli.tests)?;
+    let assertion_count =
+        |suites: &[FormulaSuite]| -> usize { suites.iter().map(|s| s.assertions.len()).sum() };
+    let total_assertions = assertion_count(&all_formula_suites);
+    let total_tests = all_tests.len();
+
+    let tests = selection.filter_tests(all_tests, registry);
+    let formula_suites = selection.filter_formula_suites(all_formula_suites);
+    let assertions = assertion_count(&formula_suites);
+    if console {
+        if selection.is_empty() {
+            println!(
+                "Loaded {} tests, {assertions} formula assertions",
+                tests.len()
+            );
+        } else {
+            println!(
+                "Selected {} of {total_tests} tests, {assertions} of {total_assertions} formula assertions",
+                tests.len()
+            );
+        }
+        println!();
+    }
+    if tests.is_empty() && formula_suites.is_empty() {
+        anyhow::bail!("No tests match the selection");
+    }
+    Ok((tests, formula_suites))
+}
+
+/// Runs the selected tests against forge.
+fn run_mode(cli: &Cli, selection: &Selection, options: &RunArgs) -> anyhow::Result<()> {
+    let forge_binary = forge_binary(cli)?;
+
+    // A report on stdout replaces the console output
+    let stdout_reports = options.reports.iter().filter(|t| t.path.is_none()).count();
+    if stdout_reports > 1 {
+        anyhow::bail!("Only one --report can go to stdout; give the others a path");
+    }
+    let console = stdout_reports == 0;
 
     // Check R availability
-    let r_config = RConfig {
-        validators_dir: cli.validators.clone(),
-        ..Default::default()
+    let r_config = r_config(cli);
+    let r_version = if options.oracle.needs_r() {
+        check_r_available(&r_config).map_err(|_| {
+            anyhow::anyhow!(
+                "R (Rscript) not found. Install with:\n  macOS: brew install r\n  Ubuntu: apt install r-base\nor run without R using --oracle recorded"
+            )
+        })?
+    } else {
+        "not used (recorded r_expected values)".to_string()
     };
 
-    let r_version = check_r_available(&r_config).map_err(|_| {
-        anyhow::anyhow!(
-            "R (Rscript) not found. Install with:\n  macOS: brew install r\n  Ubuntu: apt install r-base"
-        )
-    })?;
+    // Cached results would hide the R drift that `--oracle both` looks for
+    let use_cache = options.oracle == Oracle::Live && !options.no_cache;
+
+    if console {
+        println!("{}", "forge-e2e-r".bold());
+        println!("  Forge: {}", forge_binary.display());
+        println!("  R: {r_version}");
+        println!("  Tests: {}", cli.tests.display());
+        println!("  Validators: {}", cli.validators.display());
+        if use_cache {
+            println!("  R cache: {}", cli.cache_dir.display());
+        }
+        println!();
+    }
 
-    println!("{}", "forge-e2e-r".bold());
-    println!("  Forge: {}", forge_binary.display());
-    println!("  R: {r_version}");
-    println!("  Tests: {}", cli.tests.display());
-    println!("  Validators: {}", cli.validators.display());
-    println!();
+    let registry = SuiteRegistry::default();
+    let (tests, formula_suites) = load_selected(cli, selection, &registry, console)?;
 
-    // Load tests
-    let tests = load_tests(&cli.tests)?;
-    println!("Loaded {} tests", tests.len());
-    println!();
+    let jobs = options
+        .jobs
+        .or_else(|| thread::available_parallelism().ok().map(NonZeroUsize::get))
+        .unwrap_or(1);
 
-    if cli.all {
-        run_all_mode(&tests, &forge_binary, &r_config)?;
-    } else {
-        println!("Use --all to run all tests");
-    }
+    let ctx = RunContext {
+        runner: RunnerConfig {
+            forge_bin: forge_binary,
+            ..Default::default()
+        },
+        r_worker: (options.oracle.needs_r() && !cli.no_r_worker)
+            .then(|| RWorker::with_processes(r_config.clone(), jobs)),
+        r_cache: use_cache.then(|| RCache::new(cli.cache_dir.clone(), r_version)),
+        r_config,
+        oracle: options.oracle,
+        p_adjust: options.p_adjust,
+    };
 
-    Ok(())
+    run_tests(
+        &tests,
+        &formula_suites,
+        &registry,
+        &ctx,
+        jobs,
+        &options.reports,
+        options.allow_inconclusive,
+    )
 }
 
-fn load_tests(tests_dir: &PathBuf) -> anyhow::Result<Vec<forge_e2e_r::types::AnalyticsTestSpec>> {
-    let mut all_tests = Vec::new();
-
-    if !tests_dir.exists() {
-        anyhow::bail!("Tests directory not found: {}", tests_dir.display());
+/// Prints the selected tests grouped by suite, with their validator and tags.
+fn list_mode(cli: &Cli, selection: &Selection) -> anyhow::Result<()> {
+    let registry = SuiteRegistry::default();
+    let (tests, formula_suites) = load_selected(cli, selection, &registry, true)?;
+
+    let mut groups: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for spec in &tests {
+        let (group, validator) = match registry.select(spec) {
+            Ok(suite) => (
+                suite.name().to_string(),
+                spec.r_validator
+                    .clone()
+                    .unwrap_or_else(|| suite.validator().to_string()),
+            ),
+            Err(reason) => ("(not runnable)".to_string(), reason),
+        };
+        let tags = if spec.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", spec.tags.join(", "))
+        };
+        groups
+            .entry(group)
+            .or_default()
+            .push((spec.name.clone(), format!("{validator}{tags}")));
+    }
+    for suite in &formula_suites {
+        let (validator, _) = formula_r_params(suite);
+        groups.insert(
+            format!("{FORMULA_SUITE}: {}", suite.name),
+            suite
+                .assertions
+                .iter()
+                .map(|a| (a.name.clone(), validator.to_string()))
+                .collect(),
+        );
     }
 
-    for entry in fs::read_dir(tests_dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    for (group, mut entries) in groups {
+        entries.sort();
+        let width = entries
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        println!("{} ({})", group.bold(), entries.len());
+        for (name, details) in entries {
+            println!("  {name:<width$}  {}", details.dimmed());
+        }
+    }
+    Ok(())
+}
 
-        if path.extension().is_some_and(|e| e == "yaml") {
-            let content = fs::read_to_string(&path)?;
-            match load_analytics_tests(&content) {
-                Ok(tests) => all_tests.extend(tests),
-                Err(e) => {
-                    eprintln!("Warning: Failed to parse {}: {e}", path.display());
-                }
+/// Checks the environment and prints a fix for each problem found.
+#[allow(clippy::unnecessary_wraps)] // Result for consistent main() error handling
+fn doctor_mode(cli: &Cli) -> anyhow::Result<()> {
+    println!("{}", "forge-e2e-r doctor".bold());
+    let checks = run_checks(cli.binary.as_deref(), &r_config(cli));
+
+    for check in &checks {
+        match &check.outcome {
+            Outcome::Ok(details) => {
+                println!("  {} {} {}", "✓".green(), check.name, details.dimmed());
+            }
+            Outcome::Failed { problem, fix } => {
+                println!("  {} {}: {problem}", "✗".red(), check.name.red());
+                println!("      fix: {fix}");
+            }
+            Outcome::Skipped(reason) => {
+                println!(
+                    "  {} {} ({})",
+                    "○".yellow(),
+                    check.name.dimmed(),
+                    reason.dimmed()
+                );
             }
         }
     }
 
-    Ok(all_tests)
+    let failed = checks.iter().filter(|c| !c.passed()).count();
+    println!();
+    if failed > 0 {
+        println!("  {failed} problem(s) found");
+        std::process::exit(1);
+    }
+    println!("  {}", "Everything needed is in place".green());
+    Ok(())
 }
 
-#[allow(clippy::unnecessary_wraps)] // Result for consistent main() error handling
-fn run_all_mode(
-    tests: &[AnalyticsTestSpec],
-    forge_binary: &PathBuf,
-    r_config: &RConfig,
-) -> anyhow::Result<()> {
-    let start = Instant::now();
-    let mut results = Vec::new();
-
-    println!("{}", "Running tests...".cyan());
+/// Prints what running one test would do.
+fn explain_mode(cli: &Cli, name: &str) -> anyhow::Result<()> {
+    let registry = SuiteRegistry::default();
+    let (tests, formula_suites) = load_tests(&cli.tests)?;
+    let forge_bin = cli
+        .binary
+        .clone()
+        .or_else(find_forge_binary)
+        .unwrap_or_else(|| PathBuf::from("forge"));
+
+    let (title, invocation, r_call, note) =
+        if let Some(spec) = tests.iter().find(|t| t.name == name) {
+            let suite = registry.select(spec).map_err(anyhow::Error::msg)?;
+            let validator = spec
+                .r_validator
+                .clone()
+                .unwrap_or_else(|| suite.validator().to_string());
+            let note = if spec.iteration_counts.is_empty() {
+                suite
+                    .parse_spec(spec)
+                    .err()
+                    .map(|reason| format!("Would be skipped: {reason}"))
+            } else {
+                Some(format!(
+                    "Convergence test: forge runs once per N in {:?} (--iterations N, seed + N) \
+                 and is compared with analytic moments; R is not run",
+                    spec.iteration_counts
+                ))
+            };
+            (
+                format!("{name} (suite {})", suite.name()),
+                suite.forge_invocation(spec).map_err(anyhow::Error::msg)?,
+                suite
+                    .r_params(spec)
+                    .map(|params| (validator, params))
+                    .map_err(anyhow::Error::msg)?,
+                note,
+            )
+        } else if let Some(suite) = formula_suites
+            .iter()
+            .find(|s| s.assertions.iter().any(|a| a.name == name))
+        {
+            let (validator, params) = formula_r_params(suite);
+            (
+                format!("{name} ({FORMULA_SUITE} suite {})", suite.name),
+                formula_invocation(suite).map_err(anyhow::Error::msg)?,
+                (validator.to_string(), params),
+                Some(format!(
+                    "Runs together with the other {} assertions of {}",
+                    suite.assertions.len() - 1,
+                    suite.name
+                )),
+            )
+        } else {
+            anyhow::bail!("No test named '{name}' in {}", cli.tests.display());
+        };
+    let (validator, params) = r_call;
 
-    for test in tests {
-        let result = run_monte_carlo_test(test, forge_binary, r_config);
-        print_result(&result);
-        results.push(result);
+    println!("{}", title.bold());
+    if let Some(note) = note {
+        println!("  {}", note.yellow());
     }
+    println!();
+    println!("{}", "Forge model (<model.yaml>):".cyan());
+    print_indented(invocation.fixture.trim_end());
+    println!();
+    println!("{}", "Forge command:".cyan());
+    print_indented(&invocation.command_line(&forge_bin));
+    println!();
+    println!("{}", format!("R payload ({validator}):").cyan());
+    print_indented(&serde_json::to_string_pretty(&params)?);
+    println!();
+    println!("{}", "R command:".cyan());
+    print_indented(
+        &[
+            shell_quote(&RConfig::default().rscript_bin.display().to_string()),
+            shell_quote(&cli.validators.join(&validator).display().to_string()),
+            "--json".to_string(),
+            shell_quote(&serde_json::to_string(&params)?),
+        ]
+        .join(" "),
+    );
+    Ok(())
+}
 
-    let elapsed = start.elapsed();
+/// Records `r_expected` for every matching test under `--tests`.
+fn record_mode(cli: &Cli, selection: &Selection) -> anyhow::Result<()> {
+    let r_config = r_config(cli);
+    let r_version = check_r_available(&r_config)
+        .map_err(|_| anyhow::anyhow!("R (Rscript) not found; record mode needs R"))?;
 
-    // Summary
+    println!("{}", "forge-e2e-r record".bold());
+    println!("  R: {r_version}");
+    println!("  Tests: {}", cli.tests.display());
     println!();
-    println!("{}", "=".repeat(60));
 
-    let passed = results.iter().filter(|r| r.is_pass()).count();
-    let failed = results.iter().filter(|r| r.is_fail()).count();
-    let skipped = results
-        .iter()
-        .filter(|r| matches!(r, TestResult::Skip { .. }))
-        .count();
+    let ctx = RunContext {
+        runner: RunnerConfig::default(),
+        r_worker: (!cli.no_r_worker).then(|| RWorker::new(r_config.clone())),
+        // Recording is meant to capture what the installed R produces now
+        r_cache: None,
+        r_config,
+        oracle: Oracle::Live,
+        p_adjust: PAdjust::None,
+    };
+    let registry = SuiteRegistry::default();
+    let mut provenance = BTreeMap::new();
+    let mut results = Vec::new();
 
-    if failed == 0 {
-        println!(
-            "  {} {} passed, {} skipped in {:.2}s",
-            "PASS".green(),
-            passed.to_string().green(),
-            skipped,
-            elapsed.as_secs_f64()
-        );
-    } else {
-        println!(
-            "  {} {} passed, {} failed, {} skipped in {:.2}s",
-            "FAIL".red(),
-            passed,
-            failed.to_string().red(),
-            skipped,
-            elapsed.as_secs_f64()
-        );
+    for path in spec_files(&cli.tests)? {
+        println!("{}", path.display().to_string().cyan());
+        results.extend(record_file(
+            &path,
+            &registry,
+            &ctx,
+            selection,
+            &mut provenance,
+            print_result,
+        )?);
     }
 
-    println!("{}", "=".repeat(60));
+    let recorded = results.iter().filter(|r| r.is_pass()).count();
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r, TestResult::Error { .. }))
+        .count();
+    println!();
+    println!("  {recorded} recorded, {failed} failed");
 
     if failed > 0 {
         std::process::exit(1);
     }
-
     Ok(())
 }
 
-/// Runs a single Monte Carlo test by:
-/// 1. Creating a temporary YAML fixture for forge
-/// 2. Running forge simulate
-/// 3. Running the R validator
-/// 4. Comparing results
-#[allow(clippy::too_many_lines)]
-fn run_monte_carlo_test(
-    test: &AnalyticsTestSpec,
-    forge_binary: &PathBuf,
-    r_config: &RConfig,
-) -> TestResult {
-    // Skip tests without distribution (non-Monte Carlo tests)
-    let Some(ref distribution) = test.distribution else {
-        return TestResult::Skip {
-            name: test.name.clone(),
-            reason: "No distribution specified (not a Monte Carlo test)".to_string(),
-        };
-    };
-
-    // Build the MC.* formula based on distribution type
-    let mc_formula = match build_mc_formula(distribution, &test.params) {
-        Ok(formula) => formula,
-        Err(e) => {
-            return TestResult::Skip {
-                name: test.name.clone(),
-                reason: format!("Cannot build formula: {e}"),
-            };
-        }
-    };
+/// Deletes cache entries that can no longer be hit, or all of them.
+fn prune_mode(cli: &Cli, all: bool) -> anyhow::Result<()> {
+    let r_version = check_r_available(&r_config(cli)).ok();
+    if r_version.is_none() && !all {
+        eprintln!("Warning: R not found; keeping entries from other R versions");
+    }
 
-    // Create temporary YAML file for forge
-    let yaml_content = format!(
-        r#"_forge_version: "5.0.0"
-monte_carlo:
-  enabled: true
-  iterations: {iterations}
-  sampling: monte_carlo
-  seed: {seed}
-  outputs:
-    - variable: test_output
-      percentiles: [5, 10, 25, 50, 75, 90, 95]
-scalars:
-  test_output:
-    value: null
-    formula: "{formula}"
-"#,
-        iterations = test.iterations,
-        seed = test.seed,
-        formula = mc_formula,
+    let pruned = prune(&cli.cache_dir, &cli.validators, r_version.as_deref(), all)?;
+    println!(
+        "{}: {} removed, {} kept",
+        cli.cache_dir.display(),
+        pruned.removed,
+        pruned.kept
     );
+    Ok(())
+}
 
-    // Write to temp file
-    let temp_file = match NamedTempFile::new() {
-        Ok(f) => f,
-        Err(e) => {
-            return TestResult::Error {
-                name: test.name.clone(),
-                error: format!("Failed to create temp file: {e}"),
-            };
-        }
-    };
-
-    if let Err(e) = fs::write(temp_file.path(), &yaml_content) {
-        return TestResult::Error {
-            name: test.name.clone(),
-            error: format!("Failed to write temp file: {e}"),
-        };
+/// Lists the YAML spec files in `tests_dir`, sorted by name.
+fn spec_files(tests_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !tests_dir.exists() {
+        anyhow::bail!("Tests directory not found: {}", tests_dir.display());
     }
-
-    // Run forge simulate
-    let forge_result = run_forge_simulate(forge_binary, temp_file.path(), test.seed);
-    let forge_stats = match forge_result {
-        Ok(stats) => stats,
-        Err(e) => {
-            return TestResult::Error {
-                name: test.name.clone(),
-                error: format!("Forge failed: {e}"),
-            };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(tests_dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "yaml") {
+            files.push(path);
         }
-    };
-
-    // Run R validator
-    let r_params = RParams {
-        distribution: Some(distribution.clone()),
-        params: test.params.clone(),
-        seed: test.seed,
-        iterations: test.iterations,
-    };
+    }
+    files.sort();
+    Ok(files)
+}
 
-    let validator_script = test
-        .r_validator
-        .as_deref()
-        .unwrap_or("monte_carlo_validator.R");
-
-    let r_result = match validate_with_r(validator_script, &r_params, r_config) {
-        Ok(r) => r,
-        Err(e) => {
-            return TestResult::Error {
-                name: test.name.clone(),
-                error: format!("R validator failed: {e}"),
-            };
+/// Loads every test spec and formula suite under `tests_dir`.
+fn load_tests(tests_dir: &Path) -> anyhow::Result<(Vec<AnalyticsTestSpec>, Vec<FormulaSuite>)> {
+    let mut all_tests = Vec::new();
+    let mut formula_suites = Vec::new();
+
+    for path in spec_files(tests_dir)? {
+        let content = fs::read_to_string(&path)?;
+        let suite_name = path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let loaded = load_analytics_tests(&content).and_then(|tests| {
+            load_formula_suite(&suite_name, &content).map(|suite| (tests, suite))
+        });
+        match loaded {
+            Ok((tests, None)) if tests.is_empty() => {
+                eprintln!("Warning: {} has no tests or assumptions", path.display());
+            }
+            Ok((tests, suite)) => {
+                all_tests.extend(tests);
+                formula_suites.extend(suite);
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to parse {}: {e}", path.display());
+            }
         }
-    };
-
-    if !r_result.success {
-        return TestResult::Error {
-            name: test.name.clone(),
-            error: format!(
-                "R returned error: {}",
-                r_result.error.unwrap_or_else(|| "Unknown".to_string())
-            ),
-        };
     }
 
-    // Parse R results
-    let Some(r_stats) = parse_r_results(r_result.results.as_ref()) else {
-        return TestResult::Error {
-            name: test.name.clone(),
-            error: "Failed to parse R statistics".to_string(),
-        };
-    };
+    Ok((all_tests, formula_suites))
+}
 
-    // Get tolerance from test spec or use defaults
-    let tolerance = test
-        .tolerance
-        .as_ref()
-        .map(|t| Tolerance {
-            mean: t.mean.unwrap_or(0.01),
-            std: t.std.unwrap_or(0.05),
-            percentiles: t.percentiles.unwrap_or(0.02),
-            ..Default::default()
-        })
-        .unwrap_or_default();
+/// Runs the tests, prints them and a summary unless a report goes to
+/// stdout, then writes the reports.
+fn run_tests(
+    tests: &[AnalyticsTestSpec],
+    formula_suites: &[FormulaSuite],
+    registry: &SuiteRegistry,
+    ctx: &RunContext,
+    jobs: usize,
+    report_targets: &[ReportTarget],
+    allow_inconclusive: bool,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let console = report_targets.iter().all(|t| t.path.is_some());
 
-    // Compare results
-    compare_forge_r_results(&test.name, &forge_stats, &r_stats, &tolerance)
-}
+    if console {
+        println!("{}", format!("Running tests ({jobs} jobs)...").cyan());
+    }
 
-/// Builds the MC.* formula string for a given distribution and parameters.
-fn build_mc_formula(distribution: &str, params: &HashMap<String, f64>) -> Result<String, String> {
-    match distribution.to_lowercase().as_str() {
-        "normal" => {
-            let mean = params.get("mean").ok_or("Missing 'mean' param")?;
-            let sd = params.get("sd").ok_or("Missing 'sd' param")?;
-            Ok(format!("=MC.Normal({mean}, {sd})"))
-        }
-        "uniform" => {
-            let min = params.get("min").ok_or("Missing 'min' param")?;
-            let max = params.get("max").ok_or("Missing 'max' param")?;
-            Ok(format!("=MC.Uniform({min}, {max})"))
-        }
-        "lognormal" => {
-            let meanlog = params.get("meanlog").ok_or("Missing 'meanlog' param")?;
-            let sdlog = params.get("sdlog").ok_or("Missing 'sdlog' param")?;
-            // R uses meanlog/sdlog (log-scale parameters)
-            // Forge uses actual mean/stdev of the lognormal distribution
-            // Convert: mean = exp(meanlog + sdlog^2/2)
-            //          stdev = sqrt((exp(sdlog^2) - 1) * exp(2*meanlog + sdlog^2))
-            let mean = (meanlog + sdlog * sdlog / 2.0).exp();
-            let variance = (sdlog * sdlog).exp_m1() * (2.0 * meanlog + sdlog * sdlog).exp();
-            let stdev = variance.sqrt();
-            Ok(format!("=MC.Lognormal({mean}, {stdev})"))
-        }
-        "triangular" => {
-            let min = params.get("min").ok_or("Missing 'min' param")?;
-            let mode = params.get("mode").ok_or("Missing 'mode' param")?;
-            let max = params.get("max").ok_or("Missing 'max' param")?;
-            Ok(format!("=MC.Triangular({min}, {mode}, {max})"))
+    let mut reports = run_parallel(tests, formula_suites, registry, ctx, jobs, |report| {
+        if console {
+            print_result(&report.result);
         }
-        "pert" => {
-            let min = params.get("min").ok_or("Missing 'min' param")?;
-            let mode = params.get("mode").ok_or("Missing 'mode' param")?;
-            let max = params.get("max").ok_or("Missing 'max' param")?;
-            // Note: Forge's MC.PERT doesn't take a shape argument (uses default shape=4)
-            Ok(format!("=MC.PERT({min}, {mode}, {max})"))
-        }
-        "exponential" => {
-            // Forge doesn't support MC.Exponential yet
-            Err("Exponential distribution not supported by forge".to_string())
+    });
+
+    let corrected = adjust_reports(ctx.p_adjust, &mut reports);
+    if console && !corrected.is_empty() {
+        println!();
+        println!(
+            "{}",
+            format!("After {} correction:", ctx.p_adjust.label()).cyan()
+        );
+        for &index in &corrected {
+            print_result(&reports[index].result);
         }
-        other => Err(format!("Unsupported distribution: {other}")),
     }
-}
 
-/// Runs forge simulate and parses the JSON output.
-fn run_forge_simulate(
-    forge_binary: &PathBuf,
-    yaml_path: &std::path::Path,
-    seed: u64,
-) -> Result<ForgeStats, String> {
-    // Use a temp file for JSON output to avoid console output mixing
-    let output_file =
-        NamedTempFile::new().map_err(|e| format!("Failed to create temp output file: {e}"))?;
-    let output_path = output_file.path().with_extension("json");
-
-    let output = Command::new(forge_binary)
-        .arg("simulate")
-        .arg(yaml_path)
-        .arg("--seed")
-        .arg(seed.to_string())
-        .arg("-o")
-        .arg(&output_path)
-        .output()
-        .map_err(|e| format!("Failed to run forge: {e}"))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        return Err(format!("Forge exited with error: {stderr}\n{stdout}"));
-    }
+    let elapsed = start.elapsed();
+    let summary = Summary::new(&reports);
+    write_reports(report_targets, &reports, elapsed)?;
 
-    // Read the JSON from the output file
-    let json_content =
-        fs::read_to_string(&output_path).map_err(|e| format!("Failed to read output file: {e}"))?;
-
-    // Clean up
-    let _ = fs::remove_file(&output_path);
-
-    let json: serde_json::Value =
-        serde_json::from_str(&json_content).map_err(|e| format!("Failed to parse JSON: {e}"))?;
-
-    // Navigate to monte_carlo_results.outputs.test_output
-    let mc_results = json
-        .get("monte_carlo_results")
-        .ok_or("Missing monte_carlo_results")?;
-    let outputs = mc_results.get("outputs").ok_or("Missing outputs")?;
-    let test_output = outputs.get("test_output").ok_or("Missing test_output")?;
-
-    let mean = test_output
-        .get("mean")
-        .and_then(serde_json::Value::as_f64)
-        .ok_or("Missing mean")?;
-    let std = test_output
-        .get("std_dev")
-        .and_then(serde_json::Value::as_f64)
-        .ok_or("Missing std_dev")?;
-
-    let mut percentiles = HashMap::new();
-    if let Some(p) = test_output.get("percentiles").and_then(|v| v.as_object()) {
-        for (k, v) in p {
-            if let Some(val) = v.as_f64() {
-                // Convert p5 -> 5, p50 -> 50, etc.
-                let key = k.trim_start_matches('p').to_string();
-                percentiles.insert(key, val);
-            }
-        }
+    if console {
+        print_summary(&summary, elapsed, ctx);
     }
 
-    Ok(ForgeStats {
-        mean,
-        std,
-        percentiles,
-    })
-}
+    if !summary.succeeded(allow_inconclusive) {
+        std::process::exit(1);
+    }
 
-/// Parsed statistics from either forge or R.
-#[derive(Debug)]
-struct ForgeStats {
-    mean: f64,
-    std: f64,
-    percentiles: HashMap<String, f64>,
+    Ok(())
 }
 
-/// Parses R validator results into stats.
-fn parse_r_results(results: Option<&serde_json::Value>) -> Option<ForgeStats> {
-    let results = results?;
-
-    let mean = results.get("mean").and_then(serde_json::Value::as_f64)?;
-    let std = results
-        .get("std")
-        .or_else(|| results.get("sd"))
-        .and_then(serde_json::Value::as_f64)?;
-
-    let mut percentiles = HashMap::new();
-    if let Some(p) = results.get("percentiles").and_then(|v| v.as_object()) {
-        for (k, v) in p {
-            if let Some(val) = v.as_f64() {
-                percentiles.insert(k.clone(), val);
-            }
-        }
-    }
+fn print_summary(summary: &Summary, elapsed: Duration, ctx: &RunContext) {
+    println!();
+    println!("{}", "=".repeat(60));
 
-    Some(ForgeStats {
-        mean,
-        std,
-        percentiles,
-    })
-}
+    let Summary {
+        passed,
+        failed,
+        errors,
+        skipped,
+        inconclusive,
+    } = *summary;
 
-/// Compares forge and R results, returning Pass or Fail.
-fn compare_forge_r_results(
-    test_name: &str,
-    forge: &ForgeStats,
-    r: &ForgeStats,
-    tolerance: &Tolerance,
-) -> TestResult {
-    // Compare mean
-    if !within_tolerance(forge.mean, r.mean, tolerance.mean) {
-        let diff_pct = ((forge.mean - r.mean).abs() / r.mean.abs()) * 100.0;
-        return TestResult::Fail {
-            name: test_name.to_string(),
-            reason: format!(
-                "Mean mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
-                forge.mean,
-                r.mean,
-                diff_pct,
-                tolerance.mean * 100.0
-            ),
-        };
+    match summary.verdict() {
+        "PASS" => println!(
+            "  {} {} passed, {} skipped in {:.2}s",
+            "PASS".green(),
+            passed.to_string().green(),
+            skipped,
+            elapsed.as_secs_f64()
+        ),
+        "INCONCLUSIVE" => println!(
+            "  {} {} passed, {} inconclusive, {} skipped in {:.2}s",
+            "INCONCLUSIVE".yellow(),
+            passed,
+            inconclusive.to_string().yellow(),
+            skipped,
+            elapsed.as_secs_f64()
+        ),
+        verdict => println!(
+            "  {} {} passed, {} failed, {} errors, {} inconclusive, {} skipped in {:.2}s",
+            verdict.red(),
+            passed,
+            failed.to_string().red(),
+            errors.to_string().red(),
+            inconclusive,
+            skipped,
+            elapsed.as_secs_f64()
+        ),
     }
-
-    // Compare std
-    if !within_tolerance(forge.std, r.std, tolerance.std) {
-        let diff_pct = ((forge.std - r.std).abs() / r.std.abs()) * 100.0;
-        return TestResult::Fail {
-            name: test_name.to_string(),
-            reason: format!(
-                "Std mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
-                forge.std,
-                r.std,
-                diff_pct,
-                tolerance.std * 100.0
-            ),
-        };
+    if inconclusive > 0 {
+        println!("  Inconclusive: equivalence neither shown nor ruled out (more iterations narrow the interval)");
     }
 
-    // Compare key percentiles (5, 50, 95)
-    // Note: Different RNGs (Rust vs R) produce different random streams even with same seed.
-    // We use more lenient tolerance for percentiles since they're inherently more variable
-    // than mean/std in Monte Carlo simulations.
-    // Also use minimum absolute tolerance based on the std dev to handle edge cases.
-    let effective_pct_tolerance = tolerance.percentiles.max(0.10); // At least 10% for percentiles
-    let abs_tolerance = r.std * 0.5; // Allow half a std dev absolute difference
-
-    for pct in ["5", "50", "95"] {
-        if let (Some(&forge_val), Some(&r_val)) =
-            (forge.percentiles.get(pct), r.percentiles.get(pct))
-        {
-            let abs_diff = (forge_val - r_val).abs();
-            let rel_diff = if r_val.abs() > f64::EPSILON {
-                abs_diff / r_val.abs()
-            } else {
-                abs_diff
-            };
-
-            // Pass if within relative tolerance OR within absolute tolerance
-            if rel_diff > effective_pct_tolerance && abs_diff > abs_tolerance {
-                let diff_pct = rel_diff * 100.0;
-                return TestResult::Fail {
-                    name: test_name.to_string(),
-                    reason: format!(
-                        "P{} mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
-                        pct,
-                        forge_val,
-                        r_val,
-                        diff_pct,
-                        effective_pct_tolerance * 100.0
-                    ),
-                };
-            }
-        }
+    if let Some(cache) = &ctx.r_cache {
+        let (hits, misses) = cache.stats();
+        println!("  R results: {hits} cached, {misses} run");
     }
 
-    TestResult::Pass {
-        name: test_name.to_string(),
-        details: format!(
-            "mean={:.2} std={:.2} (within tolerance)",
-            forge.mean, forge.std
-        ),
+    println!("{}", "=".repeat(60));
+}
+
+/// Prints each line of `text` indented under a result.
+fn print_indented(text: &str) {
+    for line in text.lines() {
+        println!("      {line}");
     }
 }
 
 fn print_result(result: &TestResult) {
     match result {
-        TestResult::Pass { name, .. } => {
+        TestResult::Pass { name, details } => {
             println!("  {} {}", "✓".green(), name);
+            // Multi-line details are tables (e.g. convergence) or notes (e.g.
+            // ties) worth showing
+            if details.contains('\n') {
+                // Dimmed line by line, so each line closes its own escape
+                // sequence in logs that strip colour per line
+                for line in details.lines() {
+                    println!("{}", format!("      {line}").dimmed());
+                }
+            }
         }
         TestResult::Fail { name, reason } => {
             println!("  {} {}", "✗".red(), name.red());
-            println!("      {reason}");
+            print_indented(reason);
         }
         TestResult::Error { name, error } => {
             println!("  {} {} (error)", "✗".red(), name.red());
-            println!("      {error}");
+            print_indented(error);
         }
         TestResult::Skip { name, reason } => {
             println!("  {} {} ({})", "○".yellow(), name.dimmed(), reason.dimmed());
         }
+        TestResult::Inconclusive { name, reason } => {
+            println!("  {} {} (inconclusive)", "?".yellow(), name.yellow());
+            print_indented(reason);
+        }
     }
 }
@@ -0,0 +1,263 @@
+//! Child process execution with a timeout.
+//!
+//! stdout and stderr are drained on reader threads while the timeout is
+//! enforced, so a child that writes more than a pipe buffer (e.g. a validator
+//! returning every sample) never blocks. On expiry the child's whole process
+//! group is killed and the output read so far is kept for diagnosis. A
+//! grandchild that inherited the pipes and outlives the child gets the rest
+//! of the timeout to close them before the group is killed too.
+
+use std::fmt::Write as _;
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+/// Characters of each stream shown in a timeout message.
+const PARTIAL_OUTPUT_CHARS: usize = 500;
+
+/// Time the readers get to see the pipes close after a process group is killed.
+const KILL_GRACE: Duration = Duration::from_secs(1);
+
+/// A command that exceeded its timeout, with the output read before it was killed.
+#[derive(Debug, thiserror::Error)]
+#[error("{program} timed out after {}ms{}", timeout.as_millis(), partial_output(stdout, stderr))]
+pub struct TimedOut {
+    /// Program that was run.
+    pub program: String,
+    /// Timeout that expired.
+    pub timeout: Duration,
+    /// stdout captured before the kill.
+    pub stdout: Vec<u8>,
+    /// stderr captured before the kill.
+    pub stderr: Vec<u8>,
+}
+
+/// Runs `command` to completion, capturing stdout and stderr.
+///
+/// # Errors
+///
+/// Returns an error if the process cannot be spawned or waited on, or a
+/// [`TimedOut`] error if it runs longer than `timeout`.
+pub fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output> {
+    let program = command.get_program().to_string_lossy().into_owned();
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    new_process_group(command);
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to spawn {program}"))?;
+
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                // The group outlives the child while a grandchild is in it,
+                // so its id is still ours to kill
+                if !wait_closed(&stdout, &stderr, start + timeout) {
+                    kill_group(child.id());
+                    wait_closed(&stdout, &stderr, Instant::now() + KILL_GRACE);
+                }
+                return Ok(Output {
+                    status,
+                    stdout: stdout.finish(),
+                    stderr: stderr.finish(),
+                });
+            }
+            Ok(None) if start.elapsed() > timeout => {
+                kill_tree(&mut child);
+                return Err(TimedOut {
+                    program,
+                    timeout,
+                    stdout: stdout.snapshot(),
+                    stderr: stderr.snapshot(),
+                }
+                .into());
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => {
+                kill_tree(&mut child);
+                return Err(e).with_context(|| format!("Error waiting for {program}"));
+            }
+        }
+    }
+}
+
+/// Starts the command in a new process group so [`kill_tree`] also reaches
+/// its children (e.g. the R process behind `Rscript`).
+pub fn new_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Kills a child started with [`new_process_group`] together with its
+/// process group, and reaps it.
+pub fn kill_tree(child: &mut Child) {
+    kill_group(child.id());
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Kills the process group led by `pid`.
+fn kill_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        let _ = Command::new("kill")
+            .args(["-s", "KILL", "--", &format!("-{pid}")])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Quotes `word` for a POSIX shell unless it only has characters that need
+/// no quoting.
+#[must_use]
+pub fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// Output of one pipe, read on a background thread.
+struct Drain {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    reader: Option<JoinHandle<()>>,
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> Drain {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let reader = pipe.map(|mut pipe| {
+        let buffer = Arc::clone(&buffer);
+        thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+                buffer
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .extend_from_slice(&chunk[..n]);
+            }
+        })
+    });
+    Drain { buffer, reader }
+}
+
+/// Waits until both pipes have closed or `deadline` passes, returning
+/// whether they closed.
+fn wait_closed(stdout: &Drain, stderr: &Drain, deadline: Instant) -> bool {
+    while !(stdout.closed() && stderr.closed()) {
+        if Instant::now() > deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+impl Drain {
+    /// Whether the pipe has closed and everything in it been read.
+    fn closed(&self) -> bool {
+        self.reader.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Returns everything read, joining the reader if the pipe has closed;
+    /// a reader still blocked on an open pipe is left behind.
+    fn finish(mut self) -> Vec<u8> {
+        if self.closed() {
+            if let Some(reader) = self.reader.take() {
+                let _ = reader.join();
+            }
+        }
+        self.snapshot()
+    }
+
+    /// Returns what has been read so far without waiting.
+    fn snapshot(&self) -> Vec<u8> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Formats the tail of each non-empty stream for a timeout message.
+fn partial_output(stdout: &[u8], stderr: &[u8]) -> String {
+    let mut message = String::new();
+    for (name, bytes) in [("stdout", stdout), ("stderr", stderr)] {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let skip = text.chars().count().saturating_sub(PARTIAL_OUTPUT_CHARS);
+        let tail: String = text.chars().skip(skip).collect();
+        let ellipsis = if skip > 0 { "..." } else { "" };
+        let _ = write!(message, "\n{name} (partial): {ellipsis}{tail}");
+    }
+    message
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drains_output_larger_than_pipe_buffer() {
+        // Far more than the 64 KiB pipe buffer, written before exit
+        let output = run_with_timeout(
+            Command::new("sh").args(["-c", "head -c 1000000 /dev/zero | tr '\\0' x"]),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), 1_000_000);
+    }
+
+    #[test]
+    fn timeout_kills_group_and_keeps_partial_output() {
+        let start = Instant::now();
+        let err = run_with_timeout(
+            Command::new("sh").args(["-c", "echo started; sleep 30"]),
+            Duration::from_millis(300),
+        )
+        .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let timed_out = err.downcast_ref::<TimedOut>().unwrap();
+        assert_eq!(String::from_utf8_lossy(&timed_out.stdout).trim(), "started");
+        assert!(err.to_string().contains("stdout (partial): started"));
+    }
+
+    #[test]
+    fn grandchild_holding_the_pipes_is_killed_at_the_timeout() {
+        let start = Instant::now();
+        let output = run_with_timeout(
+            Command::new("sh").args(["-c", "sleep 30 & echo done"]),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+    }
+}
@@ -0,0 +1,256 @@
+//! On-disk cache of R validator results.
+//!
+//! A validator's output depends only on its script, its payload (which
+//! carries the seed) and the R installation, so results are stored under a
+//! SHA-256 of the script contents, the serialized payload and the
+//! `Rscript --version` string. Editing a validator changes its key, so stale
+//! results are never read; `cache prune` deletes them.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::r_validator::{RConfig, RResult};
+
+/// Default cache directory, relative to the working directory.
+pub const DEFAULT_CACHE_DIR: &str = ".r-cache";
+
+/// Cached R validator results for one R installation.
+#[derive(Debug)]
+pub struct RCache {
+    dir: PathBuf,
+    r_version: String,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// One cache file.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    validator: String,
+    /// SHA-256 of the script the result came from, for pruning.
+    script_sha256: String,
+    r_version: String,
+    result: RResult,
+}
+
+/// Outcome of [`prune`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    /// Entries still valid.
+    pub kept: usize,
+    /// Entries deleted.
+    pub removed: usize,
+}
+
+impl RCache {
+    /// Creates a cache in `dir` for results from the R reporting `r_version`.
+    #[must_use]
+    pub const fn new(dir: PathBuf, r_version: String) -> Self {
+        Self {
+            dir,
+            r_version,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the cached result for `validator` and `params`, or calls `run`
+    /// and caches what it returns.
+    ///
+    /// Only successful results are stored, so R errors are retried on the
+    /// next run. A cache that cannot be read or written behaves as a miss.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `run`, or an error if the validator script
+    /// cannot be read.
+    pub fn validate<P: Serialize + ?Sized>(
+        &self,
+        validator: &str,
+        params: &P,
+        config: &RConfig,
+        run: impl FnOnce() -> Result<RResult>,
+    ) -> Result<RResult> {
+        let script_path = config.validators_dir.join(validator);
+        let script = fs::read(&script_path)
+            .with_context(|| format!("R validator not found: {}", script_path.display()))?;
+        let params = serde_json::to_string(params)?;
+        let path = self.entry_path(&script, &params);
+
+        if let Some(entry) = read_entry(&path) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(entry.result);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let result = run()?;
+        if result.success {
+            let entry = Entry {
+                validator: validator.to_string(),
+                script_sha256: hex(&Sha256::digest(&script)),
+                r_version: self.r_version.clone(),
+                result,
+            };
+            // A failed write only costs a rerun next time
+            let _ = self.write_entry(&path, &entry);
+            return Ok(entry.result);
+        }
+        Ok(result)
+    }
+
+    /// Number of results served from the cache and computed by R so far.
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    fn entry_path(&self, script: &[u8], params: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        // Length prefixes keep the parts from running into each other
+        for part in [script, params.as_bytes(), self.r_version.as_bytes()] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        self.dir.join(format!("{}.json", hex(&hasher.finalize())))
+    }
+
+    /// Writes through a temporary file so concurrent jobs never see a
+    /// partial entry.
+    fn write_entry(&self, path: &Path, entry: &Entry) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(&serde_json::to_vec(entry)?)?;
+        file.persist(path)?;
+        Ok(())
+    }
+}
+
+fn read_entry(path: &Path) -> Option<Entry> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// Deletes the entries in `dir` that can no longer be hit, or all of them.
+///
+/// An entry is stale if its validator script was changed or removed, if it
+/// came from an R version other than `r_version` (when known), or if it
+/// cannot be read.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be read or an entry cannot be deleted.
+pub fn prune(
+    dir: &Path,
+    validators_dir: &Path,
+    r_version: Option<&str>,
+    all: bool,
+) -> Result<Pruned> {
+    let mut pruned = Pruned::default();
+    if !dir.exists() {
+        return Ok(pruned);
+    }
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let stale = all
+            || read_entry(&path).is_none_or(|entry| {
+                r_version.is_some_and(|v| v != entry.r_version)
+                    || fs::read(validators_dir.join(&entry.validator)).map_or(true, |script| {
+                        hex(&Sha256::digest(script)) != entry.script_sha256
+                    })
+            });
+        if stale {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete {}", path.display()))?;
+            pruned.removed += 1;
+        } else {
+            pruned.kept += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn result(value: u32) -> RResult {
+        RResult {
+            validator: "v.R".to_string(),
+            version: String::new(),
+            success: true,
+            results: Some(serde_json::json!({ "value": value })),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn hits_until_script_changes_then_prunes_stale_entry() {
+        let validators = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let config = RConfig {
+            validators_dir: validators.path().to_path_buf(),
+            ..Default::default()
+        };
+        let cache = RCache::new(dir.path().to_path_buf(), "R 4.3.2".to_string());
+        let params = serde_json::json!({ "seed": 42 });
+        let runs = Cell::new(0);
+        let run = || {
+            runs.set(runs.get() + 1);
+            Ok(result(runs.get()))
+        };
+
+        fs::write(validators.path().join("v.R"), "old").unwrap();
+        let first = cache.validate("v.R", &params, &config, run).unwrap();
+        let second = cache.validate("v.R", &params, &config, run).unwrap();
+        assert_eq!(first.results, second.results);
+        assert_eq!(runs.get(), 1);
+
+        // A different seed is a different entry
+        cache
+            .validate("v.R", &serde_json::json!({ "seed": 7 }), &config, run)
+            .unwrap();
+        assert_eq!(runs.get(), 2);
+
+        fs::write(validators.path().join("v.R"), "new").unwrap();
+        let third = cache.validate("v.R", &params, &config, run).unwrap();
+        assert_ne!(first.results, third.results);
+        assert_eq!(cache.stats(), (1, 3));
+
+        let pruned = prune(dir.path(), validators.path(), Some("R 4.3.2"), false).unwrap();
+        assert_eq!(
+            pruned,
+            Pruned {
+                kept: 1,
+                removed: 2
+            }
+        );
+        let pruned = prune(dir.path(), validators.path(), Some("R 4.4.0"), false).unwrap();
+        assert_eq!(
+            pruned,
+            Pruned {
+                kept: 0,
+                removed: 1
+            }
+        );
+    }
+}
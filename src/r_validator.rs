@@ -5,41 +5,21 @@
 #![allow(clippy::similar_names)]
 
 use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::time::Duration;
 
 use crate::cli_runner::{AnalyticsOutput, Stats};
+use crate::process::run_with_timeout;
 use crate::stats::Tolerance;
-
-/// Parameters to pass to an R validator.
-#[derive(Debug, Clone, Serialize)]
-pub struct RParams {
-    /// Distribution type.
-    pub distribution: Option<String>,
-    /// Numeric parameters.
-    pub params: HashMap<String, f64>,
-    /// Random seed.
-    pub seed: u64,
-    /// Number of iterations.
-    pub iterations: usize,
-}
-
-impl Default for RParams {
-    fn default() -> Self {
-        Self {
-            distribution: None,
-            params: HashMap::new(),
-            seed: 42,
-            iterations: 10_000,
-        }
-    }
-}
+use crate::types::RProvenance;
 
 /// Result from an R validator script.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RResult {
     /// Validator name.
     pub validator: String,
@@ -54,6 +34,57 @@ pub struct RResult {
     pub error: Option<String>,
 }
 
+impl RResult {
+    /// Deserializes `results` into the validator's typed result struct.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if R reported a failure, returned no results, or the
+    /// results do not have the shape of `T` (the error names the field).
+    pub fn parse_results<T: DeserializeOwned>(&self) -> Result<T> {
+        if !self.success {
+            return Err(anyhow!(
+                "{} returned error: {}",
+                self.validator,
+                self.error.as_deref().unwrap_or("Unknown")
+            ));
+        }
+        let results = self
+            .results
+            .clone()
+            .ok_or_else(|| anyhow!("{} returned no results", self.validator))?;
+        serde_json::from_value(results)
+            .with_context(|| format!("Unexpected results shape from {}", self.validator))
+    }
+}
+
+/// Sample statistics returned by `monte_carlo_validator.R`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonteCarloResults {
+    /// Sample mean.
+    pub mean: f64,
+    /// Sample standard deviation.
+    #[serde(alias = "sd")]
+    pub std: f64,
+    /// Percentiles keyed without the `p` prefix (`"5"`, `"50"`, ...).
+    #[serde(default)]
+    pub percentiles: HashMap<String, f64>,
+    /// First samples drawn (`max_samples` of them, 100 by default).
+    #[serde(default)]
+    pub samples: Vec<f64>,
+}
+
+impl From<MonteCarloResults> for Stats {
+    fn from(results: MonteCarloResults) -> Self {
+        Self {
+            mean: Some(results.mean),
+            std: Some(results.std),
+            percentiles: results.percentiles,
+            samples: results.samples,
+        }
+    }
+}
+
 /// Validation result comparing forge and R outputs.
 #[derive(Debug, Clone)]
 pub enum ValidationResult {
@@ -103,10 +134,17 @@ impl Default for RConfig {
 
 /// Runs an R validator script with the given parameters.
 ///
+/// `params` is serialized to JSON and passed via `--json`, so any payload
+/// matching the validator's documented params format can be used.
+///
 /// # Errors
 ///
 /// Returns an error if the R script cannot be found, fails to execute, or produces invalid JSON.
-pub fn validate_with_r(validator: &str, params: &RParams, config: &RConfig) -> Result<RResult> {
+pub fn validate_with_r<P: Serialize + ?Sized>(
+    validator: &str,
+    params: &P,
+    config: &RConfig,
+) -> Result<RResult> {
     let script_path = config.validators_dir.join(validator);
     if !script_path.exists() {
         return Err(anyhow!("R validator not found: {}", script_path.display()));
@@ -119,7 +157,7 @@ pub fn validate_with_r(validator: &str, params: &RParams, config: &RConfig) -> R
     command.arg("--json");
     command.arg(&params_json);
 
-    let output = execute_with_timeout(&mut command, config.timeout)
+    let output = run_with_timeout(&mut command, config.timeout)
         .with_context(|| format!("Failed to execute R validator: {validator}"))?;
 
     parse_r_output(&output, validator)
@@ -147,10 +185,13 @@ pub fn compare_results(
         };
     };
 
-    let Some(r_stats) = parse_r_stats(r_result.results.as_ref()) else {
-        return ValidationResult::Error {
-            reason: "No statistics in R output".to_string(),
-        };
+    let r_stats = match r_result.parse_results::<MonteCarloResults>() {
+        Ok(results) => Stats::from(results),
+        Err(e) => {
+            return ValidationResult::Error {
+                reason: format!("{e:#}"),
+            };
+        }
     };
 
     // Compare mean
@@ -213,57 +254,6 @@ pub fn compare_results(
     }
 }
 
-#[allow(clippy::cast_possible_truncation)]
-fn execute_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output> {
-    use std::io::{BufRead, BufReader};
-    use std::thread;
-
-    let mut child = command
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .context("Failed to spawn Rscript process")?;
-
-    let timeout_ms = timeout.as_millis() as u64;
-    let start = std::time::Instant::now();
-
-    loop {
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                let stdout = child.stdout.take().map_or_else(Vec::new, |s| {
-                    BufReader::new(s)
-                        .lines()
-                        .map_while(Result::ok)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                        .into_bytes()
-                });
-                let stderr = child.stderr.take().map_or_else(Vec::new, |s| {
-                    BufReader::new(s)
-                        .lines()
-                        .map_while(Result::ok)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                        .into_bytes()
-                });
-                return Ok(Output {
-                    status,
-                    stdout,
-                    stderr,
-                });
-            }
-            Ok(None) => {
-                if start.elapsed() > timeout {
-                    let _ = child.kill();
-                    return Err(anyhow!("R script timed out after {timeout_ms}ms"));
-                }
-                thread::sleep(Duration::from_millis(10));
-            }
-            Err(e) => return Err(anyhow!("Error waiting for R process: {e}")),
-        }
-    }
-}
-
 fn parse_r_output(output: &Output, validator: &str) -> Result<RResult> {
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -282,7 +272,12 @@ fn parse_r_output(output: &Output, validator: &str) -> Result<RResult> {
         });
     }
 
-    serde_json::from_str(&stdout).with_context(|| {
+    parse_r_json(&stdout)
+}
+
+/// Parses the JSON a validator prints in `--json` mode.
+pub(crate) fn parse_r_json(stdout: &str) -> Result<RResult> {
+    serde_json::from_str(stdout).with_context(|| {
         format!(
             "Failed to parse R validator JSON: {}",
             stdout.chars().take(200).collect::<String>()
@@ -290,39 +285,6 @@ fn parse_r_output(output: &Output, validator: &str) -> Result<RResult> {
     })
 }
 
-fn parse_r_stats(results: Option<&serde_json::Value>) -> Option<Stats> {
-    let results = results?;
-
-    let mean = results.get("mean").and_then(serde_json::Value::as_f64);
-    let std = results
-        .get("std")
-        .or_else(|| results.get("sd"))
-        .and_then(serde_json::Value::as_f64);
-
-    let percentiles = results
-        .get("percentiles")
-        .and_then(|p| p.as_object())
-        .map(|obj| {
-            obj.iter()
-                .filter_map(|(k, v)| v.as_f64().map(|f| (k.clone(), f)))
-                .collect()
-        })
-        .unwrap_or_default();
-
-    let samples = results
-        .get("samples")
-        .and_then(|s| s.as_array())
-        .map(|arr| arr.iter().filter_map(serde_json::Value::as_f64).collect())
-        .unwrap_or_default();
-
-    Some(Stats {
-        mean,
-        std,
-        percentiles,
-        samples,
-    })
-}
-
 /// Checks if R is available on the system.
 ///
 /// # Errors
@@ -360,15 +322,156 @@ pub fn check_r_package(package: &str, config: &RConfig) -> Result<bool> {
     Ok(stdout.trim() == "TRUE")
 }
 
+/// Returns the R version and the versions of the packages `validator`
+/// loads, for recording next to its results.
+///
+/// Packages are found from the script's `library()` and
+/// `requireNamespace()` calls; optional ones that are not installed are
+/// left out.
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be read or Rscript fails.
+pub fn r_provenance(validator: &str, config: &RConfig) -> Result<RProvenance> {
+    let script_path = config.validators_dir.join(validator);
+    let script = fs::read_to_string(&script_path)
+        .with_context(|| format!("R validator not found: {}", script_path.display()))?;
+
+    let packages: Vec<String> = validator_packages(&script)
+        .iter()
+        .map(|p| format!("{p:?}"))
+        .collect();
+    let expr = format!(
+        "pkgs <- c({}); versions <- lapply(setNames(pkgs, pkgs), function(p) \
+         tryCatch(as.character(packageVersion(p)), error = function(e) NULL)); \
+         cat(jsonlite::toJSON(list(r_version = R.version.string, \
+         packages = Filter(Negate(is.null), versions)), auto_unbox = TRUE))",
+        packages.join(", ")
+    );
+
+    let output = run_with_timeout(
+        Command::new(&config.rscript_bin).arg("-e").arg(&expr),
+        config.timeout,
+    )?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to query R package versions: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let versions: PackageVersions =
+        serde_json::from_slice(&output.stdout).context("Failed to parse R package versions")?;
+    Ok(RProvenance {
+        r_version: versions.r_version,
+        packages: versions.packages,
+    })
+}
+
+/// R and package versions printed by [`r_provenance`].
+#[derive(Deserialize)]
+struct PackageVersions {
+    r_version: String,
+    /// R writes an empty named list as `[]`.
+    #[serde(default, deserialize_with = "map_or_default")]
+    packages: BTreeMap<String, String>,
+}
+
+fn map_or_default<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<String, String>, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).unwrap_or_default())
+}
+
+/// R packages loaded by the validator scripts in `validators_dir`, each
+/// with the scripts that load it.
+///
+/// # Errors
+///
+/// Returns an error if the directory or a script cannot be read.
+pub fn validator_dependencies(validators_dir: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(validators_dir)
+        .with_context(|| format!("Failed to read {}", validators_dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    scripts.retain(|path| path.extension().is_some_and(|e| e == "R"));
+    scripts.sort();
+
+    let mut packages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in scripts {
+        let script = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        for package in validator_packages(&script) {
+            packages.entry(package).or_default().push(name.clone());
+        }
+    }
+    Ok(packages)
+}
+
+/// Package names a validator script loads with `library(pkg)` or
+/// `requireNamespace("pkg")`, sorted and deduplicated.
+fn validator_packages(script: &str) -> Vec<String> {
+    let mut packages: Vec<String> = script
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| {
+            ["library(", "requireNamespace("]
+                .into_iter()
+                .filter_map(move |call| {
+                    let start = line.find(call)? + call.len();
+                    let name: String = line[start..]
+                        .trim_start_matches(['"', '\''])
+                        .chars()
+                        .take_while(|c| c.is_ascii_alphanumeric() || *c == '.')
+                        .collect();
+                    (!name.is_empty()).then_some(name)
+                })
+        })
+        .collect();
+    packages.sort();
+    packages.dedup();
+    packages
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_r_params_default() {
-        let params = RParams::default();
-        assert_eq!(params.seed, 42);
-        assert_eq!(params.iterations, 10_000);
+    fn test_validator_packages() {
+        let script = r#"
+suppressPackageStartupMessages({
+  library(jsonlite)
+  library(data.tree)
+})
+# library(commented)
+if (requireNamespace("mc2d", quietly = TRUE)) {}
+"#;
+        assert_eq!(
+            validator_packages(script),
+            ["data.tree", "jsonlite", "mc2d"]
+        );
+    }
+
+    #[test]
+    fn test_parse_results_names_missing_field() {
+        let result: RResult = serde_json::from_str(
+            r#"{"validator": "monte_carlo_validator", "success": true, "results": {"mean": 1.0}}"#,
+        )
+        .unwrap();
+        let err = result.parse_results::<MonteCarloResults>().unwrap_err();
+        assert!(format!("{err:#}").contains("missing field `std`"));
+
+        let result: RResult = serde_json::from_str(
+            r#"{"validator": "monte_carlo_validator", "success": true, "results": {"mean": 1.0, "sd": 2.0}}"#,
+        )
+        .unwrap();
+        let stats = Stats::from(result.parse_results::<MonteCarloResults>().unwrap());
+        assert_eq!(stats.std, Some(2.0));
     }
 
     #[test]
@@ -0,0 +1,306 @@
+//! Persistent R worker.
+//!
+//! Keeps one `Rscript worker.R` process alive for the whole run and sends it
+//! validator requests as newline-delimited JSON over stdin/stdout, so each
+//! validator script and its packages load once instead of once per test.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::process::{kill_tree, new_process_group};
+use crate::r_validator::{parse_r_json, RConfig, RResult};
+
+/// Worker script in the validators directory.
+pub const WORKER_SCRIPT: &str = "worker.R";
+
+/// Bytes of worker stderr kept for crash reports.
+const STDERR_TAIL: usize = 4096;
+
+/// Long-lived R processes serving validator requests.
+///
+/// Each process starts when a request first needs it and is restarted after
+/// it crashes or a request times out. A request that finds its process dead
+/// is retried once on a fresh one. Concurrent requests go to separate
+/// processes, up to the pool size.
+#[derive(Debug)]
+pub struct RWorker {
+    config: RConfig,
+    processes: Vec<Mutex<Option<WorkerProcess>>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct WorkerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<String>,
+    stderr: Arc<Mutex<String>>,
+}
+
+/// Request line sent to the worker.
+#[derive(Serialize)]
+struct Request<'a> {
+    id: u64,
+    validator: &'a str,
+    /// Validator params as the JSON string passed after `--json`.
+    params: &'a str,
+}
+
+/// Response line from the worker.
+#[derive(Deserialize)]
+struct Response {
+    id: Option<u64>,
+    /// Validator output, exactly as printed in `--json` mode.
+    output: Option<String>,
+    /// Worker-level failure (validator missing, script failed to load).
+    error: Option<String>,
+}
+
+/// Why a request got no response.
+enum Failure {
+    /// The worker exited; the request may succeed on a fresh worker.
+    Exited(String),
+    /// The worker is alive but misbehaved (timeout, malformed response).
+    Other(anyhow::Error),
+}
+
+impl RWorker {
+    /// Creates a single-process worker for the validators in `config`.
+    #[must_use]
+    pub fn new(config: RConfig) -> Self {
+        Self::with_processes(config, 1)
+    }
+
+    /// Creates a worker that runs up to `processes` requests concurrently.
+    #[must_use]
+    pub fn with_processes(config: RConfig, processes: usize) -> Self {
+        Self {
+            config,
+            processes: (0..processes.max(1)).map(|_| Mutex::new(None)).collect(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Runs a validator on the worker; the counterpart of
+    /// [`validate_with_r`](crate::r_validator::validate_with_r).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the worker cannot be started, the validator cannot
+    /// be loaded, the request times out, or the output is not valid JSON.
+    pub fn validate<P: Serialize + ?Sized>(&self, validator: &str, params: &P) -> Result<RResult> {
+        let params = serde_json::to_string(params)?;
+        let response = self
+            .send(validator, &params)
+            .with_context(|| format!("R validator {validator}"))?;
+
+        if let Some(error) = response.error {
+            return Err(anyhow!("R worker: {error}"));
+        }
+        parse_r_json(&response.output.unwrap_or_default())
+    }
+
+    /// Sends one request, starting or restarting the worker as needed.
+    #[allow(clippy::significant_drop_tightening)] // the lock serializes requests
+    fn send(&self, validator: &str, params: &str) -> Result<Response> {
+        let mut process = self.acquire();
+
+        let mut retried = false;
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let line = serde_json::to_string(&Request {
+                id,
+                validator,
+                params,
+            })?;
+
+            let alive = process.as_mut().is_some_and(WorkerProcess::is_alive);
+            let mut worker = match process.take() {
+                Some(worker) if alive => worker,
+                _ => self.spawn()?,
+            };
+
+            match worker.request(&line, id, &self.config) {
+                Ok(response) => {
+                    *process = Some(worker);
+                    return Ok(response);
+                }
+                Err(Failure::Exited(_)) if !retried => retried = true,
+                Err(Failure::Exited(stderr)) => return Err(anyhow!("R worker exited: {stderr}")),
+                // Dropping the worker kills it; the next request starts a fresh one
+                Err(Failure::Other(e)) => return Err(e),
+            }
+        }
+    }
+
+    /// Locks an idle process slot, preferring already started ones, or waits
+    /// for a busy one.
+    fn acquire(&self) -> MutexGuard<'_, Option<WorkerProcess>> {
+        for slot in &self.processes {
+            match slot.try_lock() {
+                Ok(guard) => return guard,
+                Err(TryLockError::Poisoned(e)) => return e.into_inner(),
+                Err(TryLockError::WouldBlock) => {}
+            }
+        }
+        let id = self.next_id.load(Ordering::Relaxed);
+        let index = usize::try_from(id).unwrap_or_default() % self.processes.len();
+        self.processes[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn spawn(&self) -> Result<WorkerProcess> {
+        let script = self.config.validators_dir.join(WORKER_SCRIPT);
+        if !script.exists() {
+            return Err(anyhow!("R worker script not found: {}", script.display()));
+        }
+
+        let mut command = Command::new(&self.config.rscript_bin);
+        command
+            .arg(&script)
+            .arg(&self.config.validators_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        new_process_group(&mut command);
+        let mut child = command.spawn().context("Failed to spawn R worker")?;
+
+        let stdin = child.stdin.take().context("R worker stdin")?;
+        let stdout = child.stdout.take().context("R worker stdout")?;
+        let mut stderr_pipe = child.stderr.take().context("R worker stderr")?;
+
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Drain stderr so R never blocks on a full pipe; keep the tail
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let tail = Arc::clone(&stderr);
+        thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok(n @ 1..) = stderr_pipe.read(&mut buf) {
+                let mut tail = tail.lock().unwrap_or_else(PoisonError::into_inner);
+                tail.push_str(&String::from_utf8_lossy(&buf[..n]));
+                if tail.len() > STDERR_TAIL {
+                    let mut cut = tail.len() - STDERR_TAIL;
+                    while !tail.is_char_boundary(cut) {
+                        cut += 1;
+                    }
+                    tail.drain(..cut);
+                }
+            }
+        });
+
+        Ok(WorkerProcess {
+            child,
+            stdin,
+            responses,
+            stderr,
+        })
+    }
+}
+
+impl WorkerProcess {
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn stderr_tail(&self) -> String {
+        self.stderr
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn request(&mut self, line: &str, id: u64, config: &RConfig) -> Result<Response, Failure> {
+        if writeln!(self.stdin, "{line}")
+            .and_then(|()| self.stdin.flush())
+            .is_err()
+        {
+            return Err(Failure::Exited(self.stderr_tail()));
+        }
+
+        let deadline = Instant::now() + config.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = match self.responses.recv_timeout(remaining) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Failure::Other(anyhow!(
+                        "R worker timed out after {}ms",
+                        config.timeout.as_millis()
+                    )));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Failure::Exited(self.stderr_tail()));
+                }
+            };
+
+            let response: Response = serde_json::from_str(&line).map_err(|e| {
+                Failure::Other(anyhow!("Malformed R worker response ({e}): {line}"))
+            })?;
+            // Responses to earlier, abandoned requests are skipped
+            if response.id.is_none_or(|r| r == id) {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+impl Drop for WorkerProcess {
+    fn drop(&mut self) {
+        kill_tree(&mut self.child);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Stand-in for `worker.R`: answers one request (echoing its id) and
+    /// exits, or hangs for the `slow` validator.
+    const FAKE_WORKER: &str = r#"read line
+case "$line" in *slow*) exec sleep 5 ;; esac
+id=$(echo "$line" | sed 's/^{"id":\([0-9]*\).*/\1/')
+printf '{"id":%s,"output":"{\\"validator\\":\\"fake\\",\\"success\\":true,\\"results\\":{\\"id\\":%s}}"}\n' "$id" "$id"
+"#;
+
+    #[test]
+    fn restarts_after_exit_and_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(WORKER_SCRIPT), FAKE_WORKER).unwrap();
+        let worker = RWorker::new(RConfig {
+            rscript_bin: "sh".into(),
+            validators_dir: dir.path().to_path_buf(),
+            timeout: Duration::from_millis(500),
+        });
+        let params = serde_json::json!({});
+
+        let first = worker.validate("a.R", &params).unwrap();
+        assert!(first.success);
+
+        // The fake exits after each request, so this needs a restart
+        let second = worker.validate("b.R", &params).unwrap();
+        assert_ne!(first.results, second.results);
+
+        let err = worker.validate("slow.R", &params).unwrap_err();
+        assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+
+        assert!(worker.validate("c.R", &params).unwrap().success);
+    }
+}
@@ -0,0 +1,506 @@
+//! Line-based editing of a test's fields in a YAML spec file.
+//!
+//! Round-tripping through `serde_yaml` would drop every comment and the
+//! hand-written layout, so record mode edits the text instead. Only the
+//! edited field's lines change; within it, existing keys keep their position
+//! and comments, and keys the new value does not mention are left untouched.
+
+use serde_yaml_ng::{Mapping, Value};
+
+/// Indentation step used when the file gives no better hint.
+const DEFAULT_INDENT: usize = 2;
+
+/// How an existing field is updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// Update the keys present in the new value, keep the others.
+    Merge,
+    /// Replace the whole value.
+    Replace,
+}
+
+/// Sets `key` in the body of test `test` (under `tests:` or
+/// `convergence_tests:`) to `value`.
+///
+/// A missing field is inserted after the `after` field if there is one,
+/// otherwise before `tolerance:`, otherwise at the end of the test.
+///
+/// # Errors
+///
+/// Returns an error if the test cannot be found.
+pub fn set_test_field(
+    content: &str,
+    test: &str,
+    key: &str,
+    value: &Value,
+    edit: Edit,
+    after: Option<&str>,
+) -> Result<String, String> {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let (test_line, test_indent) =
+        find_test(&lines, test).ok_or_else(|| format!("Test '{test}' not found"))?;
+    let body_end = block_end(&lines, test_line + 1, test_indent);
+    let body_indent = lines[test_line + 1..body_end]
+        .iter()
+        .find(|l| is_content(l))
+        .map_or(test_indent + DEFAULT_INDENT, |l| indent(l));
+    let step = body_indent - test_indent;
+
+    let field = |name: &str| {
+        (test_line + 1..body_end)
+            .find(|&i| indent(lines[i]) == body_indent && line_key(lines[i]) == Some(name))
+    };
+
+    let insert = || {
+        let at = after
+            .and_then(field)
+            .map(|line| block_end(&lines, line + 1, body_indent))
+            .or_else(|| field("tolerance").map(|line| leading_comments(&lines, line)))
+            .unwrap_or(body_end);
+        (
+            at,
+            at,
+            format_entry(body_indent, &key_text(key), value, "", step),
+        )
+    };
+    let update = |line: usize| {
+        let end = block_end(&lines, line + 1, body_indent);
+        let replacement = match edit {
+            Edit::Merge => merge_field(lines[line], &lines[line + 1..end], value, step),
+            Edit::Replace => {
+                let (key_text, comment) = split_key_line(lines[line]);
+                format_entry(body_indent, key_text, value, comment, step)
+            }
+        };
+        (line, end, replacement)
+    };
+    let (start, end, replacement) = field(key).map_or_else(insert, update);
+
+    let mut out: Vec<String> = lines[..start].iter().map(ToString::to_string).collect();
+    out.extend(replacement);
+    out.extend(lines[end..].iter().map(ToString::to_string));
+    Ok(out.join("\n"))
+}
+
+/// Finds the line and indentation of a test's key.
+fn find_test(lines: &[&str], test: &str) -> Option<(usize, usize)> {
+    let mut section = false;
+    let mut child_indent = None;
+    for (i, line) in lines.iter().enumerate() {
+        if !is_content(line) {
+            continue;
+        }
+        let depth = indent(line);
+        if depth == 0 {
+            section = matches!(line_key(line), Some("tests" | "convergence_tests"));
+            child_indent = None;
+            continue;
+        }
+        if !section {
+            continue;
+        }
+        if depth != *child_indent.get_or_insert(depth) {
+            continue;
+        }
+        if line_key(line) == Some(test) {
+            return Some((i, depth));
+        }
+    }
+    None
+}
+
+/// Returns the index after the block that starts at `from` and is nested
+/// deeper than `parent_indent`, leaving trailing blank lines outside.
+///
+/// A comment at the parent's depth or shallower ends the block: it
+/// introduces whatever comes next.
+fn block_end(lines: &[&str], from: usize, parent_indent: usize) -> usize {
+    let mut end = lines[from..]
+        .iter()
+        .position(|l| !l.trim().is_empty() && indent(l) <= parent_indent)
+        .map_or(lines.len(), |i| from + i);
+    while end > from && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    end
+}
+
+/// Moves `line` up past the comments directly above it, so an insertion
+/// does not separate a field from its comment.
+fn leading_comments(lines: &[&str], mut line: usize) -> usize {
+    let depth = indent(lines[line]);
+    while line > 0
+        && lines[line - 1].trim_start().starts_with('#')
+        && indent(lines[line - 1]) == depth
+    {
+        line -= 1;
+    }
+    line
+}
+
+/// Rewrites one field as a merge of its old lines and `value`.
+fn merge_field(key_line: &str, children: &[&str], value: &Value, step: usize) -> Vec<String> {
+    let depth = indent(key_line);
+    let (key_text, comment) = split_key_line(key_line);
+    let has_inline_value = !line_value(key_line).is_empty();
+
+    match value {
+        Value::Mapping(map) if !has_inline_value && !is_sequence_block(children) => {
+            let mut out = vec![key_line.to_string()];
+            out.extend(merge_mapping(children, depth + step, map, step));
+            out
+        }
+        _ => format_entry(depth, key_text, value, comment, step),
+    }
+}
+
+/// One key of an existing mapping with the lines that belong to it.
+struct Entry<'a> {
+    /// Blank and comment lines above the key.
+    leading: Vec<&'a str>,
+    key_line: &'a str,
+    children: Vec<&'a str>,
+}
+
+/// Merges `map` into the mapping written in `lines`.
+fn merge_mapping(lines: &[&str], default_indent: usize, map: &Mapping, step: usize) -> Vec<String> {
+    let depth = lines
+        .iter()
+        .find(|l| is_content(l))
+        .map_or(default_indent, |l| indent(l));
+
+    // Split the old lines into entries at this depth
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut pending = Vec::new();
+    for &line in lines {
+        if is_content(line) && indent(line) == depth {
+            entries.push(Entry {
+                leading: std::mem::take(&mut pending),
+                key_line: line,
+                children: Vec::new(),
+            });
+        } else if is_content(line) && indent(line) > depth {
+            if let Some(entry) = entries.last_mut() {
+                entry.children.append(&mut pending);
+                entry.children.push(line);
+            }
+        } else {
+            pending.push(line);
+        }
+    }
+
+    let mut used = vec![false; map.len()];
+    let mut out = Vec::new();
+    for entry in &entries {
+        out.extend(entry.leading.iter().map(ToString::to_string));
+        let new = line_key(entry.key_line).and_then(|old| {
+            map.iter()
+                .enumerate()
+                .find(|(i, (k, _))| !used[*i] && k.as_str().is_some_and(|k| keys_match(old, k)))
+        });
+        if let Some((i, (_, value))) = new {
+            used[i] = true;
+            out.extend(merge_field(entry.key_line, &entry.children, value, step));
+        } else {
+            out.push(entry.key_line.to_string());
+            out.extend(entry.children.iter().map(ToString::to_string));
+        }
+    }
+    for ((key, value), used) in map.iter().zip(used) {
+        if !used {
+            out.extend(format_entry(
+                depth,
+                &key_text(&scalar(key)),
+                value,
+                "",
+                step,
+            ));
+        }
+    }
+    out.extend(pending.iter().map(ToString::to_string));
+    out
+}
+
+/// Keys match if equal, or if both are the same number (`"0.90"` is R's `"0.9"`).
+fn keys_match(old: &str, new: &str) -> bool {
+    old == new
+        || matches!(
+            (old.parse::<f64>(), new.parse::<f64>()),
+            (Ok(a), Ok(b)) if (a - b).abs() < f64::EPSILON
+        )
+}
+
+/// Formats `key: value` at `depth`, with `comment` (including the space
+/// before `#`) kept on the key line.
+fn format_entry(depth: usize, key: &str, value: &Value, comment: &str, step: usize) -> Vec<String> {
+    let pad = " ".repeat(depth);
+    if let Some(flow) = flow(value) {
+        return vec![format!("{pad}{key}: {flow}{comment}")];
+    }
+
+    let mut out = vec![format!("{pad}{key}:{comment}")];
+    match value {
+        Value::Mapping(map) => {
+            for (k, v) in map {
+                out.extend(format_entry(
+                    depth + step,
+                    &key_text(&scalar(k)),
+                    v,
+                    "",
+                    step,
+                ));
+            }
+        }
+        Value::Sequence(items) => {
+            let item_pad = " ".repeat(depth + step);
+            for item in items {
+                match (item, flow(item)) {
+                    (_, Some(flow)) => out.push(format!("{item_pad}- {flow}")),
+                    (Value::Mapping(map), None) => {
+                        let mut lines = Vec::new();
+                        for (k, v) in map {
+                            lines.extend(format_entry(
+                                depth + 2 * step,
+                                &key_text(&scalar(k)),
+                                v,
+                                "",
+                                step,
+                            ));
+                        }
+                        if let Some(first) = lines.first_mut() {
+                            *first = format!("{item_pad}- {}", first.trim_start());
+                        }
+                        out.extend(lines);
+                    }
+                    (other, None) => out.push(format!("{item_pad}- {}", scalar(other))),
+                }
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+/// Formats a value on one line if it is a scalar, an empty collection or a
+/// list of scalars (written `[a, b]` like the rest of the specs).
+fn flow(value: &Value) -> Option<String> {
+    match value {
+        Value::Mapping(map) if map.is_empty() => Some("{}".to_string()),
+        Value::Mapping(_) => None,
+        Value::Sequence(items) => {
+            let items: Option<Vec<String>> = items
+                .iter()
+                .map(|item| match item {
+                    Value::Mapping(_) | Value::Sequence(_) => None,
+                    scalar_item => Some(scalar(scalar_item)),
+                })
+                .collect();
+            items.map(|items| format!("[{}]", items.join(", ")))
+        }
+        other => Some(scalar(other)),
+    }
+}
+
+/// Formats a scalar. Strings are always double-quoted; whole numbers are
+/// written without a fraction.
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() && f.is_nan() => ".nan".to_string(),
+            Some(f) if n.is_f64() && f.is_infinite() => {
+                if f > 0.0 { ".inf" } else { "-.inf" }.to_string()
+            }
+            Some(f) if n.is_f64() => f.to_string(),
+            _ => n.to_string(),
+        },
+        Value::String(s) => serde_json::to_string(s).unwrap_or_default(),
+        Value::Sequence(_) | Value::Mapping(_) | Value::Tagged(_) => {
+            serde_yaml_ng::to_string(value)
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        }
+    }
+}
+
+/// Formats a mapping key: plain if it is a simple identifier, quoted otherwise
+/// (numbers, names with spaces, and YAML 1.1 booleans like `yes`).
+fn key_text(key: &str) -> String {
+    let key = key.trim_matches('"');
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(
+            key.to_ascii_lowercase().as_str(),
+            "yes" | "no" | "y" | "n" | "true" | "false" | "on" | "off" | "null"
+        );
+    if plain {
+        key.to_string()
+    } else {
+        serde_json::to_string(key).unwrap_or_default()
+    }
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether a line holds YAML content (not blank, not a comment).
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+fn is_sequence_block(lines: &[&str]) -> bool {
+    lines
+        .iter()
+        .find(|l| is_content(l))
+        .is_some_and(|l| l.trim_start().starts_with('-'))
+}
+
+/// Splits a `key: value` line after its indentation into the key text (as
+/// written, quotes included) and the rest after the colon.
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let key_len = match trimmed.chars().next()? {
+        quote @ ('"' | '\'') => trimmed[1..].find(quote)? + 2,
+        _ => trimmed.find(':')?,
+    };
+    let rest = trimmed[key_len..].strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then_some((&trimmed[..key_len], rest))
+}
+
+/// Returns the key of a `key: value` line, unquoted.
+fn line_key(line: &str) -> Option<&str> {
+    split_key(line).map(|(key, _)| key.trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Returns the inline value of a `key: value` line, without any comment.
+fn line_value(line: &str) -> &str {
+    let Some((_, rest)) = split_key(line) else {
+        return "";
+    };
+    comment_start(rest).map_or(rest, |i| &rest[..i]).trim()
+}
+
+/// Splits a key line into its key text and trailing comment (with the
+/// whitespace before `#`), for rewriting the value in between.
+fn split_key_line(line: &str) -> (&str, &str) {
+    let Some((key, rest)) = split_key(line) else {
+        return (line.trim(), "");
+    };
+    let comment = comment_start(rest).map_or("", |i| {
+        let before = rest[..i].trim_end().len();
+        &rest[before..]
+    });
+    (key, comment)
+}
+
+/// Finds the `#` that starts a comment, outside quotes.
+fn comment_start(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') if previous.is_whitespace() => return Some(i),
+            _ => {}
+        }
+        previous = c;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SPEC: &str = r#"_r_validator: "bootstrap_validator.R"
+
+tests:
+  # First test
+  first:
+    data: [1, 2, 3]
+    r_expected:
+      # Hand-computed
+      original_estimate: 0.031  # from the paper
+      confidence_intervals:
+        "0.90":
+          lower: 0.006
+          upper: 0.056
+      note: kept
+    tolerance:
+      mean: 0.02
+
+  # Second test
+  second:
+    data: [4]
+    # Tolerance comment
+    tolerance:
+      mean: 0.02
+"#;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml_ng::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_comments_order_and_unknown_keys() {
+        let value = yaml(
+            r#"{original_estimate: 0.0312, mean: 0.03,
+                confidence_intervals: {"0.9": {lower: 0.0061, upper: 0.0559}}}"#,
+        );
+        let updated =
+            set_test_field(SPEC, "first", "r_expected", &value, Edit::Merge, None).unwrap();
+
+        assert_eq!(
+            updated,
+            SPEC.replace(
+                "      original_estimate: 0.031  # from the paper\n",
+                "      original_estimate: 0.0312  # from the paper\n",
+            )
+            .replace("lower: 0.006\n", "lower: 0.0061\n")
+            .replace("upper: 0.056\n", "upper: 0.0559\n")
+            .replace("      note: kept\n", "      note: kept\n      mean: 0.03\n")
+        );
+    }
+
+    #[test]
+    fn missing_fields_go_after_anchor_or_before_tolerance() {
+        let expected = yaml("{posterior: {\"yes\": 0.9, \"no\": 0.1}, decision_path: [a, b]}");
+        let updated =
+            set_test_field(SPEC, "second", "r_expected", &expected, Edit::Merge, None).unwrap();
+        let provenance = yaml("{r_version: R version 4.4.1, packages: {boot: 1.3-30}}");
+        let updated = set_test_field(
+            &updated,
+            "second",
+            "r_provenance",
+            &provenance,
+            Edit::Replace,
+            Some("r_expected"),
+        )
+        .unwrap();
+
+        assert!(updated.contains(
+            r#"    data: [4]
+    r_expected:
+      posterior:
+        "yes": 0.9
+        "no": 0.1
+      decision_path: ["a", "b"]
+    r_provenance:
+      r_version: "R version 4.4.1"
+      packages:
+        boot: "1.3-30"
+    # Tolerance comment
+    tolerance:"#
+        ));
+        assert!(set_test_field(SPEC, "third", "r_expected", &expected, Edit::Merge, None).is_err());
+    }
+}
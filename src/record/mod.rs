@@ -0,0 +1,145 @@
+//! Record mode.
+//!
+//! Runs the R validators and writes their results back into the spec files
+//! as `r_expected`, with the R and package versions used as `r_provenance`.
+//! Files are edited in place (see [`edit`]), so comments and layout survive.
+
+pub mod edit;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::r_validator::r_provenance;
+use crate::select::Selection;
+use crate::suite::{run_r, RunContext, SuiteRegistry};
+use crate::types::{load_analytics_tests, AnalyticsTestSpec, RProvenance, TestResult};
+use edit::{set_test_field, Edit};
+
+/// Records `r_expected` for the matching tests in one spec file and
+/// rewrites it if anything changed.
+///
+/// `report` is called for each matching test: Pass when its values were
+/// recorded, Skip or Error otherwise. Provenance is looked up once per
+/// validator and shared through `provenance`.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, parsed or written.
+pub fn record_file(
+    path: &Path,
+    registry: &SuiteRegistry,
+    ctx: &RunContext,
+    selection: &Selection,
+    provenance: &mut BTreeMap<String, RProvenance>,
+    mut report: impl FnMut(&TestResult),
+) -> anyhow::Result<Vec<TestResult>> {
+    let original =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut specs = load_analytics_tests(&original)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    specs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut content = original.clone();
+    let mut results = Vec::new();
+    for spec in &specs {
+        // Convergence tests compare forge with analytic moments, not R
+        if !spec.iteration_counts.is_empty() {
+            continue;
+        }
+        if registry.select(spec).is_err() || !selection.matches_test(spec, registry) {
+            continue;
+        }
+
+        let result = match record_test(&content, spec, registry, ctx, provenance) {
+            Ok(updated) => {
+                let details = if updated == content {
+                    "r_expected unchanged"
+                } else {
+                    "r_expected recorded"
+                };
+                content = updated;
+                TestResult::Pass {
+                    name: spec.name.clone(),
+                    details: details.to_string(),
+                }
+            }
+            Err(NotRecorded::Skipped(reason)) => TestResult::Skip {
+                name: spec.name.clone(),
+                reason,
+            },
+            Err(NotRecorded::Failed(error)) => TestResult::Error {
+                name: spec.name.clone(),
+                error,
+            },
+        };
+        report(&result);
+        results.push(result);
+    }
+
+    if content != original {
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(results)
+}
+
+/// Why a test was not recorded.
+enum NotRecorded {
+    Skipped(String),
+    Failed(String),
+}
+
+/// Runs R for one test and returns `content` with its values written in.
+fn record_test(
+    content: &str,
+    spec: &AnalyticsTestSpec,
+    registry: &SuiteRegistry,
+    ctx: &RunContext,
+    provenance: &mut BTreeMap<String, RProvenance>,
+) -> Result<String, NotRecorded> {
+    let suite = registry.select(spec).map_err(NotRecorded::Skipped)?;
+    suite.parse_spec(spec).map_err(NotRecorded::Skipped)?;
+
+    let r_json = run_r(suite, spec, ctx).map_err(NotRecorded::Failed)?;
+    let expected = suite.record(spec, &r_json).map_err(NotRecorded::Failed)?;
+
+    let validator = spec
+        .r_validator
+        .clone()
+        .unwrap_or_else(|| suite.validator().to_string());
+    let versions = if let Some(versions) = provenance.get(&validator) {
+        versions.clone()
+    } else {
+        let versions = r_provenance(&validator, &ctx.r_config)
+            .map_err(|e| NotRecorded::Failed(format!("Failed to read R versions: {e:#}")))?;
+        provenance.insert(validator, versions.clone());
+        versions
+    };
+
+    let content = set_test_field(
+        content,
+        &spec.name,
+        "r_expected",
+        &to_yaml(&expected)?,
+        Edit::Merge,
+        None,
+    )
+    .map_err(NotRecorded::Failed)?;
+    set_test_field(
+        &content,
+        &spec.name,
+        "r_provenance",
+        &to_yaml(&versions)?,
+        Edit::Replace,
+        Some("r_expected"),
+    )
+    .map_err(NotRecorded::Failed)
+}
+
+fn to_yaml<T: Serialize>(value: &T) -> Result<serde_yaml_ng::Value, NotRecorded> {
+    serde_yaml_ng::to_value(value)
+        .map_err(|e| NotRecorded::Failed(format!("Failed to serialize: {e}")))
+}
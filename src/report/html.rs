@@ -0,0 +1,471 @@
+//! Self-contained HTML report.
+//!
+//! One page with inline CSS and SVG, so it opens from a CI artifact without
+//! network access. Tests that recorded samples get a histogram and an
+//! empirical CDF of the forge and R samples, with the KS statistic and the
+//! compared quantiles marked; every test lists its comparisons.
+
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_sign_loss)]
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use super::{xml_escape as escape, Summary};
+use crate::stats::gof::GofTest;
+use crate::stats::{ecdf_max_gap, Comparison, Samples};
+use crate::types::{TestReport, TestResult};
+
+/// Plot size in SVG units.
+const WIDTH: f64 = 460.0;
+const HEIGHT: f64 = 240.0;
+/// Space left of, right of and below the plot area.
+const MARGIN: f64 = 30.0;
+/// Space above the plot area, for the title and marker labels.
+const TOP: f64 = 36.0;
+/// Histogram bins across the combined range of both samples.
+const BINS: usize = 30;
+
+const FORGE_COLOR: &str = "#1f77b4";
+const R_COLOR: &str = "#ff7f0e";
+const GAP_COLOR: &str = "#d62728";
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+.summary { font-size: 1.1em; }
+details.test { border: 1px solid #ddd; border-left-width: 6px; margin: 0.5em 0; padding: 0.3em 0.8em; }
+details.pass { border-left-color: #2ca02c; }
+details.fail, details.error { border-left-color: #d62728; }
+details.skip { border-left-color: #bbb; }
+details.inconclusive { border-left-color: #ff9f1c; }
+summary { cursor: pointer; }
+.status { font-weight: bold; display: inline-block; min-width: 4em; }
+.meta { color: #777; font-size: 0.9em; margin-left: 1em; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.plots { display: flex; flex-wrap: wrap; gap: 1em; }
+table.metrics { border-collapse: collapse; margin: 0.5em 0; }
+table.metrics th, table.metrics td { border: 1px solid #ddd; padding: 0.2em 0.6em; text-align: right; }
+table.metrics td:first-child { text-align: left; }
+tr.failed td { background: #fde8e8; }
+";
+
+/// Writes the HTML report.
+///
+/// # Errors
+///
+/// Returns an error if writing to `out` fails.
+pub fn write_html(
+    reports: &[TestReport],
+    elapsed: Duration,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let summary = Summary::new(reports);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, r#"<html lang="en">"#)?;
+    writeln!(
+        out,
+        r#"<head><meta charset="utf-8"><title>forge-e2e-r report</title>"#
+    )?;
+    writeln!(out, "<style>{STYLE}</style></head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>forge-e2e-r</h1>")?;
+    writeln!(
+        out,
+        r#"<p class="summary">{} passed, {} failed, {} errors, {} skipped in {:.2} s</p>"#,
+        summary.passed,
+        summary.failed,
+        summary.errors,
+        summary.skipped,
+        elapsed.as_secs_f64()
+    )?;
+    for report in reports {
+        write_test(report, out)?;
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+fn write_test(report: &TestReport, out: &mut impl Write) -> io::Result<()> {
+    let (status, message) = match &report.result {
+        TestResult::Pass { details, .. } => ("pass", details),
+        TestResult::Fail { reason, .. } => ("fail", reason),
+        TestResult::Error { error, .. } => ("error", error),
+        TestResult::Skip { reason, .. } => ("skip", reason),
+        TestResult::Inconclusive { reason, .. } => ("inconclusive", reason),
+    };
+    // Failures start expanded
+    let open = if matches!(status, "fail" | "error" | "inconclusive") {
+        " open"
+    } else {
+        ""
+    };
+
+    writeln!(out, r#"<details class="test {status}"{open}>"#)?;
+    writeln!(
+        out,
+        r#"<summary><span class="status">{}</span> {} <span class="meta">{} · {:.3} s</span></summary>"#,
+        status.to_uppercase(),
+        escape(report.result.name()),
+        escape(&report.suite),
+        report.duration.as_secs_f64()
+    )?;
+    if !message.is_empty() {
+        writeln!(out, "<pre>{}</pre>", escape(message))?;
+    }
+    if let Some(samples) = &report.samples {
+        write_plots(samples, &report.comparisons, out)?;
+    }
+    if !report.comparisons.is_empty() {
+        write_metrics(&report.comparisons, out)?;
+    }
+    if !report.gof.is_empty() {
+        write_gof(&report.gof, out)?;
+    }
+    writeln!(out, "</details>")
+}
+
+fn write_metrics(comparisons: &[Comparison], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, r#"<table class="metrics">"#)?;
+    writeln!(
+        out,
+        "<tr><th>Metric</th><th>forge</th><th>R</th><th>Relative diff</th><th>Tolerance</th><th>z / p</th><th>Result</th></tr>"
+    )?;
+    for c in comparisons {
+        let tolerance = match (c.tolerance, c.absolute_tolerance) {
+            (Some(rel), Some(abs)) => format!("{:.3}% or ±{abs:.6}", rel * 100.0),
+            (Some(rel), None) => format!("{:.3}%", rel * 100.0),
+            (None, Some(abs)) => format!("±{abs:.6}"),
+            (None, None) => String::new(),
+        };
+        let z = match (c.z_score, c.p_value, c.adjusted_p_value) {
+            (Some(z), _, _) => format!("z={z:.2}"),
+            (None, Some(p), Some(adjusted)) => format!("p={p:.4}, adjusted p={adjusted:.4}"),
+            (None, Some(p), None) => format!("p={p:.4}"),
+            (None, None, _) => String::new(),
+        };
+        let (class, result) = if c.passed {
+            ("passed", "pass")
+        } else {
+            ("failed", "FAIL")
+        };
+        writeln!(
+            out,
+            r#"<tr class="{class}"><td>{}</td><td>{:.6}</td><td>{:.6}</td><td>{:.3}%</td><td>{tolerance}</td><td>{z}</td><td>{result}</td></tr>"#,
+            escape(&c.metric),
+            c.forge,
+            c.r,
+            c.relative_diff * 100.0
+        )?;
+    }
+    writeln!(out, "</table>")
+}
+
+fn write_gof(tests: &[GofTest], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, r#"<table class="metrics">"#)?;
+    writeln!(
+        out,
+        "<tr><th>Test</th><th>Statistic</th><th>p-value</th><th>Adjusted p</th><th>Min p-value</th><th>At</th><th>Result</th></tr>"
+    )?;
+    for t in tests {
+        let (class, result) = if t.passed {
+            ("passed", "pass")
+        } else {
+            ("failed", "FAIL")
+        };
+        writeln!(
+            out,
+            r#"<tr class="{class}"><td>{}</td><td>{:.4}</td><td>{:.4}</td><td>{}</td><td>{}</td><td>{}</td><td>{result}</td></tr>"#,
+            escape(&t.name),
+            t.statistic,
+            t.p_value,
+            t.adjusted_p_value
+                .map_or_else(String::new, |p| format!("{p:.4}")),
+            t.min_p_value,
+            t.at.map_or_else(String::new, |at| format!("{at:.6}"))
+        )?;
+    }
+    writeln!(out, "</table>")
+}
+
+/// Maps sample values and probabilities to SVG coordinates.
+struct Frame {
+    lo: f64,
+    hi: f64,
+}
+
+impl Frame {
+    /// Spans every value given, padded so no point sits on the border.
+    fn spanning(values: impl Iterator<Item = f64>) -> Option<Self> {
+        let (lo, hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+        if !lo.is_finite() || !hi.is_finite() {
+            return None;
+        }
+        let pad = if hi > lo { (hi - lo) * 0.02 } else { 0.5 };
+        Some(Self {
+            lo: lo - pad,
+            hi: hi + pad,
+        })
+    }
+
+    fn x(&self, value: f64) -> f64 {
+        ((value - self.lo) / (self.hi - self.lo)).mul_add(WIDTH - MARGIN - MARGIN, MARGIN)
+    }
+
+    /// `fraction` of the plot height above the x axis.
+    fn y(fraction: f64) -> f64 {
+        fraction.mul_add(-(HEIGHT - MARGIN - TOP), HEIGHT - MARGIN)
+    }
+
+    /// Opens an `<svg>` with a title and a labelled x axis.
+    fn open(&self, title: &str, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" role="img" aria-label="{title}" font-size="11">"#
+        )?;
+        writeln!(
+            out,
+            r#"<text x="{MARGIN}" y="14" font-weight="bold">{title}</text>"#
+        )?;
+        let (left, right, axis) = (MARGIN, WIDTH - MARGIN, Self::y(0.0));
+        writeln!(
+            out,
+            r##"<line x1="{left}" y1="{axis}" x2="{right}" y2="{axis}" stroke="#444"/>"##
+        )?;
+        for (value, anchor) in [
+            (self.lo, "start"),
+            (f64::midpoint(self.lo, self.hi), "middle"),
+            (self.hi, "end"),
+        ] {
+            writeln!(
+                out,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="{anchor}">{}</text>"#,
+                self.x(value),
+                axis + 14.0,
+                tick_label(value)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws a dashed vertical line at `value` with `label`, if any, above it.
+    fn marker(&self, value: f64, label: &str, color: &str, out: &mut impl Write) -> io::Result<()> {
+        let x = self.x(value);
+        writeln!(
+            out,
+            r#"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{:.1}" stroke="{color}" stroke-dasharray="4 3"/>"#,
+            Self::y(0.0),
+            Self::y(1.0)
+        )?;
+        if label.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            out,
+            r#"<text x="{x:.1}" y="{:.1}" text-anchor="middle" fill="{color}">{}</text>"#,
+            Self::y(1.0) - 3.0,
+            escape(label)
+        )
+    }
+
+    /// Marks the forge and R values of each compared quantile.
+    fn quantiles(&self, markers: &[&Comparison], out: &mut impl Write) -> io::Result<()> {
+        for marker in markers {
+            self.marker(marker.forge, &marker.metric, FORGE_COLOR, out)?;
+            self.marker(marker.r, "", R_COLOR, out)?;
+        }
+        Ok(())
+    }
+}
+
+fn tick_label(value: f64) -> String {
+    if value.abs() >= 1000.0 || value.fract().abs() < f64::EPSILON {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+fn write_plots(
+    samples: &Samples,
+    comparisons: &[Comparison],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let finite = |values: &[f64]| {
+        let mut finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        finite.sort_by(f64::total_cmp);
+        finite
+    };
+    let (forge, r) = (finite(&samples.forge), finite(&samples.r));
+    let markers: Vec<&Comparison> = comparisons
+        .iter()
+        .filter(|c| samples.quantiles.contains(&c.metric))
+        .collect();
+    let values = forge
+        .iter()
+        .chain(&r)
+        .copied()
+        .chain(markers.iter().flat_map(|m| [m.forge, m.r]));
+    let Some(frame) = Frame::spanning(values.filter(|v| v.is_finite())) else {
+        return Ok(());
+    };
+
+    writeln!(out, r#"<div class="plots">"#)?;
+    histogram(&frame, &forge, &r, &markers, out)?;
+    ecdf(&frame, &forge, &r, &markers, out)?;
+    writeln!(out, "</div>")?;
+    writeln!(
+        out,
+        r#"<p class="meta"><span style="color:{FORGE_COLOR}">■</span> forge ({} samples) <span style="color:{R_COLOR}">■</span> R ({} samples); dashed lines mark the compared quantiles</p>"#,
+        forge.len(),
+        r.len()
+    )
+}
+
+fn histogram(
+    frame: &Frame,
+    forge: &[f64],
+    r: &[f64],
+    markers: &[&Comparison],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let width = (frame.hi - frame.lo) / BINS as f64;
+    // Densities, so samples of different sizes share a scale
+    let density = |sample: &[f64]| {
+        let mut counts = [0usize; BINS];
+        for &v in sample {
+            let bin = ((v - frame.lo) / width) as usize;
+            counts[bin.min(BINS - 1)] += 1;
+        }
+        counts.map(|c| c as f64 / (sample.len().max(1) as f64 * width))
+    };
+    let (forge_density, r_density) = (density(forge), density(r));
+    let max = forge_density
+        .iter()
+        .chain(&r_density)
+        .fold(0.0_f64, |max, &d| max.max(d));
+
+    frame.open("Histogram", out)?;
+    for (densities, color) in [(forge_density, FORGE_COLOR), (r_density, R_COLOR)] {
+        for (i, d) in densities.iter().enumerate() {
+            if *d <= 0.0 {
+                continue;
+            }
+            let x = frame.x((i as f64).mul_add(width, frame.lo));
+            let top = Frame::y(d / max);
+            writeln!(
+                out,
+                r#"<rect x="{x:.1}" y="{top:.1}" width="{:.1}" height="{:.1}" fill="{color}" fill-opacity="0.45"/>"#,
+                frame.x(frame.lo + width) - frame.x(frame.lo),
+                Frame::y(0.0) - top
+            )?;
+        }
+    }
+    frame.quantiles(markers, out)?;
+    writeln!(out, "</svg>")
+}
+
+fn ecdf(
+    frame: &Frame,
+    forge: &[f64],
+    r: &[f64],
+    markers: &[&Comparison],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    frame.open("Empirical CDF", out)?;
+    for (sample, color) in [(forge, FORGE_COLOR), (r, R_COLOR)] {
+        if sample.is_empty() {
+            continue;
+        }
+        let n = sample.len() as f64;
+        let mut path = format!("M{:.1},{:.1}", frame.x(frame.lo), Frame::y(0.0));
+        for (i, v) in sample.iter().enumerate() {
+            let _ = write!(
+                path,
+                " H{:.1} V{:.1}",
+                frame.x(*v),
+                Frame::y((i + 1) as f64 / n)
+            );
+        }
+        let _ = write!(path, " H{:.1}", frame.x(frame.hi));
+        writeln!(
+            out,
+            r#"<path d="{path}" fill="none" stroke="{color}" stroke-width="1.5"/>"#
+        )?;
+    }
+    frame.quantiles(markers, out)?;
+
+    if let Some(gap) = ecdf_max_gap(forge, r) {
+        let at =
+            |sample: &[f64]| sample.partition_point(|&v| v <= gap.at) as f64 / sample.len() as f64;
+        let x = frame.x(gap.at);
+        writeln!(
+            out,
+            r#"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{:.1}" stroke="{GAP_COLOR}" stroke-width="2.5"/>"#,
+            Frame::y(at(forge)),
+            Frame::y(at(r))
+        )?;
+        writeln!(
+            out,
+            r#"<text x="{:.1}" y="14" text-anchor="end" fill="{GAP_COLOR}">KS D = {:.4} at {}</text>"#,
+            WIDTH - MARGIN,
+            gap.d,
+            tick_label(gap.at)
+        )?;
+    }
+    writeln!(out, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plots_samples_and_lists_metrics() {
+        let comparison = |metric: &str, forge: f64, r: f64, passed| Comparison {
+            metric: metric.to_string(),
+            forge,
+            r,
+            relative_diff: (forge - r).abs() / r,
+            tolerance: Some(0.1),
+            absolute_tolerance: None,
+            z_score: None,
+            p_value: None,
+            alpha: None,
+            adjusted_p_value: None,
+            passed,
+        };
+        let report = TestReport {
+            result: TestResult::Fail {
+                name: "shape<skewed>".to_string(),
+                reason: "P95 mismatch".to_string(),
+            },
+            suite: "monte_carlo".to_string(),
+            duration: Duration::from_millis(250),
+            comparisons: vec![
+                comparison("mean", 2.5, 2.5, true),
+                comparison("p95", 4.0, 3.0, false),
+            ],
+            gof: Vec::new(),
+            samples: Some(Samples {
+                forge: vec![1.0, 2.0, 3.0, 4.0],
+                r: vec![1.0, 2.0, 2.5, 3.0],
+                quantiles: vec!["p95".to_string()],
+            }),
+        };
+
+        let mut out = Vec::new();
+        write_html(&[report], Duration::from_secs(1), &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+
+        assert!(html.contains(r#"<details class="test fail" open>"#));
+        assert!(html.contains("shape&lt;skewed&gt;"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("KS D = 0.2500 at 3"));
+        assert!(html.contains(">p95</text>"));
+        assert!(html.contains(r#"<tr class="failed"><td>p95</td>"#));
+        assert!(!html.contains("<script"));
+    }
+}
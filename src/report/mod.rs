@@ -0,0 +1,586 @@
+//! Run reports: `JUnit` XML, JSON, TAP and HTML.
+//!
+//! Every format carries each test's outcome, suite and duration, and the
+//! forge and R values it compared with the relative difference and the
+//! tolerance applied. The HTML report also plots the samples of stochastic
+//! tests (see [`html`]).
+
+pub mod html;
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::stats::gof::GofTest;
+use crate::stats::Comparison;
+use crate::types::{TestReport, TestResult};
+
+/// Report format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// `JUnit` XML, as read by CI test dashboards.
+    Junit,
+    /// JSON document with a summary and every test.
+    Json,
+    /// Test Anything Protocol, version 13.
+    Tap,
+    /// Self-contained HTML page with inline SVG plots.
+    Html,
+}
+
+/// Where to write one report: `FORMAT=PATH`, or `FORMAT` for stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTarget {
+    /// Report format.
+    pub format: ReportFormat,
+    /// Output file; `None` writes to stdout.
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for ReportTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once('=')
+            .map_or((s, None), |(format, path)| (format, Some(path)));
+        let format = match format {
+            "junit" => ReportFormat::Junit,
+            "json" => ReportFormat::Json,
+            "tap" => ReportFormat::Tap,
+            "html" => ReportFormat::Html,
+            other => {
+                return Err(format!(
+                    "unknown report format '{other}' (expected junit, json, tap or html)"
+                ))
+            }
+        };
+        match path {
+            Some("") => Err(format!("empty path in '{s}'")),
+            path => Ok(Self {
+                format,
+                path: path.map(PathBuf::from),
+            }),
+        }
+    }
+}
+
+/// Outcome counts of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    /// Tests that could not run to a verdict (forge or R failed).
+    pub errors: usize,
+    pub skipped: usize,
+    /// Tests whose equivalence test could not decide.
+    pub inconclusive: usize,
+}
+
+impl Summary {
+    /// Counts the outcomes of `reports`.
+    pub fn new<'a>(reports: impl IntoIterator<Item = &'a TestReport>) -> Self {
+        reports
+            .into_iter()
+            .fold(Self::default(), |mut summary, report| {
+                match report.result {
+                    TestResult::Pass { .. } => summary.passed += 1,
+                    TestResult::Fail { .. } => summary.failed += 1,
+                    TestResult::Error { .. } => summary.errors += 1,
+                    TestResult::Skip { .. } => summary.skipped += 1,
+                    TestResult::Inconclusive { .. } => summary.inconclusive += 1,
+                }
+                summary
+            })
+    }
+
+    /// Total number of tests.
+    #[must_use]
+    pub const fn total(&self) -> usize {
+        self.passed + self.failed + self.errors + self.skipped + self.inconclusive
+    }
+
+    /// Headline verdict: `FAIL` if a test failed or ended in an error,
+    /// `INCONCLUSIVE` if an equivalence test could not decide, else `PASS`.
+    #[must_use]
+    pub const fn verdict(&self) -> &'static str {
+        if self.failed > 0 || self.errors > 0 {
+            "FAIL"
+        } else if self.inconclusive > 0 {
+            "INCONCLUSIVE"
+        } else {
+            "PASS"
+        }
+    }
+
+    /// Whether the run passed: no test failed or ended in an error, and none
+    /// was inconclusive unless `allow_inconclusive` is set.
+    #[must_use]
+    pub const fn succeeded(&self, allow_inconclusive: bool) -> bool {
+        self.failed == 0 && self.errors == 0 && (allow_inconclusive || self.inconclusive == 0)
+    }
+}
+
+/// Writes every target, creating parent directories of report files.
+///
+/// # Errors
+///
+/// Returns an error if a report cannot be written.
+pub fn write_reports(
+    targets: &[ReportTarget],
+    reports: &[TestReport],
+    elapsed: Duration,
+) -> anyhow::Result<()> {
+    for target in targets {
+        match &target.path {
+            Some(path) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut out = BufWriter::new(
+                    File::create(path)
+                        .with_context(|| format!("Failed to create {}", path.display()))?,
+                );
+                write_report(target.format, reports, elapsed, &mut out)
+                    .and_then(|()| out.flush())
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            None => write_report(target.format, reports, elapsed, &mut io::stdout().lock())?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes one report in `format`.
+///
+/// # Errors
+///
+/// Returns an error if writing to `out` fails.
+pub fn write_report(
+    format: ReportFormat,
+    reports: &[TestReport],
+    elapsed: Duration,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Junit => write_junit(reports, elapsed, out),
+        ReportFormat::Json => write_json(reports, elapsed, out),
+        ReportFormat::Tap => write_tap(reports, out),
+        ReportFormat::Html => html::write_html(reports, elapsed, out),
+    }
+}
+
+fn write_json(reports: &[TestReport], elapsed: Duration, out: &mut impl Write) -> io::Result<()> {
+    #[derive(Serialize)]
+    struct JsonReport<'a> {
+        summary: Summary,
+        duration_secs: f64,
+        tests: &'a [TestReport],
+    }
+
+    let report = JsonReport {
+        summary: Summary::new(reports),
+        duration_secs: elapsed.as_secs_f64(),
+        tests: reports,
+    };
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)
+}
+
+fn write_junit(reports: &[TestReport], elapsed: Duration, out: &mut impl Write) -> io::Result<()> {
+    // Group by suite, in order of first appearance
+    let mut suites: Vec<(&str, Vec<&TestReport>)> = Vec::new();
+    for report in reports {
+        match suites.iter_mut().find(|(name, _)| *name == report.suite) {
+            Some((_, group)) => group.push(report),
+            None => suites.push((&report.suite, vec![report])),
+        }
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="forge-e2e-r" {} time="{:.3}">"#,
+        junit_counts(&Summary::new(reports)),
+        elapsed.as_secs_f64()
+    )?;
+    for (name, group) in suites {
+        let time: Duration = group.iter().map(|r| r.duration).sum();
+        writeln!(
+            out,
+            r#"  <testsuite name="{}" {} time="{:.3}">"#,
+            xml_escape(name),
+            junit_counts(&Summary::new(group.iter().copied())),
+            time.as_secs_f64()
+        )?;
+        for report in group {
+            write_testcase(report, out)?;
+        }
+        writeln!(out, "  </testsuite>")?;
+    }
+    writeln!(out, "</testsuites>")
+}
+
+fn junit_counts(summary: &Summary) -> String {
+    format!(
+        r#"tests="{}" failures="{}" errors="{}" skipped="{}""#,
+        summary.total(),
+        summary.failed,
+        summary.errors,
+        summary.skipped + summary.inconclusive
+    )
+}
+
+fn write_testcase(report: &TestReport, out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"    <testcase name="{}" classname="{}" time="{:.3}">"#,
+        xml_escape(report.result.name()),
+        xml_escape(&report.suite),
+        report.duration.as_secs_f64()
+    )?;
+
+    if !report.comparisons.is_empty() || !report.gof.is_empty() {
+        writeln!(out, "      <properties>")?;
+        let properties = report
+            .comparisons
+            .iter()
+            .map(|c| (c.metric.as_str(), describe(c)))
+            .chain(
+                report
+                    .gof
+                    .iter()
+                    .map(|t| (t.name.as_str(), describe_gof(t))),
+            );
+        for (name, value) in properties {
+            writeln!(
+                out,
+                r#"        <property name="{}" value="{}"/>"#,
+                xml_escape(name),
+                xml_escape(&value)
+            )?;
+        }
+        writeln!(out, "      </properties>")?;
+    }
+
+    let (element, message) = match &report.result {
+        TestResult::Pass { details, .. } => {
+            if !details.is_empty() {
+                writeln!(
+                    out,
+                    "      <system-out>{}</system-out>",
+                    xml_escape(details)
+                )?;
+            }
+            writeln!(out, "    </testcase>")?;
+            return Ok(());
+        }
+        TestResult::Fail { reason, .. } => ("failure", reason),
+        TestResult::Error { error, .. } => ("error", error),
+        // JUnit has no inconclusive outcome; skipped keeps it out of the passes
+        TestResult::Skip { reason, .. } | TestResult::Inconclusive { reason, .. } => {
+            ("skipped", reason)
+        }
+    };
+    writeln!(
+        out,
+        r#"      <{element} message="{}">{}</{element}>"#,
+        xml_escape(message.lines().next().unwrap_or_default()),
+        xml_escape(message)
+    )?;
+    writeln!(out, "    </testcase>")
+}
+
+/// One-line rendering of a comparison, e.g.
+/// `forge=100.5 r=100 relative_diff=0.005 tolerance=0.01 passed`.
+fn describe(comparison: &Comparison) -> String {
+    let mut text = format!(
+        "forge={} r={} relative_diff={}",
+        comparison.forge, comparison.r, comparison.relative_diff
+    );
+    if let Some(tolerance) = comparison.tolerance {
+        let _ = write!(text, " tolerance={tolerance}");
+    }
+    if let Some(tolerance) = comparison.absolute_tolerance {
+        let _ = write!(text, " absolute_tolerance={tolerance}");
+    }
+    if let Some(z) = comparison.z_score {
+        let _ = write!(text, " z={z}");
+    }
+    if let Some(p) = comparison.p_value {
+        let _ = write!(text, " p_value={p}");
+    }
+    if let Some(alpha) = comparison.alpha {
+        let _ = write!(text, " alpha={alpha}");
+    }
+    if let Some(adjusted) = comparison.adjusted_p_value {
+        let _ = write!(text, " adjusted_p_value={adjusted}");
+    }
+    text.push_str(if comparison.passed {
+        " passed"
+    } else {
+        " failed"
+    });
+    text
+}
+
+/// One-line rendering of a goodness-of-fit test, e.g.
+/// `statistic=0.012 p_value=0.43 min_p_value=0.05 at=101.2 passed`, with
+/// `adjusted_p_value` after a multiple-comparison correction.
+fn describe_gof(gof: &GofTest) -> String {
+    let mut text = format!(
+        "statistic={} p_value={} min_p_value={}",
+        gof.statistic, gof.p_value, gof.min_p_value
+    );
+    if let Some(adjusted) = gof.adjusted_p_value {
+        let _ = write!(text, " adjusted_p_value={adjusted}");
+    }
+    if let Some(at) = gof.at {
+        let _ = write!(text, " at={at}");
+    }
+    text.push_str(if gof.passed { " passed" } else { " failed" });
+    text
+}
+
+/// Escapes XML special characters and drops control characters XML 1.0
+/// does not allow.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// YAML diagnostics block of a TAP test line.
+#[derive(Serialize)]
+struct TapDiagnostics<'a> {
+    suite: &'a str,
+    duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    comparisons: &'a [Comparison],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    gof: &'a [GofTest],
+}
+
+fn write_tap(reports: &[TestReport], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "TAP version 13")?;
+    writeln!(out, "1..{}", reports.len())?;
+    for (i, report) in reports.iter().enumerate() {
+        let number = i + 1;
+        // `#` starts a directive, so it must not appear unescaped in descriptions
+        let name = report.result.name().replace('#', "\\#");
+        let (line, message) = match &report.result {
+            TestResult::Skip { reason, .. } => {
+                let reason = reason.lines().next().unwrap_or_default();
+                writeln!(out, "ok {number} - {name} # SKIP {reason}")?;
+                continue;
+            }
+            TestResult::Pass { details, .. } => (
+                format!("ok {number} - {name}"),
+                Some(details).filter(|d| !d.is_empty()),
+            ),
+            TestResult::Fail { reason, .. } => (format!("not ok {number} - {name}"), Some(reason)),
+            TestResult::Error { error, .. } => (format!("not ok {number} - {name}"), Some(error)),
+            // Not a pass, but TODO keeps harnesses from counting it as a failure
+            TestResult::Inconclusive { reason, .. } => (
+                format!("not ok {number} - {name} # TODO inconclusive"),
+                Some(reason),
+            ),
+        };
+        writeln!(out, "{line}")?;
+
+        let diagnostics = TapDiagnostics {
+            suite: &report.suite,
+            duration_secs: report.duration.as_secs_f64(),
+            message: message.map(String::as_str),
+            comparisons: &report.comparisons,
+            gof: &report.gof,
+        };
+        let yaml = serde_yaml_ng::to_string(&diagnostics).map_err(io::Error::other)?;
+        writeln!(out, "  ---")?;
+        for line in yaml.lines() {
+            writeln!(out, "  {line}")?;
+        }
+        writeln!(out, "  ...")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reports() -> Vec<TestReport> {
+        let comparison = Comparison {
+            metric: "mean".to_string(),
+            forge: 100.5,
+            r: 100.0,
+            relative_diff: 0.005,
+            tolerance: Some(0.01),
+            absolute_tolerance: None,
+            z_score: None,
+            p_value: None,
+            alpha: None,
+            adjusted_p_value: None,
+            passed: true,
+        };
+        vec![
+            TestReport {
+                result: TestResult::Pass {
+                    name: "normal_standard".to_string(),
+                    details: String::new(),
+                },
+                suite: "monte_carlo".to_string(),
+                duration: Duration::from_millis(1500),
+                comparisons: vec![comparison],
+                gof: Vec::new(),
+                samples: None,
+            },
+            TestReport {
+                result: TestResult::Fail {
+                    name: "margin".to_string(),
+                    reason: "forge 0.3 < expected 0.5 & R".to_string(),
+                },
+                suite: "formula/breakeven".to_string(),
+                duration: Duration::from_millis(20),
+                comparisons: Vec::new(),
+                gof: Vec::new(),
+                samples: None,
+            },
+            TestReport {
+                result: TestResult::Skip {
+                    name: "gamma_shape".to_string(),
+                    reason: "no r_expected".to_string(),
+                },
+                suite: "monte_carlo".to_string(),
+                duration: Duration::ZERO,
+                comparisons: Vec::new(),
+                gof: Vec::new(),
+                samples: None,
+            },
+            TestReport {
+                result: TestResult::Inconclusive {
+                    name: "uniform_range".to_string(),
+                    reason: "Equivalence neither shown nor ruled out".to_string(),
+                },
+                suite: "monte_carlo".to_string(),
+                duration: Duration::from_millis(500),
+                comparisons: Vec::new(),
+                gof: Vec::new(),
+                samples: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn errors_fail_the_run() {
+        let mut reports = reports();
+        reports.retain(|report| !report.result.is_fail());
+        assert!(Summary::new(&reports).succeeded(true));
+
+        reports[0].result = TestResult::Error {
+            name: "normal_standard".to_string(),
+            error: "Forge failed: exit status 101".to_string(),
+        };
+        let summary = Summary::new(&reports);
+        assert_eq!((summary.passed, summary.errors), (0, 1));
+        assert_eq!(summary.verdict(), "FAIL");
+        assert!(!summary.succeeded(true));
+    }
+
+    #[test]
+    fn inconclusive_tests_are_not_a_pass() {
+        let mut reports = reports();
+        reports.retain(|report| !report.result.is_fail());
+        let summary = Summary::new(&reports);
+        assert_eq!((summary.passed, summary.inconclusive), (1, 1));
+        assert_eq!(summary.verdict(), "INCONCLUSIVE");
+        assert!(!summary.succeeded(false));
+        assert!(summary.succeeded(true));
+
+        // Every test inconclusive is no better
+        reports.retain(|report| matches!(report.result, TestResult::Inconclusive { .. }));
+        let summary = Summary::new(&reports);
+        assert_eq!(summary.verdict(), "INCONCLUSIVE");
+        assert!(!summary.succeeded(false));
+
+        reports.clear();
+        assert_eq!(Summary::new(&reports).verdict(), "PASS");
+    }
+
+    #[test]
+    fn parses_report_targets() {
+        assert_eq!(
+            "junit=out/results.xml".parse(),
+            Ok(ReportTarget {
+                format: ReportFormat::Junit,
+                path: Some(PathBuf::from("out/results.xml")),
+            })
+        );
+        assert_eq!(
+            "tap".parse(),
+            Ok(ReportTarget {
+                format: ReportFormat::Tap,
+                path: None,
+            })
+        );
+        assert!("xml=out.xml".parse::<ReportTarget>().is_err());
+        assert!("json=".parse::<ReportTarget>().is_err());
+    }
+
+    #[test]
+    fn writes_junit_and_tap() {
+        let reports = reports();
+        let render = |format| {
+            let mut out = Vec::new();
+            write_report(format, &reports, Duration::from_secs(2), &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let junit = render(ReportFormat::Junit);
+        assert!(junit.contains(
+            r#"<testsuite name="monte_carlo" tests="3" failures="0" errors="0" skipped="2" time="2.000">"#
+        ));
+        assert!(junit.contains(
+            r#"<property name="mean" value="forge=100.5 r=100 relative_diff=0.005 tolerance=0.01 passed"/>"#
+        ));
+        assert!(junit.contains(r#"<failure message="forge 0.3 &lt; expected 0.5 &amp; R">"#));
+        assert!(junit.contains(r#"<skipped message="no r_expected">"#));
+
+        let tap = render(ReportFormat::Tap);
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(
+            lines[..3],
+            ["TAP version 13", "1..4", "ok 1 - normal_standard"]
+        );
+        assert!(tap.contains("  - metric: mean\n"));
+        assert!(tap.contains("not ok 2 - margin\n"));
+        assert!(tap.contains("ok 3 - gamma_shape # SKIP no r_expected\n"));
+        assert!(tap.contains("not ok 4 - uniform_range # TODO inconclusive\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&render(ReportFormat::Json)).unwrap();
+        assert_eq!(json["summary"]["failed"], 1);
+        assert_eq!(json["summary"]["inconclusive"], 1);
+        assert_eq!(json["tests"][3]["status"], "inconclusive");
+        assert_eq!(json["tests"][0]["status"], "pass");
+        assert_eq!(json["tests"][0]["comparisons"][0]["relative_diff"], 0.005);
+    }
+}
@@ -0,0 +1,155 @@
+//! Test selection by name, suite and tag.
+//!
+//! Names are matched with shell-style globs (`*` for any run of characters,
+//! `?` for one character) against the test keys in the spec files and the
+//! keys of `assumptions:` maps.
+
+use crate::suite::SuiteRegistry;
+use crate::types::{AnalyticsTestSpec, FormulaSuite};
+
+/// Suite name that selects every formula assertion suite.
+pub const FORMULA_SUITE: &str = "formula";
+
+/// Which tests to run. An empty list places no constraint.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// Name globs; a test must match one of them.
+    pub patterns: Vec<String>,
+    /// Suite names; a test must belong to one of them.
+    pub suites: Vec<String>,
+    /// Tags; a test must carry one of them.
+    pub tags: Vec<String>,
+    /// Name globs; a test matching any of them is dropped.
+    pub exclude: Vec<String>,
+}
+
+impl Selection {
+    /// Returns true if the selection lets every test through.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+            && self.suites.is_empty()
+            && self.tags.is_empty()
+            && self.exclude.is_empty()
+    }
+
+    /// Returns true if a test with this name, suite names and tags is selected.
+    #[must_use]
+    pub fn matches(&self, name: &str, suites: &[&str], tags: &[String]) -> bool {
+        let any_glob = |patterns: &[String]| patterns.iter().any(|p| glob_match(p, name));
+        (self.patterns.is_empty() || any_glob(&self.patterns))
+            && (self.suites.is_empty() || suites.iter().any(|s| self.suites.iter().any(|x| x == s)))
+            && (self.tags.is_empty() || tags.iter().any(|t| self.tags.contains(t)))
+            && !any_glob(&self.exclude)
+    }
+
+    /// Returns true if `spec` is selected; its suite is the one the registry
+    /// would run it with.
+    #[must_use]
+    pub fn matches_test(&self, spec: &AnalyticsTestSpec, registry: &SuiteRegistry) -> bool {
+        let suite = registry.select(spec).map_or("", |suite| suite.name());
+        self.matches(&spec.name, &[suite], &spec.tags)
+    }
+
+    /// Keeps the selected tests.
+    #[must_use]
+    pub fn filter_tests(
+        &self,
+        tests: Vec<AnalyticsTestSpec>,
+        registry: &SuiteRegistry,
+    ) -> Vec<AnalyticsTestSpec> {
+        tests
+            .into_iter()
+            .filter(|spec| self.matches_test(spec, registry))
+            .collect()
+    }
+
+    /// Keeps the selected assertions, dropping suites left empty.
+    ///
+    /// Assertions belong to both [`FORMULA_SUITE`] and their file's suite
+    /// (e.g. `breakeven`), and have no tags.
+    #[must_use]
+    pub fn filter_formula_suites(&self, suites: Vec<FormulaSuite>) -> Vec<FormulaSuite> {
+        suites
+            .into_iter()
+            .filter_map(|mut suite| {
+                let names = [FORMULA_SUITE, suite.name.as_str()];
+                let assertions = suite
+                    .assertions
+                    .drain(..)
+                    .filter(|a| self.matches(&a.name, &names, &[]))
+                    .collect::<Vec<_>>();
+                suite.assertions = assertions;
+                (!suite.assertions.is_empty()).then_some(suite)
+            })
+            .collect()
+    }
+}
+
+/// Matches `name` against a glob where `*` matches any run of characters
+/// and `?` any single character.
+#[must_use]
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` absorb one more character
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("normal_*", "normal_standard"));
+        assert!(glob_match("*_call", "black_scholes_call"));
+        assert!(glob_match("*bca*", "bootstrap_bca_median"));
+        assert!(glob_match("t?", "t1"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("normal_*", "lognormal_standard"));
+        assert!(!glob_match("t?", "t10"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn criteria_combine_and_exclude_wins() {
+        let selection = Selection {
+            patterns: vec!["normal_*".to_string(), "beta_*".to_string()],
+            suites: vec!["monte_carlo".to_string()],
+            tags: vec!["slow".to_string()],
+            exclude: vec!["*_large".to_string()],
+        };
+        let slow = ["slow".to_string()];
+        assert!(selection.matches("normal_standard", &["monte_carlo"], &slow));
+        assert!(!selection.matches("normal_large", &["monte_carlo"], &slow));
+        assert!(!selection.matches("normal_standard", &["bootstrap"], &slow));
+        assert!(!selection.matches("normal_standard", &["monte_carlo"], &[]));
+        assert!(!selection.matches("gamma_shape", &["monte_carlo"], &slow));
+        assert!(Selection::default().matches("anything", &[""], &[]));
+    }
+}
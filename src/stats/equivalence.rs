@@ -0,0 +1,231 @@
+//! Equivalence tests (TOST) of forge's moments against R's.
+//!
+//! A difference inside a tolerance can mean the two agree, or only that the
+//! samples were too small to see them disagree. Two one-sided tests tell
+//! these apart: forge is shown equivalent when the 1 - 2α confidence
+//! interval of the difference lies inside the declared margin, shown not
+//! equivalent when it lies outside, and inconclusive otherwise.
+
+#![allow(clippy::cast_precision_loss)]
+
+use serde::Serialize;
+
+use super::sampling::{normal_cdf, normal_quantile};
+use super::{record, relative_difference, Comparison, Moments};
+
+/// Verdict of an equivalence test, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Equivalence {
+    /// The difference is shown to be inside the margin.
+    Equivalent,
+    /// Neither: more draws are needed to decide.
+    Inconclusive,
+    /// The difference is shown to be outside the margin.
+    NotEquivalent,
+}
+
+impl Equivalence {
+    /// Name shown in results.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Equivalent => "equivalent",
+            Self::Inconclusive => "inconclusive",
+            Self::NotEquivalent => "not equivalent",
+        }
+    }
+}
+
+/// Equivalence margin of a moment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Margin {
+    /// Fraction of R's value.
+    Relative(f64),
+    /// In the moment's own units, for values near zero where any fraction
+    /// of R's value is too small to show equivalence.
+    Absolute(f64),
+}
+
+/// Two one-sided tests of a difference against `±margin`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tost {
+    /// Estimated difference, forge minus R.
+    pub estimate: f64,
+    /// Margin on the scale of the estimate.
+    pub margin: f64,
+    /// The 1 - 2α confidence interval of the difference.
+    pub interval: (f64, f64),
+    /// Larger of the two one-sided p-values; below α means equivalent.
+    pub p_value: f64,
+    /// Level of the test.
+    pub alpha: f64,
+    /// Verdict.
+    pub outcome: Equivalence,
+}
+
+/// Runs TOST on an `estimate` with this `standard_error` (normal
+/// approximation) at level `alpha`.
+#[must_use]
+pub fn tost(estimate: f64, standard_error: f64, margin: f64, alpha: f64) -> Tost {
+    let z = normal_quantile(1.0 - alpha);
+    let interval = (
+        z.mul_add(-standard_error, estimate),
+        z.mul_add(standard_error, estimate),
+    );
+    // H0: estimate <= -margin, and H0: estimate >= margin
+    let p_lower = 1.0 - normal_cdf((estimate + margin) / standard_error);
+    let p_upper = 1.0 - normal_cdf((margin - estimate) / standard_error);
+
+    let outcome = if interval.0 > -margin && interval.1 < margin {
+        Equivalence::Equivalent
+    } else if interval.0 > margin || interval.1 < -margin {
+        Equivalence::NotEquivalent
+    } else {
+        Equivalence::Inconclusive
+    };
+    Tost {
+        estimate,
+        margin,
+        interval,
+        p_value: p_lower.max(p_upper),
+        alpha,
+        outcome,
+    }
+}
+
+/// TOST of the means of `n_forge` and `n_r` draws, equivalent within
+/// `margin` (relative to R's mean, or absolute).
+#[must_use]
+pub fn tost_mean(
+    forge: &Moments,
+    r: &Moments,
+    (n_forge, n_r): (usize, usize),
+    margin: Margin,
+    alpha: f64,
+) -> Tost {
+    let standard_error = (forge.std.powi(2) / n_forge as f64 + r.std.powi(2) / n_r as f64).sqrt();
+    let margin = match margin {
+        Margin::Relative(fraction) => fraction * r.mean.abs(),
+        Margin::Absolute(margin) => margin,
+    };
+    tost(forge.mean - r.mean, standard_error, margin, alpha)
+}
+
+/// TOST of the variances of `n_forge` and `n_r` draws.
+///
+/// Equivalent when their ratio is between `1/(1 + margin)` and
+/// `1 + margin`. Compared as logs, whose standard error is
+/// `sqrt((kurtosis + 2)/n)` per side.
+#[must_use]
+pub fn tost_variance(
+    forge: &Moments,
+    r: &Moments,
+    (n_forge, n_r): (usize, usize),
+    margin: f64,
+    alpha: f64,
+) -> Tost {
+    let standard_error = ((forge.excess_kurtosis + 2.0) / n_forge as f64
+        + (r.excess_kurtosis + 2.0) / n_r as f64)
+        .sqrt();
+    tost(
+        2.0 * (forge.std / r.std).ln(),
+        standard_error,
+        margin.ln_1p(),
+        alpha,
+    )
+}
+
+/// Records a TOST of `forge` against `r` as a comparison with its
+/// `margin`, passed only if equivalent, and returns the verdict.
+pub fn record_tost(
+    metric: impl Into<String>,
+    forge: f64,
+    r: f64,
+    margin: Margin,
+    tost: &Tost,
+) -> Equivalence {
+    let (tolerance, absolute_tolerance) = match margin {
+        Margin::Relative(fraction) => (Some(fraction), None),
+        Margin::Absolute(margin) => (None, Some(margin)),
+    };
+    record(Comparison {
+        metric: metric.into(),
+        forge,
+        r,
+        relative_diff: relative_difference(forge, r),
+        tolerance,
+        absolute_tolerance,
+        z_score: None,
+        p_value: Some(tost.p_value),
+        alpha: Some(tost.alpha),
+        adjusted_p_value: None,
+        passed: tost.outcome == Equivalence::Equivalent,
+    });
+    tost.outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tost_outcomes() {
+        // 90% interval 0.1 ± 1.645 * 0.1 = [-0.064, 0.264]
+        let inside = tost(0.1, 0.1, 0.5, 0.05);
+        assert_eq!(inside.outcome, Equivalence::Equivalent);
+        // p = P(Z > (0.5 - 0.1)/0.1 = 4)
+        assert!((inside.p_value - 3.167e-5).abs() < 1e-7);
+
+        // [-0.393, 0.593] straddles the upper margin
+        assert_eq!(tost(0.1, 0.3, 0.5, 0.05).outcome, Equivalence::Inconclusive);
+        assert_eq!(
+            tost(-1.0, 0.1, 0.5, 0.05).outcome,
+            Equivalence::NotEquivalent
+        );
+    }
+
+    #[test]
+    fn more_draws_settle_an_inconclusive_mean() {
+        let moments = |mean| Moments {
+            mean,
+            std: 15.0,
+            excess_kurtosis: 0.0,
+        };
+        let (forge, r) = (moments(100.2), moments(100.0));
+
+        let margin = Margin::Relative(0.01);
+        let few = tost_mean(&forge, &r, (1_000, 1_000), margin, 0.05);
+        assert_eq!(few.outcome, Equivalence::Inconclusive);
+        let many = tost_mean(&forge, &r, (100_000, 100_000), margin, 0.05);
+        assert_eq!(many.outcome, Equivalence::Equivalent);
+
+        // Variances 10% apart against a 5% margin
+        let wide = Moments {
+            std: 15.0 * 1.1_f64.sqrt(),
+            ..r
+        };
+        let variance = tost_variance(&wide, &r, (100_000, 100_000), 0.05, 0.05);
+        assert_eq!(variance.outcome, Equivalence::NotEquivalent);
+    }
+
+    #[test]
+    fn zero_mean_needs_an_absolute_margin() {
+        let moments = |mean| Moments {
+            mean,
+            std: 1.0,
+            excess_kurtosis: 0.0,
+        };
+        let (forge, r) = (moments(0.01), moments(0.0));
+        let n = (100_000, 100_000);
+
+        // 5% of zero is zero: no difference can be shown inside it
+        let relative = tost_mean(&forge, &r, n, Margin::Relative(0.05), 0.05);
+        assert!(relative.margin.abs() < f64::EPSILON);
+        assert_ne!(relative.outcome, Equivalence::Equivalent);
+
+        // 90% interval 0.01 ± 1.645 * 0.00447 lies inside ±0.05
+        let absolute = tost_mean(&forge, &r, n, Margin::Absolute(0.05), 0.05);
+        assert_eq!(absolute.outcome, Equivalence::Equivalent);
+    }
+}
@@ -0,0 +1,524 @@
+//! Two-sample goodness-of-fit tests of forge's samples against R's.
+//!
+//! KS is most sensitive near the median. Anderson-Darling weights the tails,
+//! where P95 and P99 live, and Cramér-von Mises sits between the two.
+//! Chi-square compares discrete outputs, or continuous ones after binning.
+//! p-values come from the asymptotic distributions, which are accurate for
+//! the thousands of samples a Monte Carlo test draws.
+
+#![allow(clippy::cast_precision_loss)]
+
+use std::cell::Cell;
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ecdf_max_gap, ks_pvalue, record_gof};
+
+/// Most bins a continuous output is split into for chi-square.
+const MAX_CHI_SQUARE_BINS: usize = 50;
+
+/// Smallest expected count per bin and sample for chi-square.
+const MIN_EXPECTED: f64 = 5.0;
+
+thread_local! {
+    /// Whether [`gof_test`] leaves verdicts to a run-level correction.
+    static DEFERRED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Goodness-of-fit test, as named in a spec's `gof:` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GofKind {
+    /// Kolmogorov-Smirnov: largest gap between the empirical CDFs.
+    Ks,
+    /// Anderson-Darling: squared CDF gaps weighted towards the tails.
+    Ad,
+    /// Cramér-von Mises: squared CDF gaps, unweighted.
+    Cvm,
+    /// Chi-square homogeneity test on discrete values or bins.
+    #[serde(alias = "chi2", alias = "chisquare")]
+    Chisq,
+}
+
+impl GofKind {
+    /// Name shown in results and reports.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Ks => "KS",
+            Self::Ad => "AD",
+            Self::Cvm => "CvM",
+            Self::Chisq => "chi-square",
+        }
+    }
+}
+
+/// A goodness-of-fit test of forge's samples against R's.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GofTest {
+    /// Test name (e.g. `KS`).
+    pub name: String,
+    /// Test statistic (D for KS, A² for AD, T for `CvM`, X² for chi-square).
+    pub statistic: f64,
+    /// p-value of the statistic under the hypothesis that both samples
+    /// come from the same distribution.
+    pub p_value: f64,
+    /// Smallest p-value accepted.
+    pub min_p_value: f64,
+    /// p-value after the run's multiple-comparison correction, which
+    /// [`passed`](Self::passed) then reflects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjusted_p_value: Option<f64>,
+    /// Sample value where the empirical CDFs are furthest apart (KS).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<f64>,
+    /// Whether the samples were accepted as the same distribution.
+    pub passed: bool,
+}
+
+/// Runs `kind` on `forge` against `r`, recording it.
+///
+/// Passes if the p-value is at least `min_p_value`, or always inside
+/// [`defer_verdicts`]. Returns `None`, without recording anything, if
+/// either sample is empty.
+pub fn gof_test(kind: GofKind, forge: &[f64], r: &[f64], min_p_value: f64) -> Option<GofTest> {
+    if forge.is_empty() || r.is_empty() {
+        return None;
+    }
+    let (statistic, p_value, at) = match kind {
+        GofKind::Ks => {
+            let gap = ecdf_max_gap(forge, r)?;
+            (gap.d, ks_pvalue(gap.d, forge.len(), r.len()), Some(gap.at))
+        }
+        GofKind::Ad => {
+            let a2 = ad_statistic(forge, r)?;
+            (a2, ad_pvalue(a2), None)
+        }
+        GofKind::Cvm => {
+            let t = cvm_statistic(forge, r)?;
+            (t, cvm_pvalue(t), None)
+        }
+        GofKind::Chisq => {
+            let (x2, df) = chi_square_statistic(forge, r)?;
+            (x2, chi_square_pvalue(x2, df), None)
+        }
+    };
+    let test = GofTest {
+        name: kind.label().to_string(),
+        statistic,
+        p_value,
+        min_p_value,
+        adjusted_p_value: None,
+        at,
+        passed: p_value >= min_p_value || DEFERRED.get(),
+    };
+    record_gof(&test);
+    Some(test)
+}
+
+/// Runs `f` with the verdicts of [`gof_test`] on this thread deferred if
+/// `defer` is set: every test passes, to be judged once the p-values of the
+/// whole run can be adjusted together.
+pub fn defer_verdicts<T>(defer: bool, f: impl FnOnce() -> T) -> T {
+    let outer = DEFERRED.replace(defer);
+    let value = f();
+    DEFERRED.set(outer);
+    value
+}
+
+/// Both samples sorted together, each value tagged with whether it came
+/// from the first sample.
+fn pooled(sample1: &[f64], sample2: &[f64]) -> Vec<(f64, bool)> {
+    let mut pooled: Vec<(f64, bool)> = sample1
+        .iter()
+        .map(|&v| (v, true))
+        .chain(sample2.iter().map(|&v| (v, false)))
+        .collect();
+    pooled.sort_by(|a, b| a.0.total_cmp(&b.0));
+    pooled
+}
+
+/// Computes the two-sample Anderson-Darling statistic A² (Pettitt 1976;
+/// Scholz and Stephens 1987 with k = 2).
+///
+/// Meant for continuous samples: tied values are ordered arbitrarily.
+/// Returns `None` if either sample is empty.
+#[must_use]
+pub fn ad_statistic(sample1: &[f64], sample2: &[f64]) -> Option<f64> {
+    if sample1.is_empty() || sample2.is_empty() {
+        return None;
+    }
+    let (m, n) = (sample1.len() as f64, sample2.len() as f64);
+    let total = m + n;
+    let pooled = pooled(sample1, sample2);
+
+    // M_i: values of the first sample among the i smallest
+    let mut m_i = 0.0_f64;
+    let mut sum = 0.0;
+    for (i, &(_, first)) in pooled.iter().enumerate().take(pooled.len() - 1) {
+        if first {
+            m_i += 1.0;
+        }
+        let i = (i + 1) as f64;
+        sum += m_i.mul_add(total, -m * i).powi(2) / (i * (total - i));
+    }
+    Some(sum / (m * n))
+}
+
+/// p-value of a two-sample A², from its limiting distribution (that of the
+/// one-sample statistic), evaluated as in Marsaglia and Marsaglia (2004).
+#[must_use]
+pub fn ad_pvalue(a2: f64) -> f64 {
+    if a2 <= 0.0 {
+        return 1.0;
+    }
+    let cdf = if a2 < 2.0 {
+        (-1.233_714_1 / a2).exp() / a2.sqrt()
+            * polynomial(
+                &[
+                    2.000_12,
+                    0.247_105,
+                    -0.064_982_1,
+                    0.034_796_2,
+                    -0.011_672,
+                    0.001_686_91,
+                ],
+                a2,
+            )
+    } else {
+        (-polynomial(
+            &[
+                1.0776,
+                -2.306_95,
+                0.434_24,
+                -0.082_433,
+                0.008_056,
+                -0.000_314_6,
+            ],
+            a2,
+        )
+        .exp())
+        .exp()
+    };
+    (1.0 - cdf).clamp(0.0, 1.0)
+}
+
+/// Evaluates the polynomial with `coefficients` (constant term first) at `x`.
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |acc, &c| acc.mul_add(x, c))
+}
+
+/// Computes the two-sample Cramér-von Mises statistic T (Anderson 1962).
+/// Returns `None` if either sample is empty.
+#[must_use]
+pub fn cvm_statistic(sample1: &[f64], sample2: &[f64]) -> Option<f64> {
+    if sample1.is_empty() || sample2.is_empty() {
+        return None;
+    }
+    let (len1, len2) = (sample1.len() as f64, sample2.len() as f64);
+
+    // Squared gaps between each value's pooled rank and its rank in its own sample
+    let (mut rank1, mut rank2) = (0.0, 0.0);
+    let (mut sum1, mut sum2) = (0.0, 0.0);
+    for (rank, &(_, first)) in pooled(sample1, sample2).iter().enumerate() {
+        let rank = (rank + 1) as f64;
+        if first {
+            rank1 += 1.0;
+            sum1 += (rank - rank1) * (rank - rank1);
+        } else {
+            rank2 += 1.0;
+            sum2 += (rank - rank2) * (rank - rank2);
+        }
+    }
+    let u = len1.mul_add(sum1, len2 * sum2);
+    Some(
+        u / (len1 * len2 * (len1 + len2))
+            - 4.0f64.mul_add(len1 * len2, -1.0) / (6.0 * (len1 + len2)),
+    )
+}
+
+/// p-value of a two-sample T, from the limiting Cramér-von Mises
+/// distribution (Csörgő and Faraway 1996, eq. 1.3).
+#[must_use]
+pub fn cvm_pvalue(t: f64) -> f64 {
+    if t <= 0.0 {
+        return 1.0;
+    }
+    let mut cdf = 0.0;
+    // Γ(k + 1/2) / (Γ(1/2) k!)
+    let mut coefficient = 1.0;
+    for k in 0..100 {
+        if k > 0 {
+            coefficient *= f64::from(2 * k - 1) / f64::from(2 * k);
+        }
+        let y = f64::from(4 * k + 1);
+        let q = y * y / (16.0 * t);
+        let term = coefficient / (PI * t.sqrt()) * y.sqrt() * scaled_bessel_k_quarter(q);
+        cdf += term;
+        if term < 1e-12 {
+            break;
+        }
+    }
+    (1.0 - cdf).clamp(0.0, 1.0)
+}
+
+/// Computes `exp(-x) K_{1/4}(x)` from `∫ exp(-x cosh t) cosh(t/4) dt` over
+/// `t ≥ 0`; the trapezoid rule converges fast on this integrand.
+fn scaled_bessel_k_quarter(x: f64) -> f64 {
+    const STEP: f64 = 0.01;
+    let integrand = |t: f64| (-x * (1.0 + t.cosh())).exp() * (t / 4.0).cosh();
+
+    let mut sum = integrand(0.0) / 2.0;
+    let mut t = STEP;
+    loop {
+        let value = integrand(t);
+        sum += value;
+        if value < 1e-300 || t > 50.0 {
+            break;
+        }
+        t += STEP;
+    }
+    sum * STEP
+}
+
+/// Computes the chi-square homogeneity statistic of two samples and its
+/// degrees of freedom.
+///
+/// Values are taken in order and grouped into bins of at least enough
+/// values to expect [`MIN_EXPECTED`] from each sample, and at most
+/// [`MAX_CHI_SQUARE_BINS`] bins; equal values always share a bin, so a
+/// discrete output with few values gets one bin per value. Returns `None`
+/// if either sample is empty.
+#[must_use]
+pub fn chi_square_statistic(sample1: &[f64], sample2: &[f64]) -> Option<(f64, usize)> {
+    if sample1.is_empty() || sample2.is_empty() {
+        return None;
+    }
+    let (n1, n2) = (sample1.len() as f64, sample2.len() as f64);
+    let total = n1 + n2;
+    let min_bin = (MIN_EXPECTED * total / n1.min(n2)).max(total / MAX_CHI_SQUARE_BINS as f64);
+
+    // (count from sample 1, count from sample 2) per bin
+    let mut bins: Vec<(f64, f64)> = Vec::new();
+    let mut current = (0.0, 0.0);
+    let pooled = pooled(sample1, sample2);
+    for (i, &(value, first)) in pooled.iter().enumerate() {
+        if first {
+            current.0 += 1.0;
+        } else {
+            current.1 += 1.0;
+        }
+        let run_ends = pooled
+            .get(i + 1)
+            .is_none_or(|next| next.0.total_cmp(&value).is_ne());
+        if run_ends && current.0 + current.1 >= min_bin {
+            bins.push(current);
+            current = (0.0, 0.0);
+        }
+    }
+    // A short last bin joins the one before it
+    if current.0 + current.1 > 0.0 {
+        match bins.last_mut() {
+            Some(last) => {
+                last.0 += current.0;
+                last.1 += current.1;
+            }
+            None => bins.push(current),
+        }
+    }
+
+    let statistic = bins
+        .iter()
+        .map(|&(c1, c2)| {
+            let expected1 = (c1 + c2) * n1 / total;
+            let expected2 = (c1 + c2) * n2 / total;
+            (c1 - expected1).powi(2) / expected1 + (c2 - expected2).powi(2) / expected2
+        })
+        .sum();
+    Some((statistic, bins.len() - 1))
+}
+
+/// p-value of a chi-square statistic with `df` degrees of freedom.
+#[must_use]
+pub fn chi_square_pvalue(x2: f64, df: usize) -> f64 {
+    if df == 0 || x2 <= 0.0 {
+        return 1.0;
+    }
+    upper_regularized_gamma(df as f64 / 2.0, x2 / 2.0)
+}
+
+/// Q(a, x) = Γ(a, x) / Γ(a), by its series below `a + 1` and its continued
+/// fraction above (Numerical Recipes, section 6.2).
+#[allow(clippy::many_single_char_names)] // Numerical Recipes notation
+fn upper_regularized_gamma(a: f64, x: f64) -> f64 {
+    let log_prefactor = a.mul_add(x.ln(), -x) - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * log_prefactor.exp()).clamp(0.0, 1.0)
+    } else {
+        // Modified Lentz
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let i = f64::from(i);
+            let an = -i * (i - a);
+            b += 2.0;
+            d = an.mul_add(d, b);
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (log_prefactor.exp() * h).clamp(0.0, 1.0)
+    }
+}
+
+/// ln Γ(x) for x > 0 (Lanczos approximation, g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + (i + 1) as f64)
+        });
+    0.5f64.mul_add((2.0 * PI).ln(), (x + 0.5) * t.ln()) - t + series.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance
+    }
+
+    #[test]
+    fn pvalues_match_tabulated_critical_values() {
+        // Chi-square upper percentage points
+        assert!(close(chi_square_pvalue(3.841, 1), 0.05, 1e-4));
+        assert!(close(chi_square_pvalue(6.635, 1), 0.01, 1e-4));
+        assert!(close(chi_square_pvalue(18.307, 10), 0.05, 1e-4));
+        assert!(close(chi_square_pvalue(124.342, 100), 0.05, 1e-4));
+
+        // Limiting Anderson-Darling distribution (Anderson and Darling 1954)
+        assert!(close(ad_pvalue(1.933), 0.10, 1e-3));
+        assert!(close(ad_pvalue(2.492), 0.05, 1e-3));
+        assert!(close(ad_pvalue(3.857), 0.01, 1e-3));
+
+        // Limiting Cramér-von Mises distribution (Anderson and Darling 1952)
+        assert!(close(cvm_pvalue(0.347_30), 0.10, 1e-4));
+        assert!(close(cvm_pvalue(0.461_36), 0.05, 1e-4));
+        assert!(close(cvm_pvalue(0.743_46), 0.01, 1e-4));
+    }
+
+    #[test]
+    fn statistics_of_small_samples() {
+        let (x, y) = ([1.0, 2.0], [3.0, 4.0]);
+        assert!(close(ad_statistic(&x, &y).unwrap(), 5.0 / 3.0, 1e-12));
+        assert!(close(cvm_statistic(&x, &y).unwrap(), 0.375, 1e-12));
+
+        // Discrete values keep their own bins
+        let a = [vec![0.0; 40], vec![1.0; 40], vec![2.0; 20]].concat();
+        let b = [vec![0.0; 20], vec![1.0; 40], vec![2.0; 40]].concat();
+        let (x2, df) = chi_square_statistic(&a, &b).unwrap();
+        assert_eq!(df, 2);
+        assert!(close(x2, 400.0 / 30.0, 1e-9));
+    }
+
+    #[test]
+    fn empty_and_single_value_samples() {
+        for (x, y) in [
+            (&[][..], &[1.0][..]),
+            (&[1.0][..], &[][..]),
+            (&[][..], &[][..]),
+        ] {
+            assert!(ad_statistic(x, y).is_none());
+            assert!(cvm_statistic(x, y).is_none());
+            assert!(chi_square_statistic(x, y).is_none());
+            for kind in [GofKind::Ks, GofKind::Ad, GofKind::Cvm, GofKind::Chisq] {
+                assert!(gof_test(kind, x, y, 0.05).is_none());
+            }
+        }
+
+        // One value each: defined statistics, and too few values for a second bin
+        let (x, y) = ([1.0], [2.0]);
+        assert!(ad_statistic(&x, &y).unwrap().is_finite());
+        assert!(cvm_statistic(&x, &y).unwrap().is_finite());
+        assert_eq!(chi_square_statistic(&x, &y), Some((0.0, 0)));
+        for kind in [GofKind::Ks, GofKind::Ad, GofKind::Cvm, GofKind::Chisq] {
+            let test = gof_test(kind, &x, &y, 0.05).unwrap();
+            assert!((0.0..=1.0).contains(&test.p_value), "{test:?}");
+        }
+    }
+
+    #[test]
+    fn shifted_samples_fail_every_test() {
+        let sample: Vec<f64> = (0..500).map(|i| f64::from(i) / 500.0).collect();
+        let shifted: Vec<f64> = sample.iter().map(|x| x + 0.2).collect();
+
+        let (tests, recorded) = super::super::record_comparisons(|| {
+            [GofKind::Ks, GofKind::Ad, GofKind::Cvm, GofKind::Chisq].map(|kind| {
+                (
+                    gof_test(kind, &sample, &sample, 0.05).unwrap(),
+                    gof_test(kind, &sample, &shifted, 0.05).unwrap(),
+                )
+            })
+        });
+
+        for (same, different) in &tests {
+            assert!(same.passed, "{same:?}");
+            assert!(!different.passed, "{different:?}");
+            assert!(different.p_value < 1e-4, "{different:?}");
+        }
+        assert!(close(tests[0].1.statistic, 0.2, 0.01));
+        assert_eq!(recorded.gof.len(), 8);
+        assert!(gof_test(GofKind::Ad, &[], &sample, 0.05).is_none());
+
+        // Left to the run-level correction
+        let deferred = defer_verdicts(true, || gof_test(GofKind::Ks, &sample, &shifted, 0.05));
+        assert!(deferred.unwrap().passed);
+    }
+}
@@ -0,0 +1,574 @@
+//! Statistical comparison utilities.
+//!
+//! Provides functions for comparing stochastic outputs within tolerance.
+
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_sign_loss)]
+
+pub mod equivalence;
+pub mod gof;
+pub mod multiple;
+pub mod sampling;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use serde::{Deserialize, Serialize};
+
+use gof::GofTest;
+
+/// Tolerance levels for statistical comparison.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tolerance {
+    /// Tolerance for mean comparison (fraction, e.g., 0.01 = 1%).
+    pub mean: f64,
+    /// Tolerance for standard deviation comparison.
+    pub std: f64,
+    /// Tolerance for percentile comparison.
+    pub percentiles: f64,
+    /// Minimum p-value for the goodness-of-fit tests.
+    pub gof_pvalue: f64,
+    /// Tolerance for CI bounds comparison.
+    pub ci_bounds: f64,
+    /// Whether the fixed fractions above or the sampling error set the
+    /// acceptable gap.
+    pub mode: ToleranceMode,
+    /// Chance of a test failing when forge and R sample the same
+    /// distribution ([`ToleranceMode::Statistical`]).
+    pub false_failure_rate: f64,
+}
+
+/// How the acceptable gap between forge and R is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToleranceMode {
+    /// Fixed relative tolerances, whatever the number of draws.
+    #[default]
+    Fixed,
+    /// A number of standard errors of the gap between two samples of n
+    /// draws, so the tolerance narrows as n grows.
+    Statistical,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            mean: 0.01,
+            std: 0.05,
+            percentiles: 0.02,
+            gof_pvalue: 0.05,
+            ci_bounds: 0.02,
+            mode: ToleranceMode::Fixed,
+            false_failure_rate: 0.001,
+        }
+    }
+}
+
+impl Tolerance {
+    /// Creates tolerance for deterministic outputs (stricter).
+    #[must_use]
+    pub const fn deterministic() -> Self {
+        Self {
+            mean: 0.001,
+            std: 0.001,
+            percentiles: 0.001,
+            gof_pvalue: 0.05,
+            ci_bounds: 0.001,
+            mode: ToleranceMode::Fixed,
+            false_failure_rate: 0.001,
+        }
+    }
+
+    /// Creates tolerance for stochastic outputs (looser).
+    #[must_use]
+    pub fn stochastic() -> Self {
+        Self::default()
+    }
+}
+
+/// Checks if actual value is within tolerance of expected value.
+#[inline]
+#[must_use]
+pub fn within_tolerance(actual: f64, expected: f64, tolerance: f64) -> bool {
+    if expected.abs() < f64::EPSILON {
+        actual.abs() <= tolerance
+    } else {
+        let relative_diff = (actual - expected).abs() / expected.abs();
+        relative_diff <= tolerance
+    }
+}
+
+/// One forge value checked against its reference, as shown in reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    /// What was compared (e.g. `mean`, `0.95 CI lower`).
+    pub metric: String,
+    /// Forge value.
+    pub forge: f64,
+    /// Reference value (live R or the recorded `r_expected`).
+    pub r: f64,
+    /// Relative difference, as in [`relative_difference`].
+    pub relative_diff: f64,
+    /// Relative tolerance applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    /// Absolute tolerance applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_tolerance: Option<f64>,
+    /// Difference in standard errors of the gap, when the tolerance was
+    /// derived from sampling error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z_score: Option<f64>,
+    /// p-value of an equivalence test (TOST); below its α means equivalent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p_value: Option<f64>,
+    /// α of an equivalence test.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<f64>,
+    /// p-value after the run's multiple-comparison correction, which
+    /// [`passed`](Self::passed) then reflects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjusted_p_value: Option<f64>,
+    /// Whether the value was accepted.
+    pub passed: bool,
+}
+
+thread_local! {
+    /// What was compared on this thread inside [`record_comparisons`].
+    static RECORDED: RefCell<Option<Recorded>> = const { RefCell::new(None) };
+}
+
+/// Forge and R samples of a stochastic test, kept for plots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Samples {
+    /// Forge samples.
+    pub forge: Vec<f64>,
+    /// R samples.
+    pub r: Vec<f64>,
+    /// Metrics of the recorded comparisons that are quantiles of the
+    /// samples (e.g. `p95`), marked on the plots.
+    pub quantiles: Vec<String>,
+}
+
+/// What a test compared, as collected by [`record_comparisons`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recorded {
+    /// Values checked against a tolerance, in order.
+    pub comparisons: Vec<Comparison>,
+    /// Goodness-of-fit tests, in order.
+    pub gof: Vec<GofTest>,
+    /// Samples behind the comparisons, for stochastic tests.
+    pub samples: Option<Samples>,
+}
+
+/// Runs `f` and returns what was compared on this thread meanwhile.
+///
+/// That is everything passed to [`compare`], [`compare_abs`],
+/// [`compare_either`], [`compare_z`], [`equivalence::record_tost`],
+/// [`gof::gof_test`] and [`record_samples`].
+///
+/// Suites compare inside their own result functions, so this is how the
+/// compared values reach reports without passing them through every
+/// [`TestResult`](crate::types::TestResult).
+pub fn record_comparisons<T>(f: impl FnOnce() -> T) -> (T, Recorded) {
+    let outer = RECORDED.with(|r| r.replace(Some(Recorded::default())));
+    let value = f();
+    let recorded = RECORDED.with(|r| r.replace(outer)).unwrap_or_default();
+    (value, recorded)
+}
+
+/// Records the samples a stochastic comparison was made from.
+pub fn record_samples(samples: Samples) {
+    RECORDED.with(|recorded| {
+        if let Some(recorded) = recorded.borrow_mut().as_mut() {
+            recorded.samples = Some(samples);
+        }
+    });
+}
+
+fn record_gof(test: &GofTest) {
+    RECORDED.with(|recorded| {
+        if let Some(recorded) = recorded.borrow_mut().as_mut() {
+            recorded.gof.push(test.clone());
+        }
+    });
+}
+
+fn record(comparison: Comparison) -> bool {
+    let passed = comparison.passed;
+    RECORDED.with(|recorded| {
+        if let Some(recorded) = recorded.borrow_mut().as_mut() {
+            recorded.comparisons.push(comparison);
+        }
+    });
+    passed
+}
+
+/// A comparison with the relative difference filled in and no z-score or
+/// p-value.
+fn comparison(
+    metric: impl Into<String>,
+    forge: f64,
+    r: f64,
+    tolerance: Option<f64>,
+    absolute_tolerance: Option<f64>,
+    passed: bool,
+) -> Comparison {
+    Comparison {
+        metric: metric.into(),
+        forge,
+        r,
+        relative_diff: relative_difference(forge, r),
+        tolerance,
+        absolute_tolerance,
+        z_score: None,
+        p_value: None,
+        alpha: None,
+        adjusted_p_value: None,
+        passed,
+    }
+}
+
+/// Checks `forge` against `r` with [`within_tolerance`], recording the comparison.
+pub fn compare(metric: impl Into<String>, forge: f64, r: f64, tolerance: f64) -> bool {
+    let passed = within_tolerance(forge, r, tolerance);
+    record(comparison(metric, forge, r, Some(tolerance), None, passed))
+}
+
+/// Checks that `forge` is within `absolute_tolerance` of `r`, recording the comparison.
+pub fn compare_abs(metric: impl Into<String>, forge: f64, r: f64, absolute_tolerance: f64) -> bool {
+    let passed = (forge - r).abs() <= absolute_tolerance;
+    record(comparison(
+        metric,
+        forge,
+        r,
+        None,
+        Some(absolute_tolerance),
+        passed,
+    ))
+}
+
+/// Checks that `forge` is within the relative or the absolute tolerance of
+/// `r`, recording the comparison. Used where values near zero make a
+/// relative check alone meaningless.
+pub fn compare_either(
+    metric: impl Into<String>,
+    forge: f64,
+    r: f64,
+    tolerance: f64,
+    absolute_tolerance: f64,
+) -> bool {
+    let passed = within_tolerance(forge, r, tolerance) || (forge - r).abs() <= absolute_tolerance;
+    record(comparison(
+        metric,
+        forge,
+        r,
+        Some(tolerance),
+        Some(absolute_tolerance),
+        passed,
+    ))
+}
+
+/// Checks that `forge` is within `max_z` standard errors of `r`, recording
+/// the comparison with its z-score. `standard_error` is that of the gap
+/// `forge - r`.
+pub fn compare_z(
+    metric: impl Into<String>,
+    forge: f64,
+    r: f64,
+    standard_error: f64,
+    max_z: f64,
+) -> bool {
+    let z_score = (forge - r) / standard_error;
+    let passed = z_score.abs() <= max_z;
+    record(Comparison {
+        z_score: Some(z_score),
+        ..comparison(metric, forge, r, None, Some(max_z * standard_error), passed)
+    })
+}
+
+/// Calculates relative difference between two values.
+#[inline]
+#[must_use]
+pub fn relative_difference(actual: f64, expected: f64) -> f64 {
+    if expected.abs() < f64::EPSILON {
+        actual.abs()
+    } else {
+        (actual - expected).abs() / expected.abs()
+    }
+}
+
+/// Computes the Kolmogorov-Smirnov test statistic (D), 1 if either sample
+/// is empty.
+#[must_use]
+pub fn ks_statistic(sample1: &[f64], sample2: &[f64]) -> f64 {
+    ecdf_max_gap(sample1, sample2).map_or(1.0, |gap| gap.d)
+}
+
+/// Largest vertical gap between the empirical CDFs of two samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EcdfGap {
+    /// Size of the gap, the KS statistic D.
+    pub d: f64,
+    /// Sample value where the gap occurs.
+    pub at: f64,
+}
+
+/// Finds where the empirical CDFs of two samples are furthest apart, or
+/// `None` if either sample is empty.
+#[must_use]
+pub fn ecdf_max_gap(sample1: &[f64], sample2: &[f64]) -> Option<EcdfGap> {
+    if sample1.is_empty() || sample2.is_empty() {
+        return None;
+    }
+    let sorted = |sample: &[f64]| {
+        let mut sorted = sample.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        sorted
+    };
+    let (sorted1, sorted2) = (sorted(sample1), sorted(sample2));
+    let ecdf =
+        |sorted: &[f64], x: f64| sorted.partition_point(|&v| v <= x) as f64 / sorted.len() as f64;
+
+    // The gap can only change at a sample value
+    sorted1
+        .iter()
+        .chain(&sorted2)
+        .map(|&x| EcdfGap {
+            d: (ecdf(&sorted1, x) - ecdf(&sorted2, x)).abs(),
+            at: x,
+        })
+        .max_by(|a, b| a.d.total_cmp(&b.d))
+}
+
+/// Computes approximate p-value for KS test.
+#[must_use]
+pub fn ks_pvalue(d: f64, n1: usize, n2: usize) -> f64 {
+    if d <= 0.0 {
+        return 1.0;
+    }
+    if d >= 1.0 {
+        return 0.0;
+    }
+
+    let n = (n1 as f64 * n2 as f64) / (n1 as f64 + n2 as f64);
+    let sqrt_n = n.sqrt();
+
+    let lambda = (sqrt_n + 0.12 + 0.11 / sqrt_n) * d;
+    let lambda_sq = lambda * lambda;
+
+    let mut sum = 0.0;
+    for k in 1..=100 {
+        let k_f = f64::from(k);
+        let term = (-2.0 * k_f * k_f * lambda_sq).exp();
+        if k % 2 == 1 {
+            sum += term;
+        } else {
+            sum -= term;
+        }
+        if term < 1e-10 {
+            break;
+        }
+    }
+
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Computes the two-sample KS test p-value.
+#[must_use]
+pub fn ks_test_pvalue(sample1: &[f64], sample2: &[f64]) -> f64 {
+    let d = ks_statistic(sample1, sample2);
+    ks_pvalue(d, sample1.len(), sample2.len())
+}
+
+/// Computes basic statistics for a sample.
+#[must_use]
+pub fn compute_stats(sample: &[f64]) -> (f64, f64) {
+    if sample.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let n = sample.len() as f64;
+    let mean = sample.iter().sum::<f64>() / n;
+    let variance = sample.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    let std = variance.sqrt();
+
+    (mean, std)
+}
+
+/// Analytic moments of a distribution.
+#[derive(Debug, Clone, Copy)]
+pub struct Moments {
+    /// Population mean.
+    pub mean: f64,
+    /// Population standard deviation.
+    pub std: f64,
+    /// Excess kurtosis (0 for the normal distribution).
+    pub excess_kurtosis: f64,
+}
+
+impl Moments {
+    /// Standard errors of the sample mean and sample std at `n` draws.
+    ///
+    /// The std error uses the large-sample approximation
+    /// `sigma * sqrt((excess_kurtosis + 2) / 4n)`.
+    #[must_use]
+    pub fn standard_errors(&self, n: usize) -> (f64, f64) {
+        let n = n as f64;
+        (
+            self.std / n.sqrt(),
+            self.std * ((self.excess_kurtosis + 2.0) / (4.0 * n)).sqrt(),
+        )
+    }
+}
+
+/// Returns the analytic moments of a distribution, using the same parameter
+/// names as the test specs. Returns `None` for unknown distributions or
+/// missing parameters.
+#[must_use]
+#[allow(clippy::suboptimal_flops)] // Textbook formulas read better than mul_add chains
+pub fn analytic_moments<S: BuildHasher>(
+    distribution: &str,
+    params: &HashMap<String, f64, S>,
+) -> Option<Moments> {
+    let p = |key: &str| params.get(key).copied();
+
+    match distribution.to_lowercase().as_str() {
+        "normal" => Some(Moments {
+            mean: p("mean")?,
+            std: p("sd")?,
+            excess_kurtosis: 0.0,
+        }),
+        "uniform" => {
+            let (min, max) = (p("min")?, p("max")?);
+            Some(Moments {
+                mean: f64::midpoint(min, max),
+                std: (max - min) / 12.0_f64.sqrt(),
+                excess_kurtosis: -1.2,
+            })
+        }
+        "lognormal" => {
+            let (mu, sigma) = (p("meanlog")?, p("sdlog")?);
+            let s2 = sigma * sigma;
+            Some(Moments {
+                mean: (mu + s2 / 2.0).exp(),
+                std: (s2.exp_m1() * (2.0 * mu + s2).exp()).sqrt(),
+                excess_kurtosis: (4.0 * s2).exp() + 2.0 * (3.0 * s2).exp() + 3.0 * (2.0 * s2).exp()
+                    - 6.0,
+            })
+        }
+        "triangular" => {
+            let (a, c, b) = (p("min")?, p("mode")?, p("max")?);
+            Some(Moments {
+                mean: (a + b + c) / 3.0,
+                std: ((a.powi(2) + b.powi(2) + c.powi(2) - a * b - a * c - b * c) / 18.0).sqrt(),
+                excess_kurtosis: -0.6,
+            })
+        }
+        "pert" => {
+            let (min, mode, max) = (p("min")?, p("mode")?, p("max")?);
+            let shape = p("shape").unwrap_or(4.0);
+            let width = max - min;
+            let alpha = 1.0 + shape * (mode - min) / width;
+            let beta = 1.0 + shape * (max - mode) / width;
+            let sum = alpha + beta;
+            Some(Moments {
+                mean: min + width * alpha / sum,
+                std: width * (alpha * beta / (sum.powi(2) * (sum + 1.0))).sqrt(),
+                excess_kurtosis: 6.0
+                    * ((alpha - beta).powi(2) * (sum + 1.0) - alpha * beta * (sum + 2.0))
+                    / (alpha * beta * (sum + 2.0) * (sum + 3.0)),
+            })
+        }
+        "exponential" => {
+            let rate = p("rate")?;
+            Some(Moments {
+                mean: 1.0 / rate,
+                std: 1.0 / rate,
+                excess_kurtosis: 6.0,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_tolerance_pass() {
+        assert!(within_tolerance(100.5, 100.0, 0.01));
+    }
+
+    #[test]
+    fn test_within_tolerance_fail() {
+        assert!(!within_tolerance(102.0, 100.0, 0.01));
+    }
+
+    #[test]
+    fn test_record_comparisons_is_scoped() {
+        // Outside a recording scope comparisons are just checks
+        assert!(compare("ignored", 1.0, 1.0, 0.01));
+
+        let (passed, outer) = record_comparisons(|| {
+            let (_, inner) = record_comparisons(|| compare_abs("bias", 0.5, 0.0, 0.1));
+            assert_eq!(inner.comparisons.len(), 1);
+            assert!(!inner.comparisons[0].passed);
+            compare("mean", 100.5, 100.0, 0.01)
+        });
+
+        assert!(passed);
+        assert!(outer.samples.is_none());
+        let outer = outer.comparisons;
+        assert_eq!(outer.len(), 1);
+        assert_eq!(outer[0].metric, "mean");
+        assert!((outer[0].relative_diff - 0.005).abs() < 1e-12);
+        assert_eq!(outer[0].tolerance, Some(0.01));
+    }
+
+    #[test]
+    fn test_ecdf_max_gap() {
+        let gap = ecdf_max_gap(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(gap, EcdfGap { d: 1.0, at: 3.0 });
+
+        let gap = ecdf_max_gap(&[1.0, 2.0, 3.0, 4.0], &[1.5, 2.0, 2.5, 3.5]).unwrap();
+        assert!((gap.d - 0.25).abs() < 1e-12);
+        assert!(ecdf_max_gap(&[], &[1.0]).is_none());
+    }
+
+    #[test]
+    fn test_tolerance_default() {
+        let tol = Tolerance::default();
+        assert!((tol.mean - 0.01).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_analytic_moments() {
+        let params = HashMap::from([("rate".to_string(), 0.1)]);
+        let m = analytic_moments("exponential", &params).unwrap();
+        assert!((m.mean - 10.0).abs() < 1e-12);
+
+        // Symmetric PERT(0, 50, 100) with shape 4 is Beta(3, 3) scaled by 100
+        let params = HashMap::from([
+            ("min".to_string(), 0.0),
+            ("mode".to_string(), 50.0),
+            ("max".to_string(), 100.0),
+        ]);
+        let m = analytic_moments("pert", &params).unwrap();
+        assert!((m.mean - 50.0).abs() < 1e-12);
+        assert!((m.std - 100.0 / 28.0_f64.sqrt()).abs() < 1e-12);
+        assert!((m.excess_kurtosis + 6.0 / 9.0).abs() < 1e-12);
+
+        let (se_mean, se_std) = Moments {
+            mean: 0.0,
+            std: 1.0,
+            excess_kurtosis: 0.0,
+        }
+        .standard_errors(200);
+        assert!((se_mean - 200.0_f64.sqrt().recip()).abs() < 1e-12);
+        assert!((se_std - 0.05).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,100 @@
+//! Multiple-comparison correction across a run.
+//!
+//! Each goodness-of-fit test fails a correct forge with probability equal
+//! to its threshold, so a run of dozens of them fails somewhere far more
+//! often. Adjusting every p-value of the run together bounds that: Holm
+//! controls the chance of any false failure, Benjamini-Hochberg the
+//! expected share of false failures among the failures. Equivalence (TOST)
+//! p-values join the same family, so each equivalence shown holds for the
+//! run as a whole.
+
+#![allow(clippy::cast_precision_loss)]
+
+/// How the p-values of a run are adjusted for multiple comparisons.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PAdjust {
+    /// Raw p-values; each test is judged alone.
+    #[default]
+    None,
+    /// Holm-Bonferroni step-down (family-wise error rate).
+    Holm,
+    /// Benjamini-Hochberg step-up (false discovery rate).
+    #[value(alias = "fdr")]
+    Bh,
+}
+
+impl PAdjust {
+    /// Name shown in results.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Holm => "Holm",
+            Self::Bh => "Benjamini-Hochberg",
+        }
+    }
+}
+
+/// Adjusts `p_values` by `method`, returning them in the same order (as R's
+/// `p.adjust`).
+#[must_use]
+pub fn adjust_p_values(method: PAdjust, p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+
+    let mut adjusted = p_values.to_vec();
+    match method {
+        PAdjust::None => {}
+        PAdjust::Holm => {
+            // Smallest p times m, next times m - 1, ..., kept non-decreasing
+            let mut running = 0.0_f64;
+            for (rank, &i) in order.iter().enumerate() {
+                running = running.max(((m - rank) as f64 * p_values[i]).min(1.0));
+                adjusted[i] = running;
+            }
+        }
+        PAdjust::Bh => {
+            // Largest p unchanged, the one at rank k times m/k, kept
+            // non-increasing from the top
+            let mut running = 1.0_f64;
+            for (rank, &i) in order.iter().enumerate().rev() {
+                running = running.min(m as f64 / (rank + 1) as f64 * p_values[i]);
+                adjusted[i] = running;
+            }
+        }
+    }
+    adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjustments_match_r_p_adjust() {
+        let close = |actual: &[f64], expected: &[f64]| {
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-12)
+        };
+        let p = [0.01, 0.04, 0.03, 0.005];
+
+        // p.adjust(c(0.01, 0.04, 0.03, 0.005), "holm") and "BH"
+        assert!(close(
+            &adjust_p_values(PAdjust::Holm, &p),
+            &[0.03, 0.06, 0.06, 0.02]
+        ));
+        assert!(close(
+            &adjust_p_values(PAdjust::Bh, &p),
+            &[0.02, 0.04, 0.04, 0.02]
+        ));
+        assert!(close(&adjust_p_values(PAdjust::None, &p), &p));
+        assert!(close(
+            &adjust_p_values(PAdjust::Holm, &[0.6, 0.3]),
+            &[0.6, 0.6]
+        ));
+        assert!(adjust_p_values(PAdjust::Bh, &[]).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Sampling error of Monte Carlo estimates.
+//!
+//! Two independent samples of the same distribution still differ in mean,
+//! std and quantiles by an amount that shrinks as 1/sqrt(n). These standard
+//! errors turn a target false-failure rate into a tolerance for n draws.
+
+#![allow(clippy::cast_precision_loss)]
+
+use std::f64::consts::PI;
+
+use super::Moments;
+
+/// Smallest sample used to estimate the density at a quantile.
+const MIN_DENSITY_SAMPLE: usize = 100;
+
+/// Sample moments, with the std and the excess kurtosis from central
+/// moments over n. `None` for fewer than 4 values or a constant sample.
+#[must_use]
+pub fn sample_moments(sample: &[f64]) -> Option<Moments> {
+    if sample.len() < 4 {
+        return None;
+    }
+    let n = sample.len() as f64;
+    let mean = sample.iter().sum::<f64>() / n;
+    let central = |power: i32| sample.iter().map(|x| (x - mean).powi(power)).sum::<f64>() / n;
+    let (m2, m4) = (central(2), central(4));
+    (m2 > 0.0).then(|| Moments {
+        mean,
+        std: m2.sqrt(),
+        excess_kurtosis: m4 / (m2 * m2) - 3.0,
+    })
+}
+
+/// Standard error of the `p` quantile (0 < p < 1) of `n` draws, from
+/// `sqrt(p(1 - p)/n) / f(q_p)`.
+///
+/// The density `f` at the quantile is estimated from `sample` by the
+/// difference quotient of its quantiles at `p ± h`, h = len^(-1/3).
+/// `None` if the sample is too small or flat there (a discrete output).
+#[must_use]
+pub fn quantile_standard_error(sample: &[f64], p: f64, n: usize) -> Option<f64> {
+    if sample.len() < MIN_DENSITY_SAMPLE {
+        return None;
+    }
+    let mut sorted = sample.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let h = (sorted.len() as f64)
+        .powf(-1.0 / 3.0)
+        .min(p / 2.0)
+        .min((1.0 - p) / 2.0);
+    let sparsity = (quantile(&sorted, p + h) - quantile(&sorted, p - h)) / (2.0 * h);
+    (sparsity > 0.0).then(|| sparsity * (p * (1.0 - p) / n as f64).sqrt())
+}
+
+/// Standard error of the `p` quantile of `n` draws from a normal
+/// distribution with this `std`; the fallback without samples.
+#[must_use]
+pub fn normal_quantile_standard_error(std: f64, p: f64, n: usize) -> f64 {
+    let z = normal_quantile(p);
+    let density = (-z * z / 2.0).exp() / (2.0 * PI).sqrt();
+    std * (p * (1.0 - p) / n as f64).sqrt() / density
+}
+
+/// Number of standard errors a deviation may reach so that `comparisons`
+/// two-sided checks fail together with probability at most
+/// `false_failure_rate` (Bonferroni).
+#[must_use]
+pub fn critical_z(false_failure_rate: f64, comparisons: usize) -> f64 {
+    -normal_quantile(false_failure_rate / (2.0 * comparisons.max(1) as f64))
+}
+
+/// Quantile of sorted values, interpolating between order statistics
+/// (R's default, type 7).
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let below = position.floor();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let index = below as usize;
+    let above = sorted.get(index + 1).unwrap_or(&sorted[index]);
+    (above - sorted[index]).mul_add(position - below, sorted[index])
+}
+
+/// Standard normal CDF, from the complementary error function (Numerical
+/// Recipes' `erfcc`, relative error below 1.2e-7).
+#[must_use]
+pub fn normal_cdf(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 10] = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ];
+    let y = (x / std::f64::consts::SQRT_2).abs();
+    let t = 1.0 / y.mul_add(0.5, 1.0);
+    let series = COEFFICIENTS
+        .iter()
+        .rev()
+        .fold(0.0, |acc: f64, &c| acc.mul_add(t, c));
+    // erfc(|x|/sqrt(2)); halved, that is the upper tail beyond |x|
+    let erfc = t * (-y).mul_add(y, series).exp();
+    if x >= 0.0 {
+        1.0 - erfc / 2.0
+    } else {
+        erfc / 2.0
+    }
+}
+
+/// Inverse of the standard normal CDF (Acklam's rational approximation,
+/// relative error below 1.2e-9).
+#[must_use]
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.024_25;
+
+    // Coefficients run from the highest power down
+    let horner = |coefficients: &[f64], x: f64| {
+        coefficients
+            .iter()
+            .fold(0.0, |acc: f64, &c| acc.mul_add(x, c))
+    };
+    let tail = |q: f64| horner(&C, q) / horner(&D, q).mul_add(q, 1.0);
+
+    if p <= 0.0 {
+        f64::NEG_INFINITY
+    } else if p >= 1.0 {
+        f64::INFINITY
+    } else if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        q * horner(&A, r) / horner(&B, r).mul_add(r, 1.0)
+    } else {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_quantiles_match_tables() {
+        assert!((normal_quantile(0.975) - 1.959_964).abs() < 1e-6);
+        assert!((normal_quantile(0.95) - 1.644_854).abs() < 1e-6);
+        assert!((normal_quantile(0.5)).abs() < 1e-12);
+        assert!((normal_quantile(0.001) + 3.090_232).abs() < 1e-6);
+        assert!((critical_z(0.05, 1) - 1.959_964).abs() < 1e-6);
+        assert!((normal_cdf(1.959_964) - 0.975).abs() < 1e-7);
+        assert!((normal_cdf(-3.090_232) - 0.001).abs() < 1e-8);
+        // Five checks sharing 0.001: 0.0001 in each tail of each
+        assert!((critical_z(0.001, 5) - 3.719_016).abs() < 1e-6);
+    }
+
+    #[test]
+    fn standard_errors_of_a_uniform_sample() {
+        let sample: Vec<f64> = (0..10_000)
+            .map(|i| (f64::from(i) + 0.5) / 10_000.0)
+            .collect();
+
+        let moments = sample_moments(&sample).unwrap();
+        assert!((moments.mean - 0.5).abs() < 1e-12);
+        assert!((moments.excess_kurtosis + 1.2).abs() < 1e-3);
+
+        // Density 1 everywhere: SE = sqrt(p(1 - p)/n)
+        let se = quantile_standard_error(&sample, 0.5, 10_000).unwrap();
+        assert!((se - 0.005).abs() < 1e-6);
+        let se = quantile_standard_error(&sample, 0.05, 2_500).unwrap();
+        assert!((se - (0.05_f64 * 0.95 / 2_500.0).sqrt()).abs() < 1e-6);
+
+        assert!(quantile_standard_error(&[1.0; 1_000], 0.5, 1_000).is_none());
+        assert!(sample_moments(&[1.0; 10]).is_none());
+    }
+}
@@ -0,0 +1,493 @@
+//! Bayesian network posteriors validated against `bayesian_validator.R`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{parse_both, parse_output, r_results, to_payload, ForgeInvocation, Output, Suite};
+use crate::stats::compare_abs;
+use crate::types::{AnalyticsTestSpec, RExpected, TestResult};
+
+/// Runs `forge bayesian` with the query target and evidence and compares every
+/// state probability, and the most probable state, with `bnlearn`.
+pub struct BayesianSuite;
+
+impl Suite for BayesianSuite {
+    fn name(&self) -> &'static str {
+        "bayesian"
+    }
+
+    fn validator(&self) -> &'static str {
+        "bayesian_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        BayesianSpec::parse(spec).map(drop)
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let BayesianSpec { network, query } = BayesianSpec::parse(spec)?;
+
+        let mut invocation =
+            ForgeInvocation::new("bayesian", build_bayesian_fixture(&spec.name, &network))
+                .arg("--query")
+                .arg(&query.target);
+        let mut evidence: Vec<_> = query.evidence.iter().collect();
+        evidence.sort();
+        for (node, state) in evidence {
+            invocation = invocation.arg("--evidence").arg(format!("{node}={state}"));
+        }
+        Ok(invocation)
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        let BayesianSpec { network, query } = BayesianSpec::parse(spec)?;
+
+        // CPTs go to R in bnlearn array order
+        let cpts = network
+            .cpts
+            .iter()
+            .map(|(node, cpt)| {
+                let probs = network
+                    .cpt_rows(node)
+                    .into_iter()
+                    .flat_map(|(_, row)| row)
+                    .collect();
+                let cpt = CptParams {
+                    levels: &cpt.levels,
+                    parents: &cpt.parents,
+                    probs,
+                };
+                (node.as_str(), cpt)
+            })
+            .collect();
+
+        to_payload(&BayesianParams {
+            network: NetworkParams {
+                nodes: &network.nodes,
+                arcs: &network.arcs,
+                cpts,
+            },
+            query: &query,
+            seed: spec.seed,
+        })
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) = match parse_both(spec, forge, r, parse_posterior, parse_r_posterior) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+
+        compare_posteriors(&spec.name, &forge, &r, tolerance(spec))
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(spec, output, parse_posterior, parse_r_posterior) {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        if expected.posterior.is_empty() {
+            return TestResult::Skip {
+                name: spec.name.clone(),
+                reason: "No posterior recorded".to_string(),
+            };
+        }
+
+        compare_posteriors(&spec.name, &actual, &expected.posterior, tolerance(spec))
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        Ok(RExpected {
+            posterior: parse_r_posterior(r)?,
+            ..Default::default()
+        })
+    }
+}
+
+/// A Bayesian test: the network and the query posed to it.
+#[derive(Debug, Clone, Deserialize)]
+struct BayesianSpec {
+    network: BayesianNetwork,
+    query: BayesianQuery,
+}
+
+impl BayesianSpec {
+    /// Parses the `network` and `query` keys of a test spec and checks that
+    /// the query and every CPT refer to the network's nodes and states.
+    fn parse(spec: &AnalyticsTestSpec) -> Result<Self, String> {
+        if !spec.extra.contains_key("network") || !spec.extra.contains_key("query") {
+            return Err("No network/query specified (not a Bayesian test)".to_string());
+        }
+        let parsed: Self =
+            serde_yaml_ng::from_value(serde_yaml_ng::Value::Mapping(spec.extra.clone()))
+                .map_err(|e| format!("Invalid Bayesian spec: {e}"))?;
+        parsed.network.check()?;
+
+        let levels = |node: &str| {
+            parsed
+                .network
+                .cpts
+                .get(node)
+                .map(|cpt| cpt.levels.as_slice())
+                .ok_or_else(|| format!("Query names unknown node '{node}'"))
+        };
+        levels(&parsed.query.target)?;
+        for (node, state) in &parsed.query.evidence {
+            if !levels(node)?.contains(state) {
+                return Err(format!(
+                    "Evidence {node}={state} is not a state of '{node}'"
+                ));
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Bayesian network specification.
+#[derive(Debug, Clone, Deserialize)]
+struct BayesianNetwork {
+    /// Node names.
+    nodes: Vec<String>,
+    /// Directed arcs as `[from, to]` pairs.
+    #[serde(default)]
+    arcs: Vec<[String; 2]>,
+    /// Conditional probability table per node.
+    cpts: HashMap<String, CptSpec>,
+}
+
+/// Conditional probability table for one node.
+#[derive(Debug, Clone, Deserialize)]
+struct CptSpec {
+    /// State names of the node.
+    levels: Vec<String>,
+    /// Parent node names (empty for root nodes).
+    #[serde(default)]
+    parents: Vec<String>,
+    /// Probabilities, one row per node level. Within a row the columns are
+    /// the parent-state combinations, first parent varying fastest.
+    probs: Vec<f64>,
+}
+
+impl BayesianNetwork {
+    /// Checks that every node has a CPT whose parents are nodes and whose
+    /// probabilities fill one row per level.
+    fn check(&self) -> Result<(), String> {
+        for node in &self.nodes {
+            let cpt = self
+                .cpts
+                .get(node)
+                .ok_or_else(|| format!("No CPT for node '{node}'"))?;
+            let mut combinations = 1;
+            for parent in &cpt.parents {
+                let parent_cpt = self
+                    .cpts
+                    .get(parent)
+                    .ok_or_else(|| format!("Node '{node}' has unknown parent '{parent}'"))?;
+                combinations *= parent_cpt.levels.len();
+            }
+            let expected = cpt.levels.len() * combinations;
+            if cpt.probs.len() != expected {
+                return Err(format!(
+                    "CPT of '{node}' has {} probabilities, expected {expected}",
+                    cpt.probs.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the CPT of `node` as one distribution per parent-state combination.
+    ///
+    /// Each entry pairs the parent states (in `parents` order) with the
+    /// probabilities over the node's levels. Root nodes yield a single entry
+    /// with no parent states.
+    fn cpt_rows(&self, node: &str) -> Vec<(Vec<&str>, Vec<f64>)> {
+        let Some(cpt) = self.cpts.get(node) else {
+            return Vec::new();
+        };
+        let parent_levels: Vec<&[String]> = cpt
+            .parents
+            .iter()
+            .map(|p| self.cpts.get(p).map_or(&[][..], |c| c.levels.as_slice()))
+            .collect();
+        let combinations: usize = parent_levels.iter().map(|l| l.len().max(1)).product();
+
+        (0..combinations)
+            .map(|combination| {
+                let mut index = combination;
+                let states = parent_levels
+                    .iter()
+                    .map(|levels| {
+                        let len = levels.len().max(1);
+                        let state = levels.get(index % len).map_or("", String::as_str);
+                        index /= len;
+                        state
+                    })
+                    .collect();
+                let probs = (0..cpt.levels.len())
+                    .filter_map(|level| cpt.probs.get(level * combinations + combination))
+                    .copied()
+                    .collect();
+                (states, probs)
+            })
+            .collect()
+    }
+}
+
+/// Bayesian inference query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BayesianQuery {
+    /// Node whose posterior is requested.
+    target: String,
+    /// Observed node states.
+    #[serde(default)]
+    evidence: HashMap<String, String>,
+}
+
+/// Absolute tolerance for probabilities (2% per the bayesian.yaml header).
+fn tolerance(spec: &AnalyticsTestSpec) -> f64 {
+    spec.tolerance
+        .as_ref()
+        .and_then(|t| t.probability)
+        .unwrap_or(0.02)
+}
+
+fn parse_r_posterior(r: &Value) -> Result<HashMap<String, f64>, String> {
+    r_results::<BayesianResults>(r).map(|r| r.posterior)
+}
+
+/// JSON payload for `bayesian_validator.R`.
+#[derive(Serialize)]
+struct BayesianParams<'a> {
+    network: NetworkParams<'a>,
+    query: &'a BayesianQuery,
+    seed: u64,
+}
+
+/// Network with CPTs flattened in bnlearn array order.
+#[derive(Serialize)]
+struct NetworkParams<'a> {
+    nodes: &'a [String],
+    arcs: &'a [[String; 2]],
+    cpts: HashMap<&'a str, CptParams<'a>>,
+}
+
+/// One node's CPT in bnlearn array order.
+#[derive(Serialize)]
+struct CptParams<'a> {
+    levels: &'a [String],
+    parents: &'a [String],
+    probs: Vec<f64>,
+}
+
+/// Results of `bayesian_validator.R` for a query.
+#[derive(Debug, Deserialize)]
+struct BayesianResults {
+    posterior: HashMap<String, f64>,
+}
+
+/// Builds the forge YAML model for a Bayesian network.
+fn build_bayesian_fixture(name: &str, network: &BayesianNetwork) -> String {
+    let mut yaml =
+        format!("_forge_version: \"5.0.0\"\nbayesian_network:\n  name: \"{name}\"\n  nodes:\n");
+    for node in &network.nodes {
+        let Some(cpt) = network.cpts.get(node) else {
+            continue;
+        };
+        let _ = writeln!(yaml, "    {node}:");
+        let _ = writeln!(yaml, "      type: discrete");
+        let _ = writeln!(yaml, "      states: {:?}", cpt.levels);
+
+        let rows = network.cpt_rows(node);
+        if cpt.parents.is_empty() {
+            let prior = rows.into_iter().next().map(|(_, p)| p).unwrap_or_default();
+            let _ = writeln!(yaml, "      prior: {prior:?}");
+        } else {
+            let _ = writeln!(yaml, "      parents: {:?}", cpt.parents);
+            let _ = writeln!(yaml, "      cpt:");
+            for (states, probs) in rows {
+                let _ = writeln!(yaml, "        \"{}\": {probs:?}", states.join(","));
+            }
+        }
+    }
+    yaml
+}
+
+/// Parses forge's posterior distribution (`state -> probability`), nested
+/// under `bayesian_results`.
+fn parse_posterior(json: &Value) -> Result<HashMap<String, f64>, String> {
+    let results = json.get("bayesian_results").unwrap_or(json);
+    let posterior = results
+        .get("posterior")
+        .and_then(Value::as_object)
+        .ok_or("Missing posterior")?;
+
+    posterior
+        .iter()
+        .map(|(state, p)| {
+            p.as_f64()
+                .map(|p| (state.clone(), p))
+                .ok_or_else(|| format!("Non-numeric probability for state '{state}'"))
+        })
+        .collect()
+}
+
+/// Compares forge and R posteriors, returning Pass or Fail, or Error if R
+/// has no states or the two disagree on which states there are.
+fn compare_posteriors(
+    test_name: &str,
+    forge: &HashMap<String, f64>,
+    r: &HashMap<String, f64>,
+    tolerance: f64,
+) -> TestResult {
+    let error = |error: String| TestResult::Error {
+        name: test_name.to_string(),
+        error,
+    };
+    if r.is_empty() {
+        return error("R posterior has no states".to_string());
+    }
+    let mut states: Vec<_> = r.keys().collect();
+    states.sort();
+    let mut forge_states: Vec<_> = forge.keys().collect();
+    forge_states.sort();
+    if forge_states != states {
+        return error(format!(
+            "Posterior states differ: forge={forge_states:?}, R={states:?}"
+        ));
+    }
+
+    for state in states {
+        let (forge_p, r_p) = (forge[state], r[state]);
+        if !compare_abs(format!("P({state})"), forge_p, r_p, tolerance) {
+            return TestResult::Fail {
+                name: test_name.to_string(),
+                reason: format!(
+                    "P({state}) mismatch: forge={forge_p:.4}, R={r_p:.4} (diff={:.4}, tol={tolerance})",
+                    (forge_p - r_p).abs()
+                ),
+            };
+        }
+    }
+
+    // Most probable state must agree, unless R itself cannot separate the
+    // top two states (within tolerance), in which case any order is valid.
+    let mut ranked: Vec<(&String, f64)> = r.iter().map(|(s, &p)| (s, p)).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let forge_top = forge
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(s, _)| s);
+
+    match ranked.as_slice() {
+        [(top, p1), (second, p2), ..] if p1 - p2 <= tolerance => TestResult::Pass {
+            name: test_name.to_string(),
+            details: format!(
+                "{} states within tolerance\nTie at top: R cannot separate {top} and {second}",
+                r.len()
+            ),
+        },
+        [(top, _), ..] if forge_top != Some(*top) => TestResult::Fail {
+            name: test_name.to_string(),
+            reason: format!(
+                "Most probable state mismatch: forge={}, R={top}",
+                forge_top.map_or("none", String::as_str)
+            ),
+        },
+        _ => TestResult::Pass {
+            name: test_name.to_string(),
+            details: format!("{} states within tolerance", r.len()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn posterior(states: &[(&str, f64)]) -> HashMap<String, f64> {
+        states.iter().map(|&(s, p)| (s.to_string(), p)).collect()
+    }
+
+    #[test]
+    fn cpt_rows_per_parent_combination() {
+        let yaml = r#"
+network:
+  nodes: ["Rain", "Sprinkler", "Grass"]
+  arcs: [["Rain", "Grass"], ["Sprinkler", "Grass"]]
+  cpts:
+    Rain:
+      levels: ["no", "yes"]
+      probs: [0.8, 0.2]
+    Sprinkler:
+      levels: ["off", "on"]
+      probs: [0.6, 0.4]
+    Grass:
+      levels: ["dry", "wet"]
+      parents: ["Rain", "Sprinkler"]
+      probs: [1.0, 0.2, 0.1, 0.01, 0.0, 0.8, 0.9, 0.99]
+query:
+  target: "Rain"
+  evidence:
+    Grass: "wet"
+"#;
+        let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(yaml).unwrap();
+        let BayesianSpec { network, query } = BayesianSpec::parse(&spec).unwrap();
+        assert_eq!(query.evidence["Grass"], "wet");
+
+        let rows = network.cpt_rows("Grass");
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1].0, vec!["yes", "off"]);
+        assert_eq!(rows[1].1, vec![0.2, 0.8]);
+        assert_eq!(rows[2].0, vec!["no", "on"]);
+        assert_eq!(rows[2].1, vec![0.1, 0.9]);
+
+        let root = network.cpt_rows("Rain");
+        assert_eq!(root.len(), 1);
+        assert!(root[0].0.is_empty());
+
+        let bad = yaml.replace(r#"Grass: "wet""#, r#"Grass: "soggy""#);
+        let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(&bad).unwrap();
+        let err = BayesianSpec::parse(&spec).unwrap_err();
+        assert!(err.contains("Grass=soggy"), "{err}");
+    }
+
+    #[test]
+    fn most_probable_state_must_agree_unless_tied() {
+        let r = posterior(&[("a", 0.40), ("b", 0.37), ("c", 0.23)]);
+        let forge = posterior(&[("a", 0.385), ("b", 0.388), ("c", 0.227)]);
+        let TestResult::Fail { reason, .. } = compare_posteriors("t", &forge, &r, 0.02) else {
+            panic!("expected a failure");
+        };
+        assert!(reason.contains("forge=b, R=a"), "{reason}");
+
+        let r = posterior(&[("a", 0.50), ("b", 0.49), ("c", 0.01)]);
+        let forge = posterior(&[("a", 0.49), ("b", 0.50), ("c", 0.01)]);
+        let TestResult::Pass { details, .. } = compare_posteriors("t", &forge, &r, 0.02) else {
+            panic!("expected a pass");
+        };
+        assert!(details.contains("Tie at top"), "{details}");
+    }
+
+    #[test]
+    fn missing_states_are_errors() {
+        let forge = posterior(&[("no", 0.05), ("yes", 0.95)]);
+        assert!(matches!(
+            compare_posteriors("t", &forge, &HashMap::new(), 0.02),
+            TestResult::Error { .. }
+        ));
+        let r = posterior(&[("no", 0.05), ("maybe", 0.0), ("yes", 0.95)]);
+        assert!(matches!(
+            compare_posteriors("t", &forge, &r, 0.02),
+            TestResult::Error { .. }
+        ));
+    }
+}
@@ -0,0 +1,466 @@
+//! Bootstrap confidence intervals validated against `bootstrap_validator.R`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    map_or_empty, parse_both, parse_output, r_results, to_payload, ForgeInvocation, Output, Suite,
+};
+use crate::stats::{compare, compare_abs, compare_either, relative_difference, Tolerance};
+use crate::types::{AnalyticsTestSpec, ConfidenceInterval, RExpected, TestResult};
+
+/// Runs `forge predict --bootstrap` and compares the original estimate, bias,
+/// std error and every CI bound with R's `boot` package.
+pub struct BootstrapSuite;
+
+impl Suite for BootstrapSuite {
+    fn name(&self) -> &'static str {
+        "bootstrap"
+    }
+
+    fn validator(&self) -> &'static str {
+        "bootstrap_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        if spec.data.is_empty() {
+            return Err("No data specified (not a bootstrap test)".to_string());
+        }
+        Ok(())
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let fixture = format!(
+            r#"_forge_version: "5.0.0"
+bootstrap:
+  iterations: {iterations}
+  seed: {seed}
+  statistic: {statistic}
+  method: {method}
+  confidence_levels: {levels:?}
+  data: {data:?}
+"#,
+            iterations = spec.iterations,
+            seed = spec.seed,
+            statistic = statistic(spec),
+            method = method(spec),
+            levels = confidence_levels(spec),
+            data = spec.data,
+        );
+
+        Ok(ForgeInvocation::new("predict", fixture)
+            .arg("--bootstrap")
+            .arg("--seed")
+            .arg(spec.seed.to_string()))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        to_payload(&BootstrapParams {
+            data: &spec.data,
+            statistic: statistic(spec),
+            method: method(spec),
+            confidence_levels: &confidence_levels(spec),
+            seed: spec.seed,
+            iterations: spec.iterations,
+        })
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) = match parse_both(
+            spec,
+            forge,
+            r,
+            parse_bootstrap_stats,
+            parse_r_bootstrap_stats,
+        ) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+
+        compare_bootstrap_results(
+            &spec.name,
+            &forge,
+            &r,
+            &confidence_levels(spec),
+            &tolerance(spec),
+        )
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual =
+            match parse_output(spec, output, parse_bootstrap_stats, parse_r_bootstrap_stats) {
+                Ok(actual) => actual,
+                Err(result) => return result,
+            };
+        let confidence_intervals = match expected
+            .confidence_intervals
+            .iter()
+            .map(|(key, ci)| parse_level(key).map(|level| (level, ci.lower, ci.upper)))
+            .collect()
+        {
+            Ok(intervals) => intervals,
+            Err(error) => {
+                return TestResult::Error {
+                    name: spec.name.clone(),
+                    error: format!("Invalid r_expected: {error}"),
+                }
+            }
+        };
+        let recorded = BootstrapStats {
+            original_estimate: expected
+                .original_estimate
+                .unwrap_or(actual.original_estimate),
+            mean: expected.mean.unwrap_or(actual.mean),
+            std: expected.std.unwrap_or(actual.std),
+            bias: expected.bias.unwrap_or(actual.bias),
+            confidence_intervals,
+        };
+
+        compare_bootstrap_results(
+            &spec.name,
+            &actual,
+            &recorded,
+            &confidence_levels(spec),
+            &tolerance(spec),
+        )
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let results = r_results::<BootstrapResults>(r)?;
+        Ok(RExpected {
+            original_estimate: Some(results.original_estimate),
+            mean: Some(results.mean),
+            std: Some(results.std),
+            bias: Some(results.bias),
+            confidence_intervals: results
+                .confidence_intervals
+                .into_iter()
+                .map(|(level, ci)| {
+                    let ci = ConfidenceInterval {
+                        lower: ci.lower,
+                        upper: ci.upper,
+                    };
+                    (level, ci)
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+fn tolerance(spec: &AnalyticsTestSpec) -> Tolerance {
+    spec.tolerance
+        .as_ref()
+        .map(|t| Tolerance {
+            mean: t.mean.unwrap_or(0.01),
+            std: t.std.unwrap_or(0.05),
+            ci_bounds: t.ci_bounds.unwrap_or(0.02),
+            ..Default::default()
+        })
+        .unwrap_or_default()
+}
+
+fn statistic(spec: &AnalyticsTestSpec) -> &str {
+    spec.statistic.as_deref().unwrap_or("mean")
+}
+
+fn method(spec: &AnalyticsTestSpec) -> &str {
+    spec.method.as_deref().unwrap_or("percentile")
+}
+
+fn confidence_levels(spec: &AnalyticsTestSpec) -> Vec<f64> {
+    if spec.confidence_levels.is_empty() {
+        vec![0.95]
+    } else {
+        spec.confidence_levels.clone()
+    }
+}
+
+/// JSON payload for `bootstrap_validator.R`.
+#[derive(Serialize)]
+struct BootstrapParams<'a> {
+    data: &'a [f64],
+    statistic: &'a str,
+    method: &'a str,
+    confidence_levels: &'a [f64],
+    seed: u64,
+    iterations: usize,
+}
+
+/// Parsed bootstrap results from either forge or R.
+#[derive(Debug)]
+struct BootstrapStats {
+    original_estimate: f64,
+    mean: f64,
+    std: f64,
+    bias: f64,
+    /// `(level, lower, upper)` for each confidence level.
+    confidence_intervals: Vec<(f64, f64, f64)>,
+}
+
+impl BootstrapStats {
+    fn interval(&self, level: f64) -> Option<(f64, f64)> {
+        self.confidence_intervals
+            .iter()
+            .find(|(l, _, _)| (l - level).abs() < 1e-9)
+            .map(|&(_, lower, upper)| (lower, upper))
+    }
+}
+
+/// Results of `bootstrap_validator.R`.
+#[derive(Debug, Deserialize)]
+struct BootstrapResults {
+    original_estimate: f64,
+    mean: f64,
+    std: f64,
+    bias: f64,
+    /// Keyed by confidence level; R omits levels it could not compute.
+    #[serde(default, deserialize_with = "map_or_empty")]
+    confidence_intervals: HashMap<String, Interval>,
+}
+
+/// Confidence interval bounds.
+#[derive(Debug, Deserialize)]
+struct Interval {
+    lower: f64,
+    upper: f64,
+}
+
+impl TryFrom<BootstrapResults> for BootstrapStats {
+    type Error = String;
+
+    fn try_from(results: BootstrapResults) -> Result<Self, String> {
+        let confidence_intervals = results
+            .confidence_intervals
+            .into_iter()
+            .map(|(key, ci)| parse_level(&key).map(|level| (level, ci.lower, ci.upper)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            original_estimate: results.original_estimate,
+            mean: results.mean,
+            std: results.std,
+            bias: results.bias,
+            confidence_intervals,
+        })
+    }
+}
+
+/// Parses a confidence level key such as `"0.95"`.
+fn parse_level(key: &str) -> Result<f64, String> {
+    key.parse()
+        .map_err(|_| format!("Invalid confidence level '{key}'"))
+}
+
+fn parse_r_bootstrap_stats(r: &Value) -> Result<BootstrapStats, String> {
+    r_results::<BootstrapResults>(r).and_then(BootstrapStats::try_from)
+}
+
+/// Parses forge's bootstrap results (`bootstrap_results` with
+/// `bootstrap_mean`, `bootstrap_std_error` and a list of
+/// `{level, lower, upper}`).
+fn parse_bootstrap_stats(json: &Value) -> Result<BootstrapStats, String> {
+    let results = json.get("bootstrap_results").unwrap_or(json);
+    let field = |key: &str| {
+        results
+            .get(key)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| format!("Missing {key}"))
+    };
+
+    let confidence_intervals = results
+        .get("confidence_intervals")
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let get = |k: &str| item.get(k).and_then(Value::as_f64);
+                    Some((get("level")?, get("lower")?, get("upper")?))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(BootstrapStats {
+        original_estimate: field("original_estimate")?,
+        mean: field("bootstrap_mean")?,
+        std: field("bootstrap_std_error")?,
+        bias: field("bias")?,
+        confidence_intervals,
+    })
+}
+
+/// Compares forge and R bootstrap results, returning Pass or Fail.
+fn compare_bootstrap_results(
+    test_name: &str,
+    forge: &BootstrapStats,
+    r: &BootstrapStats,
+    confidence_levels: &[f64],
+    tolerance: &Tolerance,
+) -> TestResult {
+    let fail = |reason: String| TestResult::Fail {
+        name: test_name.to_string(),
+        reason,
+    };
+
+    // The statistic on the original data is deterministic
+    let deterministic = Tolerance::deterministic();
+    if !compare(
+        "original estimate",
+        forge.original_estimate,
+        r.original_estimate,
+        deterministic.mean,
+    ) {
+        return fail(format!(
+            "Original estimate mismatch: forge={:.6}, R={:.6}",
+            forge.original_estimate, r.original_estimate
+        ));
+    }
+
+    if !compare("mean", forge.mean, r.mean, tolerance.mean) {
+        return fail(format!(
+            "Bootstrap mean mismatch: forge={:.6}, R={:.6} (diff={:.2}%, tol={:.1}%)",
+            forge.mean,
+            r.mean,
+            relative_difference(forge.mean, r.mean) * 100.0,
+            tolerance.mean * 100.0
+        ));
+    }
+
+    if !compare("std", forge.std, r.std, tolerance.std) {
+        return fail(format!(
+            "Bootstrap std mismatch: forge={:.6}, R={:.6} (diff={:.2}%, tol={:.1}%)",
+            forge.std,
+            r.std,
+            relative_difference(forge.std, r.std) * 100.0,
+            tolerance.std * 100.0
+        ));
+    }
+
+    // Bias is a difference of two nearly equal numbers, so a relative check is
+    // meaningless. Compare it on the scale of the bootstrap standard error.
+    let bias_tolerance = tolerance.std * r.std;
+    if !compare_abs("bias", forge.bias, r.bias, bias_tolerance) {
+        return fail(format!(
+            "Bias mismatch: forge={:.6}, R={:.6} (abs tol={bias_tolerance:.6})",
+            forge.bias, r.bias
+        ));
+    }
+
+    for &level in confidence_levels {
+        let Some((r_lower, r_upper)) = r.interval(level) else {
+            // R could not compute this interval (e.g. BCa on constant data)
+            continue;
+        };
+        let Some((forge_lower, forge_upper)) = forge.interval(level) else {
+            return fail(format!(
+                "Missing {level} confidence interval in forge output"
+            ));
+        };
+
+        // Bounds near zero make a relative check meaningless, so also accept
+        // differences that are small relative to the R interval width.
+        let abs_tolerance = tolerance.ci_bounds * (r_upper - r_lower).abs();
+        for (bound, forge_val, r_val) in [
+            ("lower", forge_lower, r_lower),
+            ("upper", forge_upper, r_upper),
+        ] {
+            if !compare_either(
+                format!("{level} CI {bound}"),
+                forge_val,
+                r_val,
+                tolerance.ci_bounds,
+                abs_tolerance,
+            ) {
+                return fail(format!(
+                    "{level} CI {bound} mismatch: forge={forge_val:.6}, R={r_val:.6} (diff={:.2}%, tol={:.1}%)",
+                    relative_difference(forge_val, r_val) * 100.0,
+                    tolerance.ci_bounds * 100.0
+                ));
+            }
+        }
+    }
+
+    TestResult::Pass {
+        name: test_name.to_string(),
+        details: format!(
+            "estimate={:.4} std={:.4} ({} CIs within tolerance)",
+            forge.original_estimate,
+            forge.std,
+            confidence_levels.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn r_results_are_typed() {
+        let r = serde_json::json!({
+            "mean": 5.0, "std": 0.5, "original_estimate": 5.0, "bias": 0.01,
+            "confidence_intervals": {"0.95": {"lower": 4.0, "upper": 6.0}}
+        });
+        let stats = BootstrapStats::try_from(r_results::<BootstrapResults>(&r).unwrap()).unwrap();
+        assert_eq!(stats.interval(0.95), Some((4.0, 6.0)));
+
+        // R writes an empty list as [] when no interval could be computed
+        let r = serde_json::json!({
+            "mean": 5.0, "std": 0.5, "original_estimate": 5.0, "bias": 0.01,
+            "confidence_intervals": []
+        });
+        assert!(r_results::<BootstrapResults>(&r)
+            .unwrap()
+            .confidence_intervals
+            .is_empty());
+
+        let err = r_results::<BootstrapResults>(&serde_json::json!({"mean": 5.0})).unwrap_err();
+        assert!(err.contains("missing field"), "{err}");
+    }
+
+    #[test]
+    fn recorded_intervals_are_checked_by_level() {
+        let yaml = r#"
+data: [0.05, -0.02, 0.08]
+confidence_levels: [0.90, 0.95]
+r_expected:
+  original_estimate: 0.031
+  confidence_intervals:
+    "0.95":
+      lower: 0.00
+      upper: 0.060
+tolerance:
+  ci_bounds: 0.05
+"#;
+        let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(yaml).unwrap();
+        let expected = spec.r_expected.as_ref().unwrap();
+        let forge = |upper: f64| {
+            serde_json::json!({"bootstrap_results": {
+                "original_estimate": 0.031, "bootstrap_mean": 0.0305,
+                "bootstrap_std_error": 0.015, "bias": -0.0005,
+                "confidence_intervals": [
+                    {"level": 0.90, "lower": 0.005, "upper": 0.055},
+                    {"level": 0.95, "lower": 0.0, "upper": upper}
+                ]
+            }})
+        };
+
+        // Only the recorded 0.95 interval is compared
+        let result = BootstrapSuite.compare_recorded(&spec, Output::Forge(&forge(0.061)), expected);
+        assert!(result.is_pass(), "{result:?}");
+        let result = BootstrapSuite.compare_recorded(&spec, Output::Forge(&forge(0.07)), expected);
+        let TestResult::Fail { reason, .. } = result else {
+            panic!("expected a failure, got {result:?}");
+        };
+        assert!(reason.starts_with("0.95 CI upper mismatch"), "{reason}");
+    }
+}
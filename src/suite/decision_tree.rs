@@ -0,0 +1,425 @@
+//! Decision tree EMVs validated against `decision_tree_validator.R`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{one_or_many, parse_both, parse_output, to_payload, ForgeInvocation, Output, Suite};
+use crate::stats::{compare, relative_difference, within_tolerance};
+use crate::types::{AnalyticsTestSpec, DecisionNode, DecisionNodeType, RExpected, TestResult};
+
+/// Runs `forge decision-tree` and compares EMVs node by node (data.tree
+/// backward induction) and the optimal decision path.
+pub struct DecisionTreeSuite;
+
+impl Suite for DecisionTreeSuite {
+    fn name(&self) -> &'static str {
+        "decision_tree"
+    }
+
+    fn validator(&self) -> &'static str {
+        "decision_tree_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        if spec.tree.is_none() {
+            return Err("No tree specified (not a decision tree test)".to_string());
+        }
+        Ok(())
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let tree = spec.tree.as_ref().ok_or("No tree specified")?;
+        let fixture = serde_yaml_ng::to_string(&DecisionTreeFixture {
+            forge_version: "5.0.0",
+            decision_tree: tree,
+        })
+        .map_err(|e| format!("Failed to serialize tree: {e}"))?;
+        Ok(ForgeInvocation::new("decision-tree", fixture))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        let tree = spec.tree.as_ref().ok_or("No tree specified")?;
+        to_payload(&DecisionTreeParams { tree })
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) = match parse_both(spec, forge, r, parse_emv_results, parse_emv_results) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+
+        compare_emv_results(&spec.name, &forge, &r, tolerance(spec))
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(spec, output, parse_emv_results, parse_emv_results) {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        let recorded = recorded_emv_results(&actual, expected);
+
+        compare_emv_results(&spec.name, &actual, &recorded, tolerance(spec))
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let results = parse_emv_results(r)?;
+
+        // Names can repeat across branches; only unambiguous ones are recorded
+        let mut counts: HashMap<&str, (usize, f64)> = HashMap::new();
+        let mut stack = vec![&results.tree];
+        while let Some(node) = stack.pop() {
+            let entry = counts.entry(&node.name).or_insert((0, node.emv));
+            entry.0 += 1;
+            stack.extend(&node.children);
+        }
+        let node_emvs = counts
+            .into_iter()
+            .filter(|(_, (count, _))| *count == 1)
+            .map(|(name, (_, emv))| (name.to_string(), emv))
+            .collect();
+
+        Ok(RExpected {
+            root_emv: Some(results.root_emv),
+            optimal_decision: results.decision_path.first().cloned(),
+            decision_path: results.decision_path,
+            node_emvs,
+            ..Default::default()
+        })
+    }
+}
+
+fn tolerance(spec: &AnalyticsTestSpec) -> f64 {
+    spec.tolerance.as_ref().and_then(|t| t.emv).unwrap_or(0.001)
+}
+
+/// Forge YAML model for a decision tree.
+#[derive(Serialize)]
+struct DecisionTreeFixture<'a> {
+    #[serde(rename = "_forge_version")]
+    forge_version: &'a str,
+    decision_tree: &'a DecisionNode,
+}
+
+/// JSON payload for `decision_tree_validator.R`.
+#[derive(Serialize)]
+struct DecisionTreeParams<'a> {
+    tree: &'a DecisionNode,
+}
+
+/// Decision tree node with its computed EMV.
+#[derive(Debug, Clone, Deserialize)]
+struct EmvNode {
+    name: String,
+    #[serde(rename = "type", default)]
+    node_type: Option<DecisionNodeType>,
+    emv: f64,
+    #[serde(default)]
+    children: Vec<Self>,
+}
+
+/// Parsed decision tree results from either forge or R.
+#[derive(Debug, Clone, Deserialize)]
+struct EmvResults {
+    root_emv: f64,
+    #[serde(default, deserialize_with = "one_or_many")]
+    decision_path: Vec<String>,
+    tree: EmvNode,
+}
+
+/// Parses decision tree results (forge output is nested under `decision_tree_results`).
+fn parse_emv_results(json: &Value) -> Result<EmvResults, String> {
+    let results = json.get("decision_tree_results").unwrap_or(json);
+    EmvResults::deserialize(results).map_err(|e| format!("Malformed decision tree results: {e}"))
+}
+
+/// Overlays the recorded root EMV, node EMVs (by name) and decisions on
+/// `actual`, so only the recorded values can differ.
+///
+/// A recorded `optimal_decision` without a full `decision_path` replaces
+/// only the first decision.
+fn recorded_emv_results(actual: &EmvResults, expected: &RExpected) -> EmvResults {
+    fn overlay(node: &mut EmvNode, expected: &RExpected) {
+        if let Some(&emv) = expected.node_emvs.get(&node.name) {
+            node.emv = emv;
+        }
+        for child in &mut node.children {
+            overlay(child, expected);
+        }
+    }
+
+    let mut recorded = actual.clone();
+    overlay(&mut recorded.tree, expected);
+    if let Some(root_emv) = expected.root_emv {
+        recorded.root_emv = root_emv;
+        recorded.tree.emv = root_emv;
+    }
+    if !expected.decision_path.is_empty() {
+        recorded.decision_path.clone_from(&expected.decision_path);
+    } else if let Some(decision) = &expected.optimal_decision {
+        match recorded.decision_path.first_mut() {
+            Some(first) => first.clone_from(decision),
+            None => recorded.decision_path.push(decision.clone()),
+        }
+    }
+    recorded
+}
+
+/// Compares forge and R decision tree results, returning Pass or Fail.
+fn compare_emv_results(
+    test_name: &str,
+    forge: &EmvResults,
+    r: &EmvResults,
+    tolerance: f64,
+) -> TestResult {
+    let fail = |reason: String| TestResult::Fail {
+        name: test_name.to_string(),
+        reason,
+    };
+
+    if !compare("root EMV", forge.root_emv, r.root_emv, tolerance) {
+        return fail(format!(
+            "Root EMV mismatch: forge={:.2}, R={:.2} (diff={:.4}%, tol={:.2}%)",
+            forge.root_emv,
+            r.root_emv,
+            relative_difference(forge.root_emv, r.root_emv) * 100.0,
+            tolerance * 100.0
+        ));
+    }
+
+    if let Err(reason) = compare_emv_nodes(&forge.tree, &r.tree, tolerance, &mut Vec::new()) {
+        return fail(reason);
+    }
+
+    if let Err(reason) = compare_decision_paths(forge, r, tolerance) {
+        return fail(reason);
+    }
+
+    TestResult::Pass {
+        name: test_name.to_string(),
+        details: format!(
+            "root_emv={:.2} path=[{}]",
+            forge.root_emv,
+            forge.decision_path.join(" > ")
+        ),
+    }
+}
+
+/// Walks both trees in pre-order and reports the first node whose EMV differs.
+///
+/// Node names repeat across branches, so nodes are identified by their full
+/// path from the root.
+fn compare_emv_nodes<'a>(
+    forge: &'a EmvNode,
+    r: &'a EmvNode,
+    tolerance: f64,
+    path: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    path.push(&r.name);
+
+    if forge.name != r.name {
+        return Err(format!(
+            "Tree structure mismatch at {}: forge has '{}'",
+            path.join(" > "),
+            forge.name
+        ));
+    }
+
+    let at = path.join(" > ");
+    if !compare(format!("EMV at {at}"), forge.emv, r.emv, tolerance) {
+        return Err(format!(
+            "EMV mismatch at {at}: forge={:.2}, R={:.2} (diff={:.4}%, tol={:.2}%)",
+            forge.emv,
+            r.emv,
+            relative_difference(forge.emv, r.emv) * 100.0,
+            tolerance * 100.0
+        ));
+    }
+
+    if forge.children.len() != r.children.len() {
+        return Err(format!(
+            "Tree structure mismatch at {}: forge has {} children, R has {}",
+            path.join(" > "),
+            forge.children.len(),
+            r.children.len()
+        ));
+    }
+
+    for (forge_child, r_child) in forge.children.iter().zip(&r.children) {
+        compare_emv_nodes(forge_child, r_child, tolerance, path)?;
+    }
+
+    path.pop();
+    Ok(())
+}
+
+/// Checks that forge chose the same optimal decisions as R.
+///
+/// A different choice is only accepted when R's tree shows it ties with the
+/// best alternative (e.g. `edge_equal_alternatives`); the paths then diverge
+/// and the remainder is not compared.
+fn compare_decision_paths(
+    forge: &EmvResults,
+    r: &EmvResults,
+    tolerance: f64,
+) -> Result<(), String> {
+    let mismatch = || {
+        format!(
+            "Decision path mismatch: forge=[{}], R=[{}]",
+            forge.decision_path.join(" > "),
+            r.decision_path.join(" > ")
+        )
+    };
+
+    // Follow R's path through the tree, one decision node at a time
+    let mut node = &r.tree;
+    let mut step = 0;
+    while !node.children.is_empty() {
+        let next = if node.node_type == Some(DecisionNodeType::Chance) {
+            // R follows the highest-EMV outcome through chance nodes
+            node.children.iter().max_by(|a, b| a.emv.total_cmp(&b.emv))
+        } else {
+            let Some(r_choice) = r.decision_path.get(step) else {
+                break;
+            };
+            let forge_choice = forge.decision_path.get(step).ok_or_else(mismatch)?;
+            step += 1;
+
+            if forge_choice != r_choice {
+                let best = node.children.iter().map(|c| c.emv).fold(f64::MIN, f64::max);
+                let tied = node
+                    .children
+                    .iter()
+                    .any(|c| &c.name == forge_choice && within_tolerance(c.emv, best, tolerance));
+                return if tied { Ok(()) } else { Err(mismatch()) };
+            }
+            node.children.iter().find(|c| &c.name == r_choice)
+        };
+        let Some(next) = next else {
+            break;
+        };
+        node = next;
+    }
+
+    if forge.decision_path.len() == r.decision_path.len() {
+        Ok(())
+    } else {
+        Err(mismatch())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_goes_to_forge_as_written() {
+        let yaml = r#"
+tree:
+  name: "Investment Decision"
+  type: decision
+  children:
+    - name: "Invest"
+      type: chance
+      cost: 100000
+      children:
+        - name: "Success"
+          type: terminal
+          probability: 0.7
+          payoff: 300000
+    - name: "Don't Invest"
+      type: terminal
+      payoff: 0
+"#;
+        let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(yaml).unwrap();
+        let tree = spec.tree.as_ref().unwrap();
+        assert_eq!(tree.children[0].node_type, DecisionNodeType::Chance);
+
+        let fixture = DecisionTreeSuite.forge_invocation(&spec).unwrap().fixture;
+        let model: serde_yaml_ng::Value = serde_yaml_ng::from_str(&fixture).unwrap();
+        let invest = &model["decision_tree"]["children"][0];
+        assert_eq!(invest["cost"].as_f64(), Some(100_000.0));
+        assert_eq!(invest["children"][0]["probability"].as_f64(), Some(0.7));
+        // Leaves carry no empty `children` or unset fields
+        assert!(!fixture.contains("children: []"), "{fixture}");
+        assert!(!fixture.contains("null"), "{fixture}");
+    }
+
+    #[test]
+    fn recorded_values_override_only_what_was_recorded() {
+        let forge = parse_emv_results(&serde_json::json!({
+            "decision_tree_results": {
+                "root_emv": 125_000.0,
+                "decision_path": ["Invest", "Expand"],
+                "tree": {"name": "Root", "type": "decision", "emv": 125_000.0, "children": [
+                    {"name": "Invest", "emv": 125_000.0},
+                    {"name": "Wait", "emv": 0.0}
+                ]}
+            }
+        }))
+        .unwrap();
+        let expected: RExpected =
+            serde_yaml_ng::from_str("{root_emv: 125000, optimal_decision: Invest}").unwrap();
+
+        let recorded = recorded_emv_results(&forge, &expected);
+        assert_eq!(recorded.decision_path, ["Invest", "Expand"]);
+        assert!(compare_emv_results("t", &forge, &recorded, 0.001).is_pass());
+
+        let expected: RExpected =
+            serde_yaml_ng::from_str("{node_emvs: {Wait: 10}, optimal_decision: Wait}").unwrap();
+        let result =
+            compare_emv_results("t", &forge, &recorded_emv_results(&forge, &expected), 0.001);
+        assert!(result.is_fail(), "{result:?}");
+    }
+
+    fn results(root_emv: f64, path: &[&str], invest: f64, wait: f64) -> EmvResults {
+        parse_emv_results(&serde_json::json!({
+            "root_emv": root_emv,
+            "decision_path": path,
+            "tree": {"name": "Root", "type": "decision", "emv": root_emv, "children": [
+                {"name": "Invest", "type": "chance", "emv": invest, "children": [
+                    {"name": "Success", "type": "terminal", "emv": 300_000.0},
+                    {"name": "Failure", "type": "terminal", "emv": -50_000.0}
+                ]},
+                {"name": "Wait", "type": "terminal", "emv": wait}
+            ]}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn first_diverging_node_is_reported_by_path() {
+        let r = results(125_000.0, &["Invest"], 125_000.0, 0.0);
+        let forge = results(125_000.0, &["Invest"], 120_000.0, 10.0);
+
+        let TestResult::Fail { reason, .. } = compare_emv_results("t", &forge, &r, 0.001) else {
+            panic!("expected a failure");
+        };
+        assert!(
+            reason.starts_with("EMV mismatch at Root > Invest: forge=120000.00"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn different_decision_path_fails_with_both_paths() {
+        let r = results(125_000.0, &["Invest"], 125_000.0, 0.0);
+        let forge = results(125_000.0, &["Wait"], 125_000.0, 0.0);
+
+        let TestResult::Fail { reason, .. } = compare_emv_results("t", &forge, &r, 0.001) else {
+            panic!("expected a failure");
+        };
+        assert_eq!(reason, "Decision path mismatch: forge=[Wait], R=[Invest]");
+
+        // Not when R shows the two choices tie
+        let tied = results(125_000.0, &["Invest"], 125_000.0, 125_000.0);
+        let forge = results(125_000.0, &["Wait"], 125_000.0, 125_000.0);
+        assert!(compare_emv_results("t", &forge, &tied, 0.001).is_pass());
+    }
+}
@@ -0,0 +1,311 @@
+//! Formula assertion suites (`assumptions:` files).
+//!
+//! Unlike the other suites these run per file, not per test: every formula in
+//! the file goes into one forge model and one R evaluation.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ForgeInvocation, RunContext};
+use crate::select::FORMULA_SUITE;
+use crate::stats::{compare, record_comparisons, within_tolerance, Comparison};
+use crate::types::{FormulaSuite, TestReport, TestResult};
+
+/// Relative tolerance for formula assertions (exact arithmetic).
+const FORMULA_TOLERANCE: f64 = 1e-9;
+
+/// Runs a formula assertion suite by:
+/// 1. Writing every formula into one forge model
+/// 2. Running forge calculate once
+/// 3. Evaluating the same formulas with the R validator, unless the oracle
+///    is `recorded`
+/// 4. Checking each forge value against `expected`, with R as a second oracle
+///
+/// The suite's run time is split evenly across its assertions.
+#[must_use]
+pub fn run_formula_suite(suite: &FormulaSuite, ctx: &RunContext) -> Vec<TestReport> {
+    let start = Instant::now();
+    let share = || {
+        let count = u32::try_from(suite.assertions.len().max(1)).unwrap_or(u32::MAX);
+        start.elapsed() / count
+    };
+    let report = |result, duration: Duration, comparisons: Vec<Comparison>| TestReport {
+        result,
+        suite: format!("{FORMULA_SUITE}/{}", suite.name),
+        duration,
+        comparisons,
+        gof: Vec::new(),
+        samples: None,
+    };
+    let errors = |error: &str| {
+        let duration = share();
+        suite
+            .assertions
+            .iter()
+            .map(|a| {
+                let result = TestResult::Error {
+                    name: a.name.clone(),
+                    error: error.to_string(),
+                };
+                report(result, duration, Vec::new())
+            })
+            .collect()
+    };
+
+    let invocation = match formula_invocation(suite) {
+        Ok(invocation) => invocation,
+        Err(e) => return errors(&e),
+    };
+
+    // Run forge calculate once for the whole suite
+    let forge_json = match invocation.run(&ctx.runner) {
+        Ok(json) => json,
+        Err(e) => return errors(&format!("Forge failed: {e:#}")),
+    };
+
+    // `expected` is itself the recorded value, so R only runs as a live oracle
+    let r_values = if ctx.oracle.needs_r() {
+        match evaluate_with_r(suite, ctx) {
+            Ok(values) => Some(values),
+            Err(e) => return errors(&e),
+        }
+    } else {
+        None
+    };
+
+    let duration = share();
+    suite
+        .assertions
+        .iter()
+        .map(|a| {
+            let (result, recorded) = record_comparisons(|| {
+                compare_formula_value(
+                    &a.name,
+                    a.expected,
+                    formula_value(&forge_json, &a.name),
+                    r_values.as_ref(),
+                )
+            });
+            report(result, duration, recorded.comparisons)
+        })
+        .collect()
+}
+
+/// Builds the forge model holding every formula of the suite.
+///
+/// # Errors
+///
+/// Returns an error if the model cannot be serialized.
+pub fn formula_invocation(suite: &FormulaSuite) -> Result<ForgeInvocation, String> {
+    let model = FormulaModel {
+        forge_version: "5.0.0",
+        assumptions: suite
+            .assertions
+            .iter()
+            .map(|a| {
+                (
+                    a.name.as_str(),
+                    FormulaCell {
+                        value: None,
+                        formula: &a.formula,
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    serde_yaml_ng::to_string(&model)
+        .map(|yaml| ForgeInvocation::new("calculate", yaml))
+        .map_err(|e| format!("Failed to serialize {} model: {e}", suite.name))
+}
+
+/// Returns the R validator for the suite and its payload of formulas.
+#[must_use]
+pub fn formula_r_params(suite: &FormulaSuite) -> (&str, Value) {
+    let formulas: HashMap<&str, &str> = suite
+        .assertions
+        .iter()
+        .map(|a| (a.name.as_str(), a.formula.as_str()))
+        .collect();
+    let validator = suite
+        .r_validator
+        .as_deref()
+        .unwrap_or("financial_validator.R");
+    (validator, serde_json::json!({ "formulas": formulas }))
+}
+
+/// Evaluates every formula of the suite with the R validator.
+fn evaluate_with_r(suite: &FormulaSuite, ctx: &RunContext) -> Result<FormulaValues, String> {
+    let (validator, params) = formula_r_params(suite);
+    let r_result = ctx
+        .validate_with_r(validator, &params)
+        .map_err(|e| format!("R validator failed: {e}"))?;
+
+    if !r_result.success {
+        return Err(format!(
+            "R returned error: {}",
+            r_result.error.unwrap_or_else(|| "Unknown".to_string())
+        ));
+    }
+
+    r_result
+        .parse_results()
+        .map_err(|e| format!("Failed to parse R results: {e:#}"))
+}
+
+/// Forge model holding one formula cell per assertion.
+#[derive(Serialize)]
+struct FormulaModel<'a> {
+    #[serde(rename = "_forge_version")]
+    forge_version: &'a str,
+    #[serde(serialize_with = "serialize_in_order")]
+    assumptions: Vec<(&'a str, FormulaCell<'a>)>,
+}
+
+/// Serializes key-value pairs as a map, keeping their order.
+fn serialize_in_order<S, K, V>(entries: &[(K, V)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    K: Serialize,
+    V: Serialize,
+{
+    serializer.collect_map(entries.iter().map(|(k, v)| (k, v)))
+}
+
+/// Formula cell in a forge model.
+#[derive(Serialize)]
+struct FormulaCell<'a> {
+    value: Option<f64>,
+    formula: &'a str,
+}
+
+/// R evaluations keyed by assertion name, with per-formula errors.
+#[derive(Debug, Deserialize)]
+struct FormulaValues {
+    #[serde(default)]
+    values: HashMap<String, Option<f64>>,
+    #[serde(default)]
+    errors: HashMap<String, String>,
+}
+
+/// Extracts a calculated scalar from forge output (`assumptions.<name>`,
+/// either a bare number or a cell with `value`).
+fn formula_value(json: &Value, name: &str) -> Option<f64> {
+    let cell = json
+        .get("assumptions")
+        .and_then(|a| a.get(name))
+        .or_else(|| json.get(name))?;
+    cell.as_f64()
+        .or_else(|| cell.get("value").and_then(Value::as_f64))
+}
+
+/// Checks one formula assertion against `expected` and, if it ran, R.
+fn compare_formula_value(
+    name: &str,
+    expected: f64,
+    forge: Option<f64>,
+    r: Option<&FormulaValues>,
+) -> TestResult {
+    let fail = |reason: String| TestResult::Fail {
+        name: name.to_string(),
+        reason,
+    };
+
+    let Some(forge) = forge else {
+        return fail("Value missing from forge output".to_string());
+    };
+    if !compare("expected", forge, expected, FORMULA_TOLERANCE) {
+        return fail(format!("forge={forge}, expected={expected}"));
+    }
+
+    let Some(r) = r else {
+        return TestResult::Pass {
+            name: name.to_string(),
+            details: format!("forge={forge} (recorded)"),
+        };
+    };
+
+    // R is a second oracle: it must agree with `expected` when it can evaluate the formula
+    match r.values.get(name).copied().flatten() {
+        Some(r_val) if within_tolerance(r_val, expected, FORMULA_TOLERANCE) => TestResult::Pass {
+            name: name.to_string(),
+            details: format!("forge={forge}, R={r_val}"),
+        },
+        Some(r_val) => fail(format!(
+            "R disagrees with expected: forge={forge}, R={r_val}, expected={expected}"
+        )),
+        None => TestResult::Pass {
+            name: name.to_string(),
+            details: format!(
+                "forge={forge} (R: {})",
+                r.errors.get(name).map_or("no value", String::as_str)
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::load_formula_suite;
+
+    fn r_values(name: &str, value: f64) -> FormulaValues {
+        FormulaValues {
+            values: HashMap::from([(name.to_string(), Some(value))]),
+            errors: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn assertions_load_in_file_order() {
+        let yaml = r#"
+_forge_version: "1.0.0"
+assumptions:
+  test_variance_basic:
+    value: null
+    formula: "=VARIANCE(100000, 120000)"
+    expected: -20000
+  test_breakeven_units_basic:
+    value: null
+    formula: "=BREAKEVEN_UNITS(50000, 100, 60)"
+    expected: 1250
+"#;
+        let suite = load_formula_suite("variance", yaml).unwrap().unwrap();
+        let names: Vec<&str> = suite.assertions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["test_variance_basic", "test_breakeven_units_basic"]);
+        assert!(load_formula_suite("empty", "tests: {}").unwrap().is_none());
+    }
+
+    #[test]
+    fn forge_must_match_expected_within_formula_tolerance() {
+        let inside = 1250.0 * FORMULA_TOLERANCE.mul_add(0.5, 1.0);
+        let outside = 1250.0 * FORMULA_TOLERANCE.mul_add(2.0, 1.0);
+        assert!(compare_formula_value("t", 1250.0, Some(inside), None).is_pass());
+        assert!(compare_formula_value("t", 1250.0, Some(outside), None).is_fail());
+        assert!(compare_formula_value("t", 1250.0, None, None).is_fail());
+    }
+
+    #[test]
+    fn r_disagreeing_with_expected_fails_even_when_forge_agrees() {
+        let agree = r_values("t", 1250.0);
+        assert!(compare_formula_value("t", 1250.0, Some(1250.0), Some(&agree)).is_pass());
+
+        let disagree = r_values("t", 1300.0);
+        let TestResult::Fail { reason, .. } =
+            compare_formula_value("t", 1250.0, Some(1250.0), Some(&disagree))
+        else {
+            panic!("expected a failure");
+        };
+        assert!(reason.starts_with("R disagrees with expected"), "{reason}");
+
+        // A formula R cannot evaluate leaves forge's match to decide
+        let unevaluated = FormulaValues {
+            values: HashMap::new(),
+            errors: HashMap::from([("t".to_string(), "unknown function".to_string())]),
+        };
+        assert!(compare_formula_value("t", 1250.0, Some(1250.0), Some(&unevaluated)).is_pass());
+    }
+}
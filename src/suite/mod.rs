@@ -0,0 +1,612 @@
+//! Analytics suites.
+//!
+//! Each analytics area (Monte Carlo, bootstrap, Bayesian networks, ...) is a
+//! [`Suite`] plug-in: it checks a test spec, builds the forge invocation and
+//! the R payload for it, and compares the two results. [`SuiteRegistry`]
+//! selects the suite for a spec from its `_suite:` key or `_r_validator`, so
+//! adding an area never touches the runner loop.
+//!
+//! The [`Oracle`] decides what forge is checked against: a live R run, the
+//! `r_expected` values recorded in the spec, or both.
+
+mod bayesian;
+mod bootstrap;
+mod decision_tree;
+pub mod formula;
+mod monte_carlo;
+mod pool;
+mod real_options;
+mod sensitivity;
+mod tornado;
+
+use std::io::Write as _;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use bayesian::BayesianSuite;
+pub use bootstrap::BootstrapSuite;
+pub use decision_tree::DecisionTreeSuite;
+pub use monte_carlo::MonteCarloSuite;
+pub use pool::{adjust_reports, run_parallel};
+pub use real_options::RealOptionsSuite;
+pub use sensitivity::SensitivitySuite;
+pub use tornado::TornadoSuite;
+
+use crate::cli_runner::{run_forge_json, ForgeCommand, RunnerConfig};
+use crate::process::shell_quote;
+use crate::r_cache::RCache;
+use crate::r_validator::{validate_with_r, RConfig, RResult};
+use crate::r_worker::RWorker;
+use crate::stats::multiple::PAdjust;
+use crate::stats::within_tolerance;
+use crate::types::{AnalyticsTestSpec, RExpected, TestResult};
+
+/// Reference that forge results are checked against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Oracle {
+    /// The `r_expected` values recorded in each spec; R is not needed.
+    Recorded,
+    /// A live run of the R validator.
+    #[default]
+    Live,
+    /// A live R run, which must also still reproduce the recorded values.
+    Both,
+}
+
+impl Oracle {
+    /// Whether this oracle runs the R validators.
+    #[must_use]
+    pub const fn needs_r(self) -> bool {
+        !matches!(self, Self::Recorded)
+    }
+}
+
+/// Forge and R configuration shared by every test in a run.
+#[derive(Debug)]
+pub struct RunContext {
+    /// Forge binary and timeout.
+    pub runner: RunnerConfig,
+    /// R validators directory and timeout.
+    pub r_config: RConfig,
+    /// Persistent R worker; without one each validation launches `Rscript`.
+    pub r_worker: Option<RWorker>,
+    /// Cache of R results; without one every validation runs R.
+    pub r_cache: Option<RCache>,
+    /// What forge results are checked against.
+    pub oracle: Oracle,
+    /// Correction applied to the goodness-of-fit p-values of the whole run
+    /// (see [`adjust_reports`]).
+    pub p_adjust: PAdjust,
+}
+
+impl RunContext {
+    /// Runs an R validator on the worker, or in a fresh `Rscript` if there is
+    /// none, unless the result is already cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the validator cannot be run or its output is not valid JSON.
+    pub fn validate_with_r<P: Serialize + ?Sized>(
+        &self,
+        validator: &str,
+        params: &P,
+    ) -> anyhow::Result<RResult> {
+        let run = || {
+            self.r_worker.as_ref().map_or_else(
+                || validate_with_r(validator, params, &self.r_config),
+                |worker| worker.validate(validator, params),
+            )
+        };
+        self.r_cache.as_ref().map_or_else(run, |cache| {
+            cache.validate(validator, params, &self.r_config, run)
+        })
+    }
+}
+
+/// A forge command together with the YAML model it runs on.
+#[derive(Debug, Clone)]
+pub struct ForgeInvocation {
+    /// Forge subcommand (e.g. `simulate`).
+    pub command: String,
+    /// YAML model, written to a temporary file passed after the command.
+    pub fixture: String,
+    /// Arguments after the fixture path.
+    pub args: Vec<String>,
+}
+
+impl ForgeInvocation {
+    /// Creates an invocation without extra arguments.
+    #[must_use]
+    pub fn new(command: &str, fixture: String) -> Self {
+        Self {
+            command: command.to_string(),
+            fixture,
+            args: Vec::new(),
+        }
+    }
+
+    /// Appends an argument.
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Returns the command line [`run`](Self::run) executes, with
+    /// placeholders for the temporary model and output files.
+    #[must_use]
+    pub fn command_line(&self, forge_bin: &Path) -> String {
+        let mut words = vec![
+            shell_quote(&forge_bin.display().to_string()),
+            shell_quote(&self.command),
+            "<model.yaml>".to_string(),
+        ];
+        words.extend(self.args.iter().map(|arg| shell_quote(arg)));
+        words.extend(["-o".to_string(), "<output.json>".to_string()]);
+        words.join(" ")
+    }
+
+    /// Writes the fixture to a temporary file and runs forge on it.
+    ///
+    /// Every call gets its own fixture and output files, so concurrent jobs
+    /// never share a path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fixture cannot be written or forge fails.
+    pub fn run(&self, runner: &RunnerConfig) -> anyhow::Result<Value> {
+        let mut fixture = tempfile::Builder::new().suffix(".yaml").tempfile()?;
+        fixture.write_all(self.fixture.as_bytes())?;
+
+        run_forge_json(
+            &ForgeCommand {
+                cmd: self.command.clone(),
+                args: self.args.clone(),
+                fixture: fixture.path().to_path_buf(),
+            },
+            runner,
+        )
+    }
+}
+
+/// Results checked against a spec's recorded `r_expected` values.
+#[derive(Debug, Clone, Copy)]
+pub enum Output<'a> {
+    /// Forge output (`--oracle recorded`).
+    Forge(&'a Value),
+    /// Live R validator `results` (`--oracle both`).
+    R(&'a Value),
+}
+
+/// An analytics area validated against one R validator.
+pub trait Suite: Send + Sync {
+    /// Name matched against the `_suite:` key.
+    fn name(&self) -> &'static str;
+
+    /// R validator script used when the spec does not name one.
+    fn validator(&self) -> &'static str;
+
+    /// Checks that the spec carries what this suite needs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the test is skipped.
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String>;
+
+    /// Builds the forge model and command line for the spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model cannot be built.
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String>;
+
+    /// Builds the JSON payload for the R validator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload cannot be built.
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String>;
+
+    /// Compares forge output with the R validator's `results`.
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult;
+
+    /// Compares forge output or live R results with the spec's recorded
+    /// `r_expected` values.
+    ///
+    /// Recorded blocks are often partial; values that were not recorded are
+    /// not compared.
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let _ = (output, expected);
+        TestResult::Skip {
+            name: spec.name.clone(),
+            reason: format!("Suite '{}' has no recorded comparison", self.name()),
+        }
+    }
+
+    /// Converts the R validator's `results` into the `r_expected` values
+    /// written back by `record`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the results cannot be parsed or the suite does not
+    /// record values.
+    fn record(&self, spec: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let _ = (spec, r);
+        Err(format!("Suite '{}' cannot record r_expected", self.name()))
+    }
+
+    /// Whether tests of this suite must not run concurrently with each other
+    /// (e.g. a validator that writes shared state).
+    fn serial(&self) -> bool {
+        false
+    }
+
+    /// Runs the test. Suites that need more than one forge run override this.
+    fn run(&self, spec: &AnalyticsTestSpec, ctx: &RunContext) -> TestResult {
+        run_pipeline(self, spec, ctx)
+    }
+}
+
+/// Runs the standard pipeline: build the fixture, run forge, run the R
+/// validator, compare.
+pub fn run_pipeline<S: Suite + ?Sized>(
+    suite: &S,
+    spec: &AnalyticsTestSpec,
+    ctx: &RunContext,
+) -> TestResult {
+    let error = |error: String| TestResult::Error {
+        name: spec.name.clone(),
+        error,
+    };
+
+    if let Err(reason) = suite.parse_spec(spec) {
+        return TestResult::Skip {
+            name: spec.name.clone(),
+            reason,
+        };
+    }
+
+    let forge_json = match suite
+        .forge_invocation(spec)
+        .and_then(|invocation| invocation.run(&ctx.runner).map_err(|e| format!("{e:#}")))
+    {
+        Ok(json) => json,
+        Err(e) => return error(format!("Forge failed: {e}")),
+    };
+
+    if ctx.oracle == Oracle::Recorded {
+        let Some(expected) = &spec.r_expected else {
+            return TestResult::Skip {
+                name: spec.name.clone(),
+                reason: "No r_expected recorded".to_string(),
+            };
+        };
+        return suite.compare_recorded(spec, Output::Forge(&forge_json), expected);
+    }
+
+    let r_json = match run_r(suite, spec, ctx) {
+        Ok(r_json) => r_json,
+        Err(e) => return error(e),
+    };
+
+    let result = suite.compare(spec, &forge_json, &r_json);
+    match (&spec.r_expected, ctx.oracle) {
+        (Some(expected), Oracle::Both) if result.is_pass() => {
+            check_r_reproduces(suite, spec, &r_json, expected).unwrap_or(result)
+        }
+        _ => result,
+    }
+}
+
+/// Runs the spec's R validator and returns its `results`.
+///
+/// # Errors
+///
+/// Returns an error if the payload cannot be built, the validator fails or
+/// it returns no results.
+pub fn run_r<S: Suite + ?Sized>(
+    suite: &S,
+    spec: &AnalyticsTestSpec,
+    ctx: &RunContext,
+) -> Result<Value, String> {
+    let payload = suite
+        .r_params(spec)
+        .map_err(|e| format!("Failed to build R params: {e}"))?;
+
+    let validator = spec
+        .r_validator
+        .as_deref()
+        .unwrap_or_else(|| suite.validator());
+    let r_result = ctx
+        .validate_with_r(validator, &payload)
+        .map_err(|e| format!("R validator failed: {e}"))?;
+
+    if !r_result.success {
+        return Err(format!(
+            "R returned error: {}",
+            r_result.error.unwrap_or_else(|| "Unknown".to_string())
+        ));
+    }
+
+    r_result
+        .results
+        .ok_or_else(|| "R returned no results".to_string())
+}
+
+/// Checks live R results against the recorded values, returning the failure
+/// if R no longer reproduces them (e.g. after an R package update).
+fn check_r_reproduces<S: Suite + ?Sized>(
+    suite: &S,
+    spec: &AnalyticsTestSpec,
+    r: &Value,
+    expected: &RExpected,
+) -> Option<TestResult> {
+    // The suite labels the sides forge and R; here they are live R and the record
+    let drift = |reason: String| {
+        format!("R no longer reproduces r_expected (forge = live R, R = recorded): {reason}")
+    };
+    match suite.compare_recorded(spec, Output::R(r), expected) {
+        TestResult::Fail { name, reason } => Some(TestResult::Fail {
+            name,
+            reason: drift(reason),
+        }),
+        TestResult::Error { name, error } => Some(TestResult::Error {
+            name,
+            error: drift(error),
+        }),
+        TestResult::Pass { .. } | TestResult::Skip { .. } | TestResult::Inconclusive { .. } => None,
+    }
+}
+
+/// Selects the suite for each test spec.
+pub struct SuiteRegistry {
+    suites: Vec<Box<dyn Suite>>,
+}
+
+impl Default for SuiteRegistry {
+    /// Registry with every built-in suite.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(MonteCarloSuite));
+        registry.register(Box::new(BootstrapSuite));
+        registry.register(Box::new(BayesianSuite));
+        registry.register(Box::new(DecisionTreeSuite));
+        registry.register(Box::new(RealOptionsSuite));
+        registry.register(Box::new(TornadoSuite));
+        registry.register(Box::new(SensitivitySuite));
+        registry
+    }
+}
+
+impl SuiteRegistry {
+    /// Creates a registry with no suites.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self { suites: Vec::new() }
+    }
+
+    /// Adds a suite. Later registrations win over earlier ones.
+    pub fn register(&mut self, suite: Box<dyn Suite>) {
+        self.suites.insert(0, suite);
+    }
+
+    /// Looks up a suite by name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&dyn Suite> {
+        self.suites
+            .iter()
+            .find(|s| s.name() == name)
+            .map(AsRef::as_ref)
+    }
+
+    /// Names of the registered suites.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.suites.iter().map(|s| s.name())
+    }
+
+    /// Selects the suite for a spec: its `_suite:` key if set, otherwise the
+    /// suite whose validator matches `_r_validator`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no registered suite matches.
+    pub fn select(&self, spec: &AnalyticsTestSpec) -> Result<&dyn Suite, String> {
+        if let Some(name) = spec.suite.as_deref() {
+            return self
+                .get(name)
+                .ok_or_else(|| format!("Unknown suite '{name}'"));
+        }
+
+        let validator = spec
+            .r_validator
+            .as_deref()
+            .ok_or("No _suite or _r_validator specified")?;
+        self.suites
+            .iter()
+            .find(|s| s.validator() == validator)
+            .map(AsRef::as_ref)
+            .ok_or_else(|| format!("No suite for validator '{validator}'"))
+    }
+}
+
+/// Serializes a validator's typed payload for [`Suite::r_params`].
+fn to_payload<P: Serialize>(params: &P) -> Result<Value, String> {
+    serde_json::to_value(params).map_err(|e| format!("Failed to serialize R params: {e}"))
+}
+
+/// Parses forge output and the R validator's results.
+///
+/// A parse failure becomes the test's error result.
+fn parse_both<T>(
+    spec: &AnalyticsTestSpec,
+    forge: &Value,
+    r: &Value,
+    parse_forge: impl Fn(&Value) -> Result<T, String>,
+    parse_r: impl Fn(&Value) -> Result<T, String>,
+) -> Result<(T, T), TestResult> {
+    let error = |error: String| TestResult::Error {
+        name: spec.name.clone(),
+        error,
+    };
+    let forge =
+        parse_forge(forge).map_err(|e| error(format!("Failed to parse forge output: {e}")))?;
+    let r = parse_r(r).map_err(|e| error(format!("Failed to parse R results: {e}")))?;
+    Ok((forge, r))
+}
+
+/// Parses the output checked by [`Suite::compare_recorded`].
+///
+/// A parse failure becomes the test's error result.
+fn parse_output<T>(
+    spec: &AnalyticsTestSpec,
+    output: Output<'_>,
+    parse_forge: impl Fn(&Value) -> Result<T, String>,
+    parse_r: impl Fn(&Value) -> Result<T, String>,
+) -> Result<T, TestResult> {
+    let parsed = match output {
+        Output::Forge(forge) => {
+            parse_forge(forge).map_err(|e| format!("Failed to parse forge output: {e}"))
+        }
+        Output::R(r) => parse_r(r).map_err(|e| format!("Failed to parse R results: {e}")),
+    };
+    parsed.map_err(|error| TestResult::Error {
+        name: spec.name.clone(),
+        error,
+    })
+}
+
+/// Deserializes R results into the validator's typed result struct, so a
+/// shape mismatch names the offending field instead of surfacing later as a
+/// missing value.
+fn r_results<R: DeserializeOwned>(r: &Value) -> Result<R, String> {
+    R::deserialize(r).map_err(|e| e.to_string())
+}
+
+/// Deserializes a list that R's `auto_unbox` may have collapsed to a scalar.
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(item) => vec![item],
+        OneOrMany::Many(items) => items,
+    })
+}
+
+/// Deserializes a map that R wrote as `[]` because it was empty.
+fn map_or_empty<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MapOrEmpty<T> {
+        Map(T),
+        Empty([(); 0]),
+    }
+
+    Ok(match MapOrEmpty::deserialize(deserializer)? {
+        MapOrEmpty::Map(map) => map,
+        MapOrEmpty::Empty(_) => T::default(),
+    })
+}
+
+/// Groups a ranked list into runs whose values are equal within tolerance.
+fn tie_groups<'a>(ranked: &[(&'a str, f64)], tolerance: f64) -> Vec<Vec<&'a str>> {
+    let mut groups: Vec<(f64, Vec<&str>)> = Vec::new();
+    for &(name, value) in ranked {
+        match groups.last_mut() {
+            Some((head, group)) if within_tolerance(value, *head, tolerance) => group.push(name),
+            _ => groups.push((value, vec![name])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Returns the first rank (1-based) at which `ranking` leaves R's tie groups.
+fn ranking_mismatch(ranking: &[String], groups: &[Vec<&str>]) -> Option<usize> {
+    let mut position = 0;
+    for group in groups {
+        let Some(slice) = ranking.get(position..position + group.len()) else {
+            return Some(position + 1);
+        };
+        if !slice.iter().all(|name| group.contains(&name.as_str())) {
+            return Some(position + 1);
+        }
+        position += group.len();
+    }
+    (position != ranking.len()).then_some(position + 1)
+}
+
+/// Describes each tie group, e.g. `price = volume`.
+fn describe_ties(groups: &[Vec<&str>]) -> Vec<String> {
+    groups
+        .iter()
+        .filter(|g| g.len() > 1)
+        .map(|g| g.join(" = "))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(suite: Option<&str>, validator: Option<&str>) -> AnalyticsTestSpec {
+        let mut spec: AnalyticsTestSpec = serde_yaml_ng::from_str("{}").unwrap();
+        spec.suite = suite.map(str::to_string);
+        spec.r_validator = validator.map(str::to_string);
+        spec
+    }
+
+    #[test]
+    fn registry_selects_by_suite_key_then_validator() {
+        let registry = SuiteRegistry::default();
+
+        let by_validator = registry
+            .select(&spec(None, Some("tornado_validator.R")))
+            .unwrap();
+        assert_eq!(by_validator.name(), "tornado");
+
+        // `_suite:` wins over `_r_validator`
+        let by_name = registry
+            .select(&spec(Some("bootstrap"), Some("tornado_validator.R")))
+            .unwrap();
+        assert_eq!(by_name.name(), "bootstrap");
+
+        assert!(registry.select(&spec(Some("nope"), None)).is_err());
+        assert!(registry
+            .select(&spec(None, Some("date_validator.R")))
+            .is_err());
+    }
+
+    #[test]
+    fn ranking_accepts_any_order_within_ties() {
+        let groups = tie_groups(&[("a", 3.0), ("b", 2.0), ("c", 2.0)], 0.001);
+        assert_eq!(describe_ties(&groups), vec!["b = c"]);
+
+        let ranking = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(ranking_mismatch(&ranking(&["a", "c", "b"]), &groups), None);
+        assert_eq!(
+            ranking_mismatch(&ranking(&["b", "a", "c"]), &groups),
+            Some(1)
+        );
+        assert_eq!(ranking_mismatch(&ranking(&["a", "b"]), &groups), Some(2));
+    }
+}
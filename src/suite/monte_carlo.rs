@@ -0,0 +1,917 @@
+//! Monte Carlo distributions validated against `monte_carlo_validator.R`.
+
+use std::collections::HashMap;
+use std::f64::consts::SQRT_2;
+use std::fmt::Write as _;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    parse_both, parse_output, r_results, run_pipeline, to_payload, ForgeInvocation, Output,
+    RunContext, Suite,
+};
+use crate::r_validator::MonteCarloResults;
+use crate::stats::equivalence::{record_tost, tost_mean, tost_variance, Equivalence, Margin, Tost};
+use crate::stats::gof::{gof_test, GofKind, GofTest};
+use crate::stats::sampling::{
+    critical_z, normal_quantile_standard_error, quantile_standard_error, sample_moments,
+};
+use crate::stats::{
+    analytic_moments, compare, compare_either, compare_z, record_samples, relative_difference,
+    Moments, Samples, Tolerance, ToleranceMode,
+};
+use crate::types::{AnalyticsTestSpec, EquivalenceSpec, RExpected, TestResult};
+
+/// Largest error, in standard errors, still consistent with 1/sqrt(N) convergence.
+const CONVERGENCE_MAX_Z: f64 = 4.0;
+
+/// Largest slope of log error against log N accepted as convergence, halfway
+/// between the 1/sqrt(N) rate (-0.5) and an error that does not shrink (0).
+const CONVERGENCE_MAX_SLOPE: f64 = -0.25;
+
+/// Runs `forge simulate` on one `MC.*` formula and compares it with R's sample.
+///
+/// Mean, std and percentiles are checked first, against fixed tolerances or
+/// (`tolerance.mode: statistical`) their sampling error, then the shape of
+/// the distribution with the spec's goodness-of-fit tests. Moments given an
+/// `equivalence:` margin are judged by TOST instead of their tolerance.
+/// Specs with `iteration_counts` run in convergence mode instead.
+pub struct MonteCarloSuite;
+
+impl Suite for MonteCarloSuite {
+    fn name(&self) -> &'static str {
+        "monte_carlo"
+    }
+
+    fn validator(&self) -> &'static str {
+        "monte_carlo_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        let distribution = spec
+            .distribution
+            .as_deref()
+            .ok_or("No distribution specified (not a Monte Carlo test)")?;
+        build_mc_formula(distribution, &spec.params)
+            .map(|_| ())
+            .map_err(|e| format!("Cannot build formula: {e}"))
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let distribution = spec.distribution.as_deref().unwrap_or_default();
+        let formula = build_mc_formula(distribution, &spec.params)?;
+        Ok(simulate(&formula, spec.iterations, spec.seed, true))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        to_payload(&MonteCarloParams {
+            distribution: spec.distribution.as_deref().unwrap_or_default(),
+            params: &spec.params,
+            seed: spec.seed,
+            iterations: spec.iterations,
+            max_samples: spec.iterations,
+        })
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) = match parse_both(spec, forge, r, parse_mc_stats, parse_r_mc_stats) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+
+        compare_mc_stats(spec, &forge, &r)
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(spec, output, parse_mc_stats, parse_r_mc_stats) {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        let recorded = McStats {
+            mean: expected.mean.unwrap_or(actual.mean),
+            std: expected.std.unwrap_or(actual.std),
+            percentiles: expected.percentiles.clone(),
+            samples: Vec::new(),
+        };
+
+        compare_mc_stats(spec, &actual, &recorded)
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let stats = parse_r_mc_stats(r)?;
+        Ok(RExpected {
+            mean: Some(stats.mean),
+            std: Some(stats.std),
+            percentiles: stats.percentiles,
+            ..Default::default()
+        })
+    }
+
+    fn run(&self, spec: &AnalyticsTestSpec, ctx: &RunContext) -> TestResult {
+        if spec.iteration_counts.is_empty() {
+            run_pipeline(self, spec, ctx)
+        } else {
+            run_convergence(self, spec, ctx)
+        }
+    }
+}
+
+fn tolerance(spec: &AnalyticsTestSpec) -> Tolerance {
+    spec.tolerance
+        .as_ref()
+        .map(|t| Tolerance {
+            mean: t.mean.unwrap_or(0.01),
+            std: t.std.unwrap_or(0.05),
+            percentiles: t.percentiles.unwrap_or(0.02),
+            gof_pvalue: t.gof_pvalue.unwrap_or(0.05),
+            mode: t.mode.unwrap_or_default(),
+            false_failure_rate: t.false_failure_rate.unwrap_or(0.001),
+            ..Default::default()
+        })
+        .unwrap_or_default()
+}
+
+/// Goodness-of-fit tests to run: the spec's `gof:` list, or KS alone.
+fn gof_kinds(spec: &AnalyticsTestSpec) -> Vec<GofKind> {
+    spec.gof.clone().unwrap_or_else(|| vec![GofKind::Ks])
+}
+
+/// Builds the MC.* formula string for a given distribution and parameters.
+fn build_mc_formula(distribution: &str, params: &HashMap<String, f64>) -> Result<String, String> {
+    match distribution.to_lowercase().as_str() {
+        "normal" => {
+            let mean = params.get("mean").ok_or("Missing 'mean' param")?;
+            let sd = params.get("sd").ok_or("Missing 'sd' param")?;
+            Ok(format!("=MC.Normal({mean}, {sd})"))
+        }
+        "uniform" => {
+            let min = params.get("min").ok_or("Missing 'min' param")?;
+            let max = params.get("max").ok_or("Missing 'max' param")?;
+            Ok(format!("=MC.Uniform({min}, {max})"))
+        }
+        "lognormal" => {
+            let meanlog = params.get("meanlog").ok_or("Missing 'meanlog' param")?;
+            let sdlog = params.get("sdlog").ok_or("Missing 'sdlog' param")?;
+            // R uses meanlog/sdlog (log-scale parameters)
+            // Forge uses actual mean/stdev of the lognormal distribution
+            // Convert: mean = exp(meanlog + sdlog^2/2)
+            //          stdev = sqrt((exp(sdlog^2) - 1) * exp(2*meanlog + sdlog^2))
+            let mean = (meanlog + sdlog * sdlog / 2.0).exp();
+            let variance = (sdlog * sdlog).exp_m1() * (2.0 * meanlog + sdlog * sdlog).exp();
+            let stdev = variance.sqrt();
+            Ok(format!("=MC.Lognormal({mean}, {stdev})"))
+        }
+        "triangular" => {
+            let min = params.get("min").ok_or("Missing 'min' param")?;
+            let mode = params.get("mode").ok_or("Missing 'mode' param")?;
+            let max = params.get("max").ok_or("Missing 'max' param")?;
+            Ok(format!("=MC.Triangular({min}, {mode}, {max})"))
+        }
+        "pert" => {
+            let min = params.get("min").ok_or("Missing 'min' param")?;
+            let mode = params.get("mode").ok_or("Missing 'mode' param")?;
+            let max = params.get("max").ok_or("Missing 'max' param")?;
+            // Note: Forge's MC.PERT doesn't take a shape argument (uses default shape=4)
+            Ok(format!("=MC.PERT({min}, {mode}, {max})"))
+        }
+        "exponential" => {
+            // Forge doesn't support MC.Exponential yet
+            Err("Exponential distribution not supported by forge".to_string())
+        }
+        other => Err(format!("Unsupported distribution: {other}")),
+    }
+}
+
+/// Builds the `forge simulate` invocation for one `MC.*` formula, asking
+/// for every sample drawn if `export_samples` is set.
+fn simulate(formula: &str, iterations: usize, seed: u64, export_samples: bool) -> ForgeInvocation {
+    let fixture = format!(
+        r#"_forge_version: "5.0.0"
+monte_carlo:
+  enabled: true
+  iterations: {iterations}
+  sampling: monte_carlo
+  seed: {seed}
+  export_samples: {export_samples}
+  outputs:
+    - variable: test_output
+      percentiles: [5, 10, 25, 50, 75, 90, 95]
+scalars:
+  test_output:
+    value: null
+    formula: "{formula}"
+"#
+    );
+    ForgeInvocation::new("simulate", fixture)
+        .arg("--seed")
+        .arg(seed.to_string())
+}
+
+/// JSON payload for `monte_carlo_validator.R`.
+#[derive(Serialize)]
+struct MonteCarloParams<'a> {
+    distribution: &'a str,
+    params: &'a HashMap<String, f64>,
+    seed: u64,
+    iterations: usize,
+    /// Samples to return (all of them, for the KS test).
+    max_samples: usize,
+}
+
+/// Sample statistics from either forge or R.
+#[derive(Debug)]
+struct McStats {
+    mean: f64,
+    std: f64,
+    /// Keyed by percentile without the `p` prefix (`"5"`, `"50"`, ...).
+    percentiles: HashMap<String, f64>,
+    /// Samples drawn, if the output includes them.
+    samples: Vec<f64>,
+}
+
+impl From<MonteCarloResults> for McStats {
+    fn from(results: MonteCarloResults) -> Self {
+        Self {
+            mean: results.mean,
+            std: results.std,
+            percentiles: results.percentiles,
+            samples: results.samples,
+        }
+    }
+}
+
+/// Parses forge's sample statistics from
+/// `monte_carlo_results.outputs.test_output` (`std_dev`, `p5`-style keys,
+/// optional `samples`).
+fn parse_mc_stats(json: &Value) -> Result<McStats, String> {
+    let stats = json
+        .get("monte_carlo_results")
+        .ok_or("Missing monte_carlo_results")?
+        .get("outputs")
+        .ok_or("Missing outputs")?
+        .get("test_output")
+        .ok_or("Missing test_output")?;
+
+    let mean = stats
+        .get("mean")
+        .and_then(Value::as_f64)
+        .ok_or("Missing mean")?;
+    let std = stats
+        .get("std_dev")
+        .and_then(Value::as_f64)
+        .ok_or("Missing std_dev")?;
+
+    let mut percentiles = HashMap::new();
+    if let Some(p) = stats.get("percentiles").and_then(Value::as_object) {
+        for (k, v) in p {
+            if let Some(val) = v.as_f64() {
+                // Convert p5 -> 5, p50 -> 50, etc.
+                percentiles.insert(k.trim_start_matches('p').to_string(), val);
+            }
+        }
+    }
+
+    let samples = stats
+        .get("samples")
+        .and_then(Value::as_array)
+        .map(|values| values.iter().filter_map(Value::as_f64).collect())
+        .unwrap_or_default();
+
+    Ok(McStats {
+        mean,
+        std,
+        percentiles,
+        samples,
+    })
+}
+
+fn parse_r_mc_stats(r: &Value) -> Result<McStats, String> {
+    r_results::<MonteCarloResults>(r).map(McStats::from)
+}
+
+/// Percentiles compared between forge and R, and marked on the plots.
+const KEY_PERCENTILES: [&str; 3] = ["5", "50", "95"];
+
+/// Compares forge and R results, returning Pass or Fail.
+fn compare_mc_stats(spec: &AnalyticsTestSpec, forge: &McStats, r: &McStats) -> TestResult {
+    let test_name = spec.name.as_str();
+    let tolerance = tolerance(spec);
+
+    if !forge.samples.is_empty() || !r.samples.is_empty() {
+        record_samples(Samples {
+            forge: forge.samples.clone(),
+            r: r.samples.clone(),
+            quantiles: KEY_PERCENTILES
+                .iter()
+                .map(|pct| format!("p{pct}"))
+                .collect(),
+        });
+    }
+
+    let (equivalence, tosts) = spec
+        .equivalence
+        .as_ref()
+        .map_or((Equivalence::Equivalent, Vec::new()), |margins| {
+            check_equivalence(spec, margins, forge, r)
+        });
+    if equivalence == Equivalence::NotEquivalent {
+        return TestResult::Fail {
+            name: test_name.to_string(),
+            reason: format!("Not equivalent to R: {}", tosts.join("; ")),
+        };
+    }
+
+    let by_tolerance = ByTolerance::new(spec);
+    let checked = match tolerance.mode {
+        ToleranceMode::Fixed => check_fixed(forge, r, &tolerance, by_tolerance),
+        ToleranceMode::Statistical => check_statistical(spec, forge, r, &tolerance, by_tolerance),
+    };
+    let checked = match checked {
+        Ok(checked) => checked,
+        Err(reason) => {
+            return TestResult::Fail {
+                name: test_name.to_string(),
+                reason,
+            };
+        }
+    };
+
+    // Matching moments and percentiles can still hide a wrong shape
+    let mut shape = Vec::new();
+    for kind in gof_kinds(spec) {
+        match gof_test(kind, &forge.samples, &r.samples, tolerance.gof_pvalue) {
+            Some(test) if !test.passed => {
+                return TestResult::Fail {
+                    name: test_name.to_string(),
+                    reason: format!(
+                        "Distribution shape mismatch: {} (min p={})",
+                        describe_gof(&test),
+                        tolerance.gof_pvalue
+                    ),
+                };
+            }
+            Some(test) => shape.push(describe_gof(&test)),
+            None if forge.samples.is_empty() => {
+                shape.push(format!(
+                    "{} skipped: forge exported no samples",
+                    kind.label()
+                ));
+            }
+            None => shape.push(format!("{} skipped: no R samples", kind.label())),
+        }
+    }
+
+    if equivalence == Equivalence::Inconclusive {
+        return TestResult::Inconclusive {
+            name: test_name.to_string(),
+            reason: format!(
+                "Equivalence neither shown nor ruled out at n={}: {}",
+                spec.iterations,
+                tosts.join("; ")
+            ),
+        };
+    }
+
+    shape.extend(tosts);
+    TestResult::Pass {
+        name: test_name.to_string(),
+        details: format!(
+            "mean={:.2} std={:.2} {} ({checked})",
+            forge.mean,
+            forge.std,
+            shape.join(", ")
+        ),
+    }
+}
+
+/// Moments left to the tolerance checks; those with an equivalence margin
+/// are judged by TOST instead.
+#[derive(Debug, Clone, Copy)]
+struct ByTolerance {
+    mean: bool,
+    std: bool,
+}
+
+impl ByTolerance {
+    fn new(spec: &AnalyticsTestSpec) -> Self {
+        let margins = spec.equivalence.as_ref();
+        Self {
+            mean: margins.is_none_or(|m| m.mean_margin().is_none()),
+            std: margins.is_none_or(|m| m.variance.is_none()),
+        }
+    }
+}
+
+/// Runs TOST on each moment `margins` declares, returning the worst
+/// verdict and a description of each test.
+fn check_equivalence(
+    spec: &AnalyticsTestSpec,
+    margins: &EquivalenceSpec,
+    forge: &McStats,
+    r: &McStats,
+) -> (Equivalence, Vec<String>) {
+    let n = (spec.iterations, spec.iterations);
+    let excess_kurtosis = excess_kurtosis(spec, forge, r);
+    let moments = |stats: &McStats| Moments {
+        mean: stats.mean,
+        std: stats.std,
+        excess_kurtosis,
+    };
+    let (forge_moments, r_moments) = (moments(forge), moments(r));
+
+    let mut verdict = Equivalence::Equivalent;
+    let mut described = Vec::new();
+    let mut describe = |label: &str, tost: &Tost| {
+        described.push(format!(
+            "{label} {:.4}, {:.0}% CI [{:.4}, {:.4}] vs ±{:.4} (p={:.4}, {})",
+            tost.estimate,
+            2.0f64.mul_add(-margins.alpha, 1.0) * 100.0,
+            tost.interval.0,
+            tost.interval.1,
+            tost.margin,
+            tost.p_value,
+            tost.outcome.label()
+        ));
+    };
+    if let Some(margin) = margins.mean_margin() {
+        let tost = tost_mean(&forge_moments, &r_moments, n, margin, margins.alpha);
+        verdict = verdict.max(record_tost("mean TOST", forge.mean, r.mean, margin, &tost));
+        describe("mean diff", &tost);
+    }
+    if let Some(margin) = margins.variance {
+        let tost = tost_variance(&forge_moments, &r_moments, n, margin, margins.alpha);
+        verdict = verdict.max(record_tost(
+            "variance TOST",
+            forge.std.powi(2),
+            r.std.powi(2),
+            Margin::Relative(margin),
+            &tost,
+        ));
+        describe("log variance ratio", &tost);
+    }
+    (verdict, described)
+}
+
+/// Checks mean, std and percentiles against the fixed relative tolerances.
+fn check_fixed(
+    forge: &McStats,
+    r: &McStats,
+    tolerance: &Tolerance,
+    by_tolerance: ByTolerance,
+) -> Result<String, String> {
+    if by_tolerance.mean && !compare("mean", forge.mean, r.mean, tolerance.mean) {
+        let diff_pct = ((forge.mean - r.mean).abs() / r.mean.abs()) * 100.0;
+        return Err(format!(
+            "Mean mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
+            forge.mean,
+            r.mean,
+            diff_pct,
+            tolerance.mean * 100.0
+        ));
+    }
+
+    if by_tolerance.std && !compare("std", forge.std, r.std, tolerance.std) {
+        let diff_pct = ((forge.std - r.std).abs() / r.std.abs()) * 100.0;
+        return Err(format!(
+            "Std mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
+            forge.std,
+            r.std,
+            diff_pct,
+            tolerance.std * 100.0
+        ));
+    }
+
+    // Note: Different RNGs (Rust vs R) produce different random streams even with same seed.
+    // We use more lenient tolerance for percentiles since they're inherently more variable
+    // than mean/std in Monte Carlo simulations.
+    // Also use minimum absolute tolerance based on the std dev to handle edge cases.
+    let effective_pct_tolerance = tolerance.percentiles.max(0.10); // At least 10% for percentiles
+    let abs_tolerance = r.std * 0.5; // Allow half a std dev absolute difference
+
+    for (pct, forge_val, r_val) in key_percentiles(forge, r) {
+        // Pass if within relative tolerance OR within absolute tolerance
+        if !compare_either(
+            format!("p{pct}"),
+            forge_val,
+            r_val,
+            effective_pct_tolerance,
+            abs_tolerance,
+        ) {
+            let diff_pct = relative_difference(forge_val, r_val) * 100.0;
+            return Err(format!(
+                "P{} mismatch: forge={:.4}, R={:.4} (diff={:.2}%, tol={:.1}%)",
+                pct,
+                forge_val,
+                r_val,
+                diff_pct,
+                effective_pct_tolerance * 100.0
+            ));
+        }
+    }
+    Ok("within tolerance".to_string())
+}
+
+/// Checks mean, std and percentiles against the sampling error of two
+/// independent samples of `iterations` draws.
+///
+/// The kurtosis behind the std's standard error and the densities behind
+/// the quantiles' come from the samples (R's, else forge's), falling back
+/// to the analytic kurtosis and a normal density. All checks together fail
+/// a correct forge with probability `false_failure_rate`.
+fn check_statistical(
+    spec: &AnalyticsTestSpec,
+    forge: &McStats,
+    r: &McStats,
+    tolerance: &Tolerance,
+    by_tolerance: ByTolerance,
+) -> Result<String, String> {
+    let n = spec.iterations;
+    let samples = shape_samples(forge, r);
+    let (se_mean, se_std) = Moments {
+        mean: r.mean,
+        std: r.std,
+        excess_kurtosis: excess_kurtosis(spec, forge, r),
+    }
+    .standard_errors(n);
+
+    // (metric, label in messages, forge, R, standard error of one side)
+    let mut checks = Vec::new();
+    if by_tolerance.mean {
+        checks.push((
+            "mean".to_string(),
+            "Mean".to_string(),
+            forge.mean,
+            r.mean,
+            se_mean,
+        ));
+    }
+    if by_tolerance.std {
+        checks.push((
+            "std".to_string(),
+            "Std".to_string(),
+            forge.std,
+            r.std,
+            se_std,
+        ));
+    }
+    for (pct, forge_val, r_val) in key_percentiles(forge, r) {
+        let p = pct.parse::<f64>().unwrap_or_default() / 100.0;
+        let se = quantile_standard_error(samples, p, n)
+            .unwrap_or_else(|| normal_quantile_standard_error(r.std, p, n));
+        checks.push((format!("p{pct}"), format!("P{pct}"), forge_val, r_val, se));
+    }
+    let max_z = critical_z(tolerance.false_failure_rate, checks.len());
+
+    let mut largest: Option<(String, f64)> = None;
+    for (metric, label, forge_val, r_val, se) in checks {
+        // Both sides carry sampling error, so the gap has sqrt(2) times each one's
+        let gap_se = se * SQRT_2;
+        let z = (forge_val - r_val) / gap_se;
+        if !compare_z(metric.clone(), forge_val, r_val, gap_se, max_z) {
+            return Err(format!(
+                "{label} mismatch: forge={forge_val:.4}, R={r_val:.4} (z={z:.2}, max |z|={max_z:.2} at n={n})"
+            ));
+        }
+        if largest.as_ref().is_none_or(|(_, w)| z.abs() > w.abs()) {
+            largest = Some((metric, z));
+        }
+    }
+    Ok(largest.map_or_else(
+        || "within tolerance".to_string(),
+        |(metric, z)| format!("largest z={z:.2} ({metric}), max |z|={max_z:.2}"),
+    ))
+}
+
+/// Samples the kurtosis and quantile densities are estimated from: R's,
+/// else forge's.
+fn shape_samples<'a>(forge: &'a McStats, r: &'a McStats) -> &'a [f64] {
+    if r.samples.is_empty() {
+        &forge.samples
+    } else {
+        &r.samples
+    }
+}
+
+/// Excess kurtosis of the samples, else of the analytic distribution, else
+/// that of a normal (0).
+fn excess_kurtosis(spec: &AnalyticsTestSpec, forge: &McStats, r: &McStats) -> f64 {
+    sample_moments(shape_samples(forge, r))
+        .or_else(|| {
+            analytic_moments(
+                spec.distribution.as_deref().unwrap_or_default(),
+                &spec.params,
+            )
+        })
+        .map_or(0.0, |moments| moments.excess_kurtosis)
+}
+
+/// The [`KEY_PERCENTILES`] both sides report, with forge's and R's value.
+fn key_percentiles(forge: &McStats, r: &McStats) -> Vec<(&'static str, f64, f64)> {
+    KEY_PERCENTILES
+        .iter()
+        .filter_map(|&pct| Some((pct, *forge.percentiles.get(pct)?, *r.percentiles.get(pct)?)))
+        .collect()
+}
+
+/// Renders a goodness-of-fit result, e.g. `KS=0.0123 at x=101.2500 (p=0.4312)`.
+fn describe_gof(test: &GofTest) -> String {
+    let at = test
+        .at
+        .map_or_else(String::new, |at| format!(" at x={at:.4}"));
+    format!(
+        "{}={:.4}{at} (p={:.4})",
+        test.name, test.statistic, test.p_value
+    )
+}
+
+/// Runs a convergence test by:
+/// 1. Running forge simulate at each of `iteration_counts`, with a seed
+///    derived per N so the runs are independent
+/// 2. Measuring the mean and std error against the analytic moments
+/// 3. Checking each error stays within a few standard errors (sigma/sqrt(N))
+///    and that the errors shrink at the 1/sqrt(N) rate (see
+///    [`convergence_failure`])
+fn run_convergence(
+    suite: &MonteCarloSuite,
+    spec: &AnalyticsTestSpec,
+    ctx: &RunContext,
+) -> TestResult {
+    if let Err(reason) = suite.parse_spec(spec) {
+        return TestResult::Skip {
+            name: spec.name.clone(),
+            reason,
+        };
+    }
+
+    let distribution = spec.distribution.as_deref().unwrap_or_default();
+    let Some(moments) = analytic_moments(distribution, &spec.params) else {
+        return TestResult::Skip {
+            name: spec.name.clone(),
+            reason: format!("No analytic moments for {distribution}"),
+        };
+    };
+
+    let formula = match build_mc_formula(distribution, &spec.params) {
+        Ok(formula) => formula,
+        Err(e) => {
+            return TestResult::Error {
+                name: spec.name.clone(),
+                error: e,
+            };
+        }
+    };
+
+    let mut table = format!(
+        "{:>8}  {:>12} {:>10} {:>6}  {:>12} {:>10} {:>6}",
+        "N", "mean", "|err|", "z", "std", "|err|", "z"
+    );
+    let mut rows = Vec::new();
+
+    for &iterations in &spec.iteration_counts {
+        let seed = convergence_seed(spec.seed, iterations);
+        let stats = match simulate(&formula, iterations, seed, false)
+            .run(&ctx.runner)
+            .map_err(|e| format!("{e:#}"))
+            .and_then(|json| parse_mc_stats(&json))
+        {
+            Ok(stats) => stats,
+            Err(e) => {
+                return TestResult::Error {
+                    name: spec.name.clone(),
+                    error: format!("Forge failed at N={iterations}: {e}"),
+                };
+            }
+        };
+
+        let (se_mean, se_std) = moments.standard_errors(iterations);
+        let row = ConvergenceRow {
+            iterations,
+            mean_err: (stats.mean - moments.mean).abs(),
+            std_err: (stats.std - moments.std).abs(),
+            mean_z: (stats.mean - moments.mean).abs() / se_mean,
+            std_z: (stats.std - moments.std).abs() / se_std,
+        };
+        let _ = write!(
+            table,
+            "\n{iterations:>8}  {:>12.4} {:>10.4} {:>6.2}  {:>12.4} {:>10.4} {:>6.2}",
+            stats.mean, row.mean_err, row.mean_z, stats.std, row.std_err, row.std_z
+        );
+        rows.push(row);
+    }
+
+    let slope = error_slope(&rows).map_or_else(String::new, |slope| {
+        format!(", error slope={slope:.2} (1/sqrt(N): -0.5)")
+    });
+    let summary = format!(
+        "analytic mean={:.4}, std={:.4}{slope}\n{table}",
+        moments.mean, moments.std
+    );
+
+    match convergence_failure(&rows) {
+        Some(reason) if spec.expected_error_reduction => TestResult::Fail {
+            name: spec.name.clone(),
+            reason: format!("{reason}\n{summary}"),
+        },
+        _ => TestResult::Pass {
+            name: spec.name.clone(),
+            details: summary,
+        },
+    }
+}
+
+/// Seed for the run at `iterations`, distinct per N so the errors at
+/// different N are independent draws.
+const fn convergence_seed(seed: u64, iterations: usize) -> u64 {
+    seed.wrapping_add(iterations as u64)
+}
+
+/// Errors of one convergence run against the analytic moments.
+#[derive(Debug)]
+struct ConvergenceRow {
+    iterations: usize,
+    mean_err: f64,
+    std_err: f64,
+    /// `mean_err` in standard errors at this N.
+    mean_z: f64,
+    /// `std_err` in standard errors at this N.
+    std_z: f64,
+}
+
+/// Returns why the errors are not converging at the 1/sqrt(N) rate, if they
+/// are not: an error more than [`CONVERGENCE_MAX_Z`] standard errors out at
+/// some N, or errors whose log-log slope against N is flatter than
+/// [`CONVERGENCE_MAX_SLOPE`].
+fn convergence_failure(rows: &[ConvergenceRow]) -> Option<String> {
+    let worst = rows
+        .iter()
+        .map(|row| (row.iterations, row.mean_z.max(row.std_z)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((iterations, z)) = worst.filter(|&(_, z)| z > CONVERGENCE_MAX_Z) {
+        return Some(format!(
+            "Error not shrinking at 1/sqrt(N): {z:.2} standard errors at N={iterations} (max {CONVERGENCE_MAX_Z})"
+        ));
+    }
+    error_slope(rows)
+        .filter(|&slope| slope > CONVERGENCE_MAX_SLOPE)
+        .map(|slope| {
+            format!(
+                "Error not shrinking at 1/sqrt(N): log-log slope {slope:.2} (expected -0.5, max {CONVERGENCE_MAX_SLOPE})"
+            )
+        })
+}
+
+/// Least-squares slope of log |error| against log N, pooling the mean and
+/// std errors (each series with its own intercept). `None` without two
+/// distinct N or with a zero error, whose log is undefined.
+#[allow(clippy::cast_precision_loss)]
+fn error_slope(rows: &[ConvergenceRow]) -> Option<f64> {
+    let points: Vec<(f64, f64, f64)> = rows
+        .iter()
+        .map(|row| ((row.iterations as f64).ln(), row.mean_err, row.std_err))
+        .collect();
+    if points
+        .iter()
+        .any(|&(_, mean, std)| mean <= 0.0 || std <= 0.0)
+    {
+        return None;
+    }
+    let x_bar = points.iter().map(|p| p.0).sum::<f64>() / points.len() as f64;
+    let sxx: f64 = points.iter().map(|p| (p.0 - x_bar).powi(2)).sum();
+    if sxx <= 0.0 {
+        return None;
+    }
+    let series = |error: fn(&(f64, f64, f64)) -> f64| {
+        let y_bar = points.iter().map(|p| error(p).ln()).sum::<f64>() / points.len() as f64;
+        points
+            .iter()
+            .map(|p| (p.0 - x_bar) * (error(p).ln() - y_bar))
+            .sum::<f64>()
+    };
+    Some((series(|p| p.1) + series(|p| p.2)) / (2.0 * sxx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::record_comparisons;
+
+    #[test]
+    fn statistical_tolerance_narrows_with_iterations() {
+        let stats = |mean| McStats {
+            mean,
+            std: 15.0,
+            percentiles: HashMap::new(),
+            samples: Vec::new(),
+        };
+        let run = |iterations: usize| {
+            let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(&format!(
+                "{{name: normal, distribution: normal, params: {{mean: 100, sd: 15}}, \
+                 iterations: {iterations}, tolerance: {{mode: statistical}}}}"
+            ))
+            .unwrap();
+            record_comparisons(|| compare_mc_stats(&spec, &stats(100.5), &stats(100.0)))
+        };
+
+        // A 0.5% gap in the mean is 0.75 standard errors at n=1000
+        let (result, recorded) = run(1_000);
+        assert!(matches!(result, TestResult::Pass { .. }), "{result:?}");
+        let z = recorded.comparisons[0].z_score.unwrap();
+        assert!((z - 0.5 / (15.0 / 1_000_f64.sqrt() * SQRT_2)).abs() < 1e-9);
+
+        // and 7.5 at n=100000
+        let (result, recorded) = run(100_000);
+        assert!(matches!(result, TestResult::Fail { .. }), "{result:?}");
+        assert!(!recorded.comparisons[0].passed);
+    }
+
+    #[test]
+    fn underpowered_equivalence_is_inconclusive() {
+        let stats = |mean| McStats {
+            mean,
+            std: 15.0,
+            percentiles: HashMap::new(),
+            samples: Vec::new(),
+        };
+        let run = |iterations: usize, forge_mean| {
+            let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(&format!(
+                "{{name: normal, iterations: {iterations}, equivalence: {{mean: 0.01}}}}"
+            ))
+            .unwrap();
+            compare_mc_stats(&spec, &stats(forge_mean), &stats(100.0))
+        };
+
+        // 90% CI of the difference: 0.2 ± 1.10 at n=1000, 0.2 ± 0.11 at n=100000
+        assert!(matches!(run(1_000, 100.2), TestResult::Inconclusive { .. }));
+        assert!(matches!(run(100_000, 100.2), TestResult::Pass { .. }));
+        assert!(matches!(run(100_000, 102.0), TestResult::Fail { .. }));
+    }
+
+    #[test]
+    fn flat_error_fails_the_convergence_rate() {
+        // Normal(100, 15): both errors given as a multiple of their standard error
+        let moments = analytic_moments(
+            "normal",
+            &HashMap::from([("mean".to_string(), 100.0), ("sd".to_string(), 15.0)]),
+        )
+        .unwrap();
+        let rows = |errors: &[(usize, f64, f64)]| -> Vec<ConvergenceRow> {
+            errors
+                .iter()
+                .map(|&(iterations, mean_err, std_err)| {
+                    let (se_mean, se_std) = moments.standard_errors(iterations);
+                    ConvergenceRow {
+                        iterations,
+                        mean_err,
+                        std_err,
+                        mean_z: mean_err / se_mean,
+                        std_z: std_err / se_std,
+                    }
+                })
+                .collect()
+        };
+        let at_rate = |iterations: usize, z: f64| {
+            let (se_mean, se_std) = moments.standard_errors(iterations);
+            (iterations, z * se_mean, z * se_std)
+        };
+
+        let converging = rows(&[
+            at_rate(1_000, 1.5),
+            at_rate(10_000, 0.8),
+            at_rate(100_000, 1.2),
+        ]);
+        assert!(convergence_failure(&converging).is_none());
+        let slope = error_slope(&converging).unwrap();
+        assert!((slope + 0.5).abs() < 0.2, "{slope}");
+
+        // Within 4 standard errors at every N, but not shrinking at all
+        let flat = rows(&[
+            (1_000, 0.05, 0.03),
+            (10_000, 0.05, 0.03),
+            (100_000, 0.05, 0.03),
+        ]);
+        assert!(flat
+            .iter()
+            .all(|row| row.mean_z.max(row.std_z) < CONVERGENCE_MAX_Z));
+        let reason = convergence_failure(&flat).unwrap();
+        assert!(reason.contains("log-log slope"), "{reason}");
+        assert!(error_slope(&flat).unwrap().abs() < 1e-12);
+
+        // A single N has no rate to check
+        assert!(error_slope(&rows(&[at_rate(1_000, 1.0)])).is_none());
+    }
+
+    #[test]
+    fn convergence_runs_use_a_seed_per_n() {
+        let seeds: Vec<u64> = [1_000, 10_000, 100_000]
+            .iter()
+            .map(|&n| convergence_seed(42, n))
+            .collect();
+        assert_eq!(seeds, [1_042, 10_042, 100_042]);
+    }
+}
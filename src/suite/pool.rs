@@ -0,0 +1,402 @@
+//! Parallel test execution.
+//!
+//! Tests and formula suites are handed out to `jobs` threads. Results are
+//! reported in spec order regardless of which job finishes first, so output
+//! and summaries match a sequential run.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::formula::run_formula_suite;
+use super::{RunContext, Suite, SuiteRegistry};
+use crate::stats::gof::defer_verdicts;
+use crate::stats::multiple::{adjust_p_values, PAdjust};
+use crate::stats::{record_comparisons, Comparison};
+use crate::types::{AnalyticsTestSpec, FormulaSuite, TestReport, TestResult};
+
+/// One unit of work.
+enum Job<'a> {
+    Test(&'a AnalyticsTestSpec),
+    /// A formula suite runs as a single forge model, so it is one job.
+    Formulas(&'a FormulaSuite),
+}
+
+/// Runs every test and formula suite on up to `jobs` threads.
+///
+/// `report` is called on the calling thread for each result, in spec order
+/// (tests first, then formula suites). Tests of a [`Suite::serial`] suite
+/// never overlap each other; time spent waiting for one is not counted in
+/// its duration.
+///
+/// [`Suite::serial`]: super::Suite::serial
+pub fn run_parallel(
+    tests: &[AnalyticsTestSpec],
+    formula_suites: &[FormulaSuite],
+    registry: &SuiteRegistry,
+    ctx: &RunContext,
+    jobs: usize,
+    mut report: impl FnMut(&TestReport),
+) -> Vec<TestReport> {
+    let queue: Vec<Job> = tests
+        .iter()
+        .map(Job::Test)
+        .chain(formula_suites.iter().map(Job::Formulas))
+        .collect();
+
+    let serial: HashMap<&str, Mutex<()>> = registry
+        .names()
+        .filter(|name| registry.get(name).is_some_and(Suite::serial))
+        .map(|name| (name, Mutex::new(())))
+        .collect();
+
+    let run = |job: &Job| match job {
+        Job::Test(spec) => {
+            let report = match registry.select(spec) {
+                Ok(suite) => {
+                    let _guard = serial
+                        .get(suite.name())
+                        .map(|lock| lock.lock().unwrap_or_else(PoisonError::into_inner));
+                    let start = Instant::now();
+                    let (result, recorded) = record_comparisons(|| {
+                        defer_verdicts(ctx.p_adjust != PAdjust::None, || suite.run(spec, ctx))
+                    });
+                    TestReport {
+                        result,
+                        suite: suite.name().to_string(),
+                        duration: start.elapsed(),
+                        comparisons: recorded.comparisons,
+                        gof: recorded.gof,
+                        samples: recorded.samples,
+                    }
+                }
+                Err(reason) => TestReport {
+                    result: TestResult::Skip {
+                        name: spec.name.clone(),
+                        reason,
+                    },
+                    suite: spec.suite.clone().unwrap_or_default(),
+                    duration: Duration::ZERO,
+                    comparisons: Vec::new(),
+                    gof: Vec::new(),
+                    samples: None,
+                },
+            };
+            vec![report]
+        }
+        Job::Formulas(suite) => run_formula_suite(suite, ctx),
+    };
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results = Vec::new();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, queue.len().max(1)) {
+            let sender = sender.clone();
+            let (next, queue, run) = (&next, &queue, &run);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = queue.get(index) else {
+                    break;
+                };
+                if sender.send((index, run(job))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Buffer out-of-order completions until the next job in order is done
+        let mut pending = BTreeMap::new();
+        let mut reported = 0;
+        for (index, job_results) in receiver {
+            pending.insert(index, job_results);
+            while let Some(job_results) = pending.remove(&reported) {
+                for result in &job_results {
+                    report(result);
+                }
+                results.extend(job_results);
+                reported += 1;
+            }
+        }
+    });
+
+    results
+}
+
+/// Adjusts every p-value of the run together by `method`: those of the
+/// goodness-of-fit tests and of the equivalence tests (TOST). Each test is
+/// then judged on its adjusted p-values.
+///
+/// Tests run with their goodness-of-fit verdicts deferred when a correction
+/// is set, so a passed or inconclusive test whose adjusted goodness-of-fit
+/// p-value is too small becomes a failure here. Equivalence is shown by a
+/// p-value below α, so a passed test whose adjusted TOST p-value is no
+/// longer below it becomes inconclusive. Returns the indices of the tests
+/// whose result changed.
+pub fn adjust_reports(method: PAdjust, reports: &mut [TestReport]) -> Vec<usize> {
+    if method == PAdjust::None {
+        return Vec::new();
+    }
+    let p_values: Vec<f64> = reports
+        .iter()
+        .flat_map(|report| {
+            let gof = report.gof.iter().map(|gof| gof.p_value);
+            let tost = report.comparisons.iter().filter_map(tost_p_value);
+            gof.chain(tost.map(|(p_value, _)| p_value))
+        })
+        .collect();
+    let mut adjusted = adjust_p_values(method, &p_values).into_iter();
+
+    let mut changed = Vec::new();
+    for (index, report) in reports.iter_mut().enumerate() {
+        let mut mismatches = Vec::new();
+        for gof in &mut report.gof {
+            let p_value = adjusted.next().unwrap_or(gof.p_value);
+            gof.adjusted_p_value = Some(p_value);
+            gof.passed = p_value >= gof.min_p_value;
+            if !gof.passed {
+                mismatches.push(format!(
+                    "{}={:.4} (p={:.4}, adjusted p={p_value:.4}, min p={})",
+                    gof.name, gof.statistic, gof.p_value, gof.min_p_value
+                ));
+            }
+        }
+        let mut unshown = Vec::new();
+        for comparison in &mut report.comparisons {
+            let Some((p_value, alpha)) = tost_p_value(comparison) else {
+                continue;
+            };
+            let adjusted_p_value = adjusted.next().unwrap_or(p_value);
+            comparison.adjusted_p_value = Some(adjusted_p_value);
+            if comparison.passed && adjusted_p_value >= alpha {
+                comparison.passed = false;
+                unshown.push(format!(
+                    "{} (p={p_value:.4}, adjusted p={adjusted_p_value:.4}, alpha={alpha})",
+                    comparison.metric
+                ));
+            }
+        }
+
+        let name = report.result.name().to_string();
+        if !mismatches.is_empty()
+            && matches!(
+                report.result,
+                TestResult::Pass { .. } | TestResult::Inconclusive { .. }
+            )
+        {
+            report.result = TestResult::Fail {
+                name,
+                reason: format!(
+                    "Distribution shape mismatch after {} correction: {}",
+                    method.label(),
+                    mismatches.join(", ")
+                ),
+            };
+            changed.push(index);
+        } else if !unshown.is_empty() && report.result.is_pass() {
+            report.result = TestResult::Inconclusive {
+                name,
+                reason: format!(
+                    "Equivalence not shown after {} correction: {}",
+                    method.label(),
+                    unshown.join(", ")
+                ),
+            };
+            changed.push(index);
+        }
+    }
+    changed
+}
+
+/// The p-value and α of an equivalence test's comparison.
+fn tost_p_value(comparison: &Comparison) -> Option<(f64, f64)> {
+    comparison.p_value.zip(comparison.alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::stats::gof::GofTest;
+    use crate::suite::ForgeInvocation;
+
+    /// Sleeps `seed` ms and fails if another of its tests is running.
+    #[derive(Default)]
+    struct SlowSerialSuite {
+        running: AtomicBool,
+    }
+
+    impl Suite for SlowSerialSuite {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn validator(&self) -> &'static str {
+            "slow.R"
+        }
+
+        fn parse_spec(&self, _: &AnalyticsTestSpec) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn forge_invocation(&self, _: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+            Err("unused".to_string())
+        }
+
+        fn r_params(&self, _: &AnalyticsTestSpec) -> Result<Value, String> {
+            Err("unused".to_string())
+        }
+
+        fn compare(&self, spec: &AnalyticsTestSpec, _: &Value, _: &Value) -> TestResult {
+            TestResult::Skip {
+                name: spec.name.clone(),
+                reason: "unused".to_string(),
+            }
+        }
+
+        fn serial(&self) -> bool {
+            true
+        }
+
+        fn run(&self, spec: &AnalyticsTestSpec, _: &RunContext) -> TestResult {
+            let overlapped = self.running.swap(true, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(spec.seed));
+            self.running.store(false, Ordering::SeqCst);
+            if overlapped {
+                TestResult::Fail {
+                    name: spec.name.clone(),
+                    reason: "overlapped".to_string(),
+                }
+            } else {
+                TestResult::Pass {
+                    name: spec.name.clone(),
+                    details: String::new(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_in_spec_order_and_serializes_suites() {
+        let mut registry = SuiteRegistry::empty();
+        registry.register(Box::new(SlowSerialSuite::default()));
+        let tests: Vec<AnalyticsTestSpec> = [30, 1, 20, 5]
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| {
+                serde_yaml_ng::from_str(&format!("{{name: t{i}, seed: {ms}, suite: slow}}"))
+                    .unwrap()
+            })
+            .collect();
+        let ctx = RunContext {
+            runner: crate::cli_runner::RunnerConfig::default(),
+            r_config: crate::r_validator::RConfig::default(),
+            r_worker: None,
+            r_cache: None,
+            oracle: crate::suite::Oracle::Live,
+            p_adjust: PAdjust::None,
+        };
+
+        let mut reported = Vec::new();
+        let reports = run_parallel(&tests, &[], &registry, &ctx, 4, |r| {
+            reported.push(r.result.name().to_string());
+        });
+
+        assert_eq!(reported, ["t0", "t1", "t2", "t3"]);
+        assert!(reports.iter().all(|r| r.result.is_pass()), "{reports:?}");
+        assert!(reports[0].duration >= Duration::from_millis(30));
+        assert!(reports.iter().all(|r| r.suite == "slow"));
+    }
+
+    #[test]
+    fn adjusted_p_values_decide_shape_failures() {
+        let report = |name: &str, p_value: f64| TestReport {
+            result: TestResult::Pass {
+                name: name.to_string(),
+                details: String::new(),
+            },
+            suite: "monte_carlo".to_string(),
+            duration: Duration::ZERO,
+            comparisons: Vec::new(),
+            gof: vec![GofTest {
+                name: "KS".to_string(),
+                statistic: 0.1,
+                p_value,
+                min_p_value: 0.05,
+                adjusted_p_value: None,
+                at: None,
+                passed: true,
+            }],
+            samples: None,
+        };
+        let mut reports = vec![
+            report("a", 0.03),
+            report("b", 0.001),
+            report("c", 0.5),
+            report("d", 0.6),
+        ];
+
+        // Holm: 0.03 * 3 = 0.09 passes, 0.001 * 4 = 0.004 fails
+        assert_eq!(adjust_reports(PAdjust::Holm, &mut reports), [1]);
+        assert!(reports[0].result.is_pass());
+        assert_eq!(reports[0].gof[0].adjusted_p_value, Some(0.09));
+        assert!(reports[1].result.is_fail());
+        assert!(!reports[1].gof[0].passed);
+        assert!(adjust_reports(PAdjust::None, &mut reports).is_empty());
+    }
+
+    #[test]
+    fn adjusted_tost_p_values_can_leave_equivalence_unshown() {
+        let report = |name: &str, p_value: f64| TestReport {
+            result: TestResult::Pass {
+                name: name.to_string(),
+                details: String::new(),
+            },
+            suite: "monte_carlo".to_string(),
+            duration: Duration::ZERO,
+            comparisons: vec![Comparison {
+                metric: "mean TOST".to_string(),
+                forge: 100.2,
+                r: 100.0,
+                relative_diff: 0.002,
+                tolerance: Some(0.01),
+                absolute_tolerance: None,
+                z_score: None,
+                p_value: Some(p_value),
+                alpha: Some(0.05),
+                adjusted_p_value: None,
+                passed: true,
+            }],
+            gof: Vec::new(),
+            samples: None,
+        };
+        let mut reports = vec![report("a", 0.03), report("b", 0.001)];
+        reports[1].gof.push(GofTest {
+            name: "KS".to_string(),
+            statistic: 0.01,
+            p_value: 0.5,
+            min_p_value: 0.05,
+            adjusted_p_value: None,
+            at: None,
+            passed: true,
+        });
+
+        // Holm over [0.03, 0.001, 0.5]: 0.001 * 3, then 0.03 * 2 = 0.06 >= alpha
+        assert_eq!(adjust_reports(PAdjust::Holm, &mut reports), [0]);
+        let TestResult::Inconclusive { reason, .. } = &reports[0].result else {
+            panic!("expected inconclusive: {:?}", reports[0].result);
+        };
+        assert!(reason.contains("adjusted p=0.0600"), "{reason}");
+        assert!(!reports[0].comparisons[0].passed);
+        assert!(reports[1].result.is_pass());
+        assert_eq!(reports[1].comparisons[0].adjusted_p_value, Some(0.003));
+        assert_eq!(reports[1].gof[0].adjusted_p_value, Some(0.5));
+    }
+}
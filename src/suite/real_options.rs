@@ -0,0 +1,296 @@
+//! Real option prices and Greeks validated against `real_options_validator.R`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{parse_both, parse_output, to_payload, ForgeInvocation, Output, Suite};
+use crate::stats::{compare, relative_difference};
+use crate::types::{AnalyticsTestSpec, Greeks, RExpected, TestResult};
+
+/// Runs `forge real-options` and compares the price and each Greek with the
+/// Black-Scholes / CRR binomial values from R, each with its own tolerance.
+pub struct RealOptionsSuite;
+
+impl Suite for RealOptionsSuite {
+    fn name(&self) -> &'static str {
+        "real_options"
+    }
+
+    fn validator(&self) -> &'static str {
+        "real_options_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        if spec.spot.is_none() || spec.strike.is_none() {
+            return Err("No S/K specified (not a real options test)".to_string());
+        }
+        Ok(())
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let o = OptionSpec::from_spec(spec)?;
+        let fixture = format!(
+            r#"_forge_version: "5.0.0"
+real_options:
+  model: {model}
+  option_type: {option_type}
+  underlying_value: {spot}
+  strike: {strike}
+  risk_free_rate: {rate}
+  volatility: {sigma}
+  time_to_expiry: {maturity}
+  dividend_yield: {dividend_yield}
+  steps: {steps}
+  american: {american}
+"#,
+            model = o.model,
+            option_type = o.option_type,
+            spot = o.spot,
+            strike = o.strike,
+            rate = o.rate,
+            sigma = o.sigma,
+            maturity = o.maturity,
+            dividend_yield = o.dividend_yield,
+            steps = o.steps,
+            american = o.american,
+        );
+        Ok(ForgeInvocation::new("real-options", fixture))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        to_payload(&OptionSpec::from_spec(spec)?)
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) =
+            match parse_both(spec, forge, r, parse_option_results, parse_option_results) {
+                Ok(parsed) => parsed,
+                Err(result) => return result,
+            };
+
+        let (price_tolerance, greeks_tolerance) = tolerances(spec);
+        compare_option_results(&spec.name, &forge, &r, price_tolerance, greeks_tolerance)
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(spec, output, parse_option_results, parse_option_results) {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        // Only the recorded Greeks are compared
+        let recorded = OptionResults {
+            price: expected.price.unwrap_or(actual.price),
+            greeks: expected.greeks.clone(),
+        };
+
+        let (price_tolerance, greeks_tolerance) = tolerances(spec);
+        compare_option_results(
+            &spec.name,
+            &actual,
+            &recorded,
+            price_tolerance,
+            greeks_tolerance,
+        )
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let results = parse_option_results(r)?;
+        Ok(RExpected {
+            price: Some(results.price),
+            greeks: results.greeks,
+            ..Default::default()
+        })
+    }
+}
+
+/// Relative tolerances for the price and the Greeks.
+fn tolerances(spec: &AnalyticsTestSpec) -> (f64, f64) {
+    let tolerance = spec.tolerance.as_ref();
+    (
+        tolerance.and_then(|t| t.price).unwrap_or(0.001),
+        tolerance.and_then(|t| t.greeks).unwrap_or(0.01),
+    )
+}
+
+/// Option parameters with defaults applied, serialized as the JSON payload
+/// for `real_options_validator.R`.
+#[derive(Serialize)]
+struct OptionSpec<'a> {
+    model: &'a str,
+    option_type: &'a str,
+    #[serde(rename = "S")]
+    spot: f64,
+    #[serde(rename = "K")]
+    strike: f64,
+    #[serde(rename = "r")]
+    rate: f64,
+    sigma: f64,
+    #[serde(rename = "T")]
+    maturity: f64,
+    #[serde(rename = "q")]
+    dividend_yield: f64,
+    #[serde(rename = "n")]
+    steps: usize,
+    american: bool,
+}
+
+impl<'a> OptionSpec<'a> {
+    fn from_spec(spec: &'a AnalyticsTestSpec) -> Result<Self, String> {
+        let (Some(spot), Some(strike)) = (spec.spot, spec.strike) else {
+            return Err("No S/K specified".to_string());
+        };
+        Ok(Self {
+            model: spec.model.as_deref().unwrap_or("black_scholes"),
+            option_type: spec.option_type.as_deref().unwrap_or("call"),
+            spot,
+            strike,
+            rate: spec.rate.unwrap_or(0.05),
+            sigma: spec.sigma.unwrap_or(0.3),
+            maturity: spec.maturity.unwrap_or(1.0),
+            dividend_yield: spec.dividend_yield.unwrap_or(0.0),
+            steps: spec.steps.unwrap_or(100),
+            american: spec.american,
+        })
+    }
+}
+
+/// Parsed option valuation from either forge or R.
+#[derive(Debug, Deserialize)]
+struct OptionResults {
+    price: f64,
+    #[serde(default)]
+    greeks: Option<Greeks>,
+}
+
+/// Parses option results (forge output is nested under `real_options_results`).
+fn parse_option_results(json: &Value) -> Result<OptionResults, String> {
+    let results = json.get("real_options_results").unwrap_or(json);
+    serde_json::from_value(results.clone()).map_err(|e| format!("Malformed option results: {e}"))
+}
+
+/// Compares forge and R option valuations, returning Pass or Fail.
+fn compare_option_results(
+    test_name: &str,
+    forge: &OptionResults,
+    r: &OptionResults,
+    price_tolerance: f64,
+    greeks_tolerance: f64,
+) -> TestResult {
+    if !compare("price", forge.price, r.price, price_tolerance) {
+        return TestResult::Fail {
+            name: test_name.to_string(),
+            reason: format!(
+                "Price mismatch: forge={:.4}, R={:.4} (diff={:.3}%, tol={:.2}%)",
+                forge.price,
+                r.price,
+                relative_difference(forge.price, r.price) * 100.0,
+                price_tolerance * 100.0
+            ),
+        };
+    }
+
+    // Binomial results carry no Greeks from R; only compare what R computed
+    let mut compared = 0;
+    let mut diverged = Vec::new();
+    if let Some(ref r_greeks) = r.greeks {
+        let forge_greeks = forge.greeks.clone().unwrap_or_default();
+        for ((greek, r_val), (_, forge_val)) in
+            r_greeks.named().into_iter().zip(forge_greeks.named())
+        {
+            let Some(r_val) = r_val else {
+                continue;
+            };
+            compared += 1;
+            match forge_val {
+                Some(forge_val) if compare(greek, forge_val, r_val, greeks_tolerance) => {}
+                Some(forge_val) => diverged.push(format!(
+                    "{greek}: forge={forge_val:.4}, R={r_val:.4} (diff={:.2}%)",
+                    relative_difference(forge_val, r_val) * 100.0
+                )),
+                None => diverged.push(format!("{greek}: missing from forge output")),
+            }
+        }
+    }
+
+    if !diverged.is_empty() {
+        return TestResult::Fail {
+            name: test_name.to_string(),
+            reason: format!(
+                "Greeks mismatch (tol={:.1}%): {}",
+                greeks_tolerance * 100.0,
+                diverged.join("; ")
+            ),
+        };
+    }
+
+    TestResult::Pass {
+        name: test_name.to_string(),
+        details: format!(
+            "price={:.4} ({compared} Greeks within tolerance)",
+            forge.price
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(price: f64, delta: f64, gamma: Option<f64>) -> OptionResults {
+        OptionResults {
+            price,
+            greeks: Some(Greeks {
+                delta: Some(delta),
+                gamma,
+                ..Greeks::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn spec_maps_option_symbols() {
+        let yaml = r"
+model: binomial
+option_type: put
+S: 100
+K: 110
+sigma: 0.30
+n: 200
+american: true
+";
+        let spec: AnalyticsTestSpec = serde_yaml_ng::from_str(yaml).unwrap();
+        let option = OptionSpec::from_spec(&spec).unwrap();
+        assert!((option.strike - 110.0).abs() < f64::EPSILON);
+        assert_eq!(option.steps, 200);
+        assert!(option.american);
+        // Unset parameters take their defaults
+        assert!((option.rate - 0.05).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn diverging_greeks_are_named() {
+        let r = results(15.4129, -0.3757, Some(0.0120));
+        let forge = results(15.4130, -0.3758, Some(0.0120));
+        assert!(compare_option_results("t", &forge, &r, 0.001, 0.01).is_pass());
+
+        let forge = results(15.4130, -0.3900, Some(0.0120));
+        let TestResult::Fail { reason, .. } = compare_option_results("t", &forge, &r, 0.001, 0.01)
+        else {
+            panic!("expected a failure");
+        };
+        assert!(reason.contains("delta: forge=-0.3900"), "{reason}");
+        assert!(!reason.contains("gamma"), "{reason}");
+
+        let forge = results(15.4130, -0.3758, None);
+        let TestResult::Fail { reason, .. } = compare_option_results("t", &forge, &r, 0.001, 0.01)
+        else {
+            panic!("expected a failure");
+        };
+        assert!(reason.contains("gamma: missing"), "{reason}");
+    }
+}
@@ -0,0 +1,528 @@
+//! One-way, two-way and elasticity sensitivity validated against
+//! `sensitivity_validator.R`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    describe_ties, one_or_many, parse_both, parse_output, ranking_mismatch, tie_groups, to_payload,
+    ForgeInvocation, Output, Suite,
+};
+use crate::stats::{compare, relative_difference};
+use crate::types::{AnalyticsTestSpec, ElasticityEntry, RExpected, SensitivityRange, TestResult};
+
+/// Runs `forge sensitivity` and compares sweeps element by element, grids
+/// cell by cell and elasticities per variable.
+pub struct SensitivitySuite;
+
+impl Suite for SensitivitySuite {
+    fn name(&self) -> &'static str {
+        "sensitivity"
+    }
+
+    fn validator(&self) -> &'static str {
+        "sensitivity_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        if spec.base_values.is_empty() {
+            return Err("No base_values specified (not a sensitivity test)".to_string());
+        }
+        Ok(())
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let fixture = serde_yaml_ng::to_string(&SensitivityFixtureFile {
+            forge_version: "5.0.0",
+            sensitivity: &SensitivityFixture::from_spec(spec),
+        })
+        .map_err(|e| format!("Failed to serialize model: {e}"))?;
+        Ok(ForgeInvocation::new("sensitivity", fixture))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        // The fixture doubles as the R payload
+        to_payload(&SensitivityFixture::from_spec(spec))
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) = match parse_both(
+            spec,
+            forge,
+            r,
+            parse_sensitivity_results,
+            parse_sensitivity_results,
+        ) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+
+        compare_sensitivity_results(spec, &forge, &r)
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(
+            spec,
+            output,
+            parse_sensitivity_results,
+            parse_sensitivity_results,
+        ) {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        let recorded = recorded_sensitivity_results(&actual, expected);
+
+        compare_sensitivity_results(spec, &actual, &recorded)
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let results = parse_sensitivity_results(r)?;
+        let elasticities = results
+            .elasticities
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| ElasticityEntry {
+                rank: Some(i + 1),
+                variable: e.variable,
+                elasticity: e.elasticity,
+                interpretation: None,
+            })
+            .collect();
+        Ok(RExpected {
+            vary_values: results.vary_values,
+            outputs: results.outputs,
+            base_output: results.base_output,
+            elasticity: results.elasticity,
+            min_output: results.min_output,
+            max_output: results.max_output,
+            range: results.range,
+            grid: results.grid,
+            elasticities,
+            ..Default::default()
+        })
+    }
+}
+
+/// Sensitivity model, written as the forge fixture and sent to R.
+#[derive(Serialize)]
+struct SensitivityFixture<'a> {
+    analysis_type: &'a str,
+    model: &'a str,
+    base_values: &'a HashMap<String, f64>,
+    coefficients: &'a HashMap<String, f64>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    vary: &'a [String],
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    range: &'a HashMap<String, SensitivityRange>,
+}
+
+impl<'a> SensitivityFixture<'a> {
+    fn from_spec(spec: &'a AnalyticsTestSpec) -> Self {
+        Self {
+            analysis_type: spec.analysis_type.as_deref().unwrap_or("one_way"),
+            model: spec.model.as_deref().unwrap_or("linear"),
+            base_values: &spec.base_values,
+            coefficients: &spec.coefficients,
+            vary: &spec.vary,
+            range: &spec.range,
+        }
+    }
+}
+
+/// Forge fixture file wrapping the sensitivity model.
+#[derive(Serialize)]
+struct SensitivityFixtureFile<'a> {
+    #[serde(rename = "_forge_version")]
+    forge_version: &'a str,
+    sensitivity: &'a SensitivityFixture<'a>,
+}
+
+/// Elasticity of one input, as reported by forge or R.
+#[derive(Debug, Clone, Deserialize)]
+struct ElasticityResult {
+    variable: String,
+    elasticity: f64,
+}
+
+/// Parsed sensitivity results from either forge or R.
+#[derive(Debug, Default, Deserialize)]
+struct SensitivityResults {
+    #[serde(default, deserialize_with = "one_or_many")]
+    vary_values: Vec<f64>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    outputs: Vec<f64>,
+    base_output: Option<f64>,
+    elasticity: Option<f64>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    values1: Vec<f64>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    values2: Vec<f64>,
+    #[serde(default)]
+    grid: Vec<Vec<f64>>,
+    min_output: Option<f64>,
+    max_output: Option<f64>,
+    /// Output range of a one-way sweep; recorded, not compared.
+    range: Option<f64>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    elasticities: Vec<ElasticityResult>,
+}
+
+impl SensitivityResults {
+    fn elasticity_of(&self, variable: &str) -> Option<f64> {
+        self.elasticities
+            .iter()
+            .find(|e| e.variable == variable)
+            .map(|e| e.elasticity)
+    }
+}
+
+/// Overlays every recorded value on `actual`, so only those can differ.
+fn recorded_sensitivity_results(
+    actual: &SensitivityResults,
+    expected: &RExpected,
+) -> SensitivityResults {
+    let series = |recorded: &[f64], actual: &[f64]| {
+        if recorded.is_empty() {
+            actual
+        } else {
+            recorded
+        }
+        .to_vec()
+    };
+    let elasticities = if expected.elasticities.is_empty() {
+        actual.elasticities.clone()
+    } else {
+        expected
+            .elasticities
+            .iter()
+            .map(|e| ElasticityResult {
+                variable: e.variable.clone(),
+                elasticity: e.elasticity,
+            })
+            .collect()
+    };
+
+    SensitivityResults {
+        vary_values: series(&expected.vary_values, &actual.vary_values),
+        outputs: series(&expected.outputs, &actual.outputs),
+        base_output: expected.base_output.or(actual.base_output),
+        elasticity: expected.elasticity.or(actual.elasticity),
+        values1: actual.values1.clone(),
+        values2: actual.values2.clone(),
+        grid: if expected.grid.is_empty() {
+            actual.grid.clone()
+        } else {
+            expected.grid.clone()
+        },
+        min_output: expected.min_output.or(actual.min_output),
+        max_output: expected.max_output.or(actual.max_output),
+        range: expected.range.or(actual.range),
+        elasticities,
+    }
+}
+
+/// Parses sensitivity results (forge nests under `sensitivity_results`, R under `data`).
+fn parse_sensitivity_results(json: &Value) -> Result<SensitivityResults, String> {
+    let results = json
+        .get("sensitivity_results")
+        .or_else(|| json.get("data"))
+        .unwrap_or(json);
+    serde_json::from_value(results.clone())
+        .map_err(|e| format!("Malformed sensitivity results: {e}"))
+}
+
+/// Compares two series element by element, labelling each point with `label`.
+fn compare_series(
+    what: &str,
+    forge: &[f64],
+    r: &[f64],
+    tolerance: f64,
+    label: impl Fn(usize) -> String,
+) -> Result<(), String> {
+    if forge.len() != r.len() {
+        return Err(format!(
+            "{what} length mismatch: forge={}, R={}",
+            forge.len(),
+            r.len()
+        ));
+    }
+    for (i, (&f, &r_val)) in forge.iter().zip(r).enumerate() {
+        let point = format!("{what}[{i}] ({})", label(i));
+        if !compare(point.as_str(), f, r_val, tolerance) {
+            return Err(format!(
+                "{point} mismatch: forge={f:.4}, R={r_val:.4} (diff={:.3}%, tol={:.2}%)",
+                relative_difference(f, r_val) * 100.0,
+                tolerance * 100.0
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Compares an optional scalar that R reported.
+fn compare_scalar(
+    what: &str,
+    forge: Option<f64>,
+    r: Option<f64>,
+    tolerance: f64,
+) -> Result<(), String> {
+    let Some(r) = r else {
+        return Ok(());
+    };
+    let Some(f) = forge else {
+        return Err(format!("{what} missing from forge output"));
+    };
+    if compare(what, f, r, tolerance) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{what} mismatch: forge={f:.4}, R={r:.4} (diff={:.3}%, tol={:.2}%)",
+            relative_difference(f, r) * 100.0,
+            tolerance * 100.0
+        ))
+    }
+}
+
+/// Compares forge and R sensitivity results, returning Pass or Fail.
+///
+/// One-way sweeps are checked point by point, two-way grids cell by cell
+/// (failures name the grid coordinate and input values), and elasticities
+/// per variable with R's ranking compared up to ties.
+fn compare_sensitivity_results(
+    test: &AnalyticsTestSpec,
+    forge: &SensitivityResults,
+    r: &SensitivityResults,
+) -> TestResult {
+    let tolerance = test.tolerance.as_ref();
+    let output_tolerance = tolerance.and_then(|t| t.output).unwrap_or(0.001);
+    let elasticity_tolerance = tolerance.and_then(|t| t.elasticity).unwrap_or(0.001);
+
+    let outcome = match test.analysis_type.as_deref().unwrap_or("one_way") {
+        "two_way" => compare_sensitivity_grid(test, forge, r, output_tolerance),
+        "elasticity" => compare_elasticities(forge, r, output_tolerance, elasticity_tolerance),
+        _ => compare_one_way(test, forge, r, output_tolerance, elasticity_tolerance),
+    };
+
+    match outcome {
+        Ok(details) => TestResult::Pass {
+            name: test.name.clone(),
+            details,
+        },
+        Err(reason) => TestResult::Fail {
+            name: test.name.clone(),
+            reason,
+        },
+    }
+}
+
+/// Checks a one-way sweep: input values, outputs, base output and elasticity.
+fn compare_one_way(
+    test: &AnalyticsTestSpec,
+    forge: &SensitivityResults,
+    r: &SensitivityResults,
+    output_tolerance: f64,
+    elasticity_tolerance: f64,
+) -> Result<String, String> {
+    let variable = test.vary.first().map_or("x", String::as_str);
+    let at = |i: usize| {
+        r.vary_values
+            .get(i)
+            .map_or_else(String::new, |v| format!("{variable}={v}"))
+    };
+
+    compare_series(
+        "vary_values",
+        &forge.vary_values,
+        &r.vary_values,
+        output_tolerance,
+        at,
+    )?;
+    compare_series("outputs", &forge.outputs, &r.outputs, output_tolerance, at)?;
+    compare_scalar(
+        "base_output",
+        forge.base_output,
+        r.base_output,
+        output_tolerance,
+    )?;
+    compare_scalar(
+        "elasticity",
+        forge.elasticity,
+        r.elasticity,
+        elasticity_tolerance,
+    )?;
+
+    Ok(format!(
+        "{} points within tolerance{}",
+        r.outputs.len(),
+        r.elasticity
+            .map_or_else(String::new, |e| format!(", elasticity={e:.4}"))
+    ))
+}
+
+/// Checks a two-way grid cell by cell.
+fn compare_sensitivity_grid(
+    test: &AnalyticsTestSpec,
+    forge: &SensitivityResults,
+    r: &SensitivityResults,
+    tolerance: f64,
+) -> Result<String, String> {
+    let var1 = test.vary.first().map_or("row", String::as_str);
+    let var2 = test.vary.get(1).map_or("col", String::as_str);
+
+    let rows = r.grid.len();
+    let cols = r.grid.first().map_or(0, Vec::len);
+    let forge_cols = forge.grid.first().map_or(0, Vec::len);
+    if forge.grid.len() != rows || forge_cols != cols {
+        return Err(format!(
+            "Grid shape mismatch: forge={}x{forge_cols}, R={rows}x{cols}",
+            forge.grid.len()
+        ));
+    }
+
+    for (i, (forge_row, r_row)) in forge.grid.iter().zip(&r.grid).enumerate() {
+        let row_label = r
+            .values1
+            .get(i)
+            .map_or_else(String::new, |v| format!("{var1}={v}"));
+        compare_series(&format!("grid[{i}]"), forge_row, r_row, tolerance, |j| {
+            let col_label = r
+                .values2
+                .get(j)
+                .map_or_else(String::new, |v| format!("{var2}={v}"));
+            format!("{row_label}, {col_label}")
+        })?;
+    }
+
+    compare_scalar("min_output", forge.min_output, r.min_output, tolerance)?;
+    compare_scalar("max_output", forge.max_output, r.max_output, tolerance)?;
+
+    Ok(format!("{rows}x{cols} grid within tolerance"))
+}
+
+/// Checks per-variable elasticities and their ranking (ties accepted).
+fn compare_elasticities(
+    forge: &SensitivityResults,
+    r: &SensitivityResults,
+    output_tolerance: f64,
+    tolerance: f64,
+) -> Result<String, String> {
+    compare_scalar(
+        "base_output",
+        forge.base_output,
+        r.base_output,
+        output_tolerance,
+    )?;
+
+    for r_entry in &r.elasticities {
+        compare_scalar(
+            &format!("Elasticity of {}", r_entry.variable),
+            forge.elasticity_of(&r_entry.variable),
+            Some(r_entry.elasticity),
+            tolerance,
+        )?;
+    }
+
+    let ranked: Vec<(&str, f64)> = r
+        .elasticities
+        .iter()
+        .map(|e| (e.variable.as_str(), e.elasticity.abs()))
+        .collect();
+    let groups = tie_groups(&ranked, tolerance);
+    let forge_ranking: Vec<String> = forge
+        .elasticities
+        .iter()
+        .map(|e| e.variable.clone())
+        .collect();
+    let r_ranking: Vec<&str> = ranked.iter().map(|(name, _)| *name).collect();
+
+    if let Some(rank) = ranking_mismatch(&forge_ranking, &groups) {
+        return Err(format!(
+            "Elasticity ranking mismatch at rank {rank}: forge=[{}], R=[{}]",
+            forge_ranking.join(", "),
+            r_ranking.join(", ")
+        ));
+    }
+
+    let ties = describe_ties(&groups);
+    Ok(if ties.is_empty() {
+        format!("rankings=[{}]", forge_ranking.join(", "))
+    } else {
+        format!(
+            "rankings=[{}]\nTie in R: {}",
+            forge_ranking.join(", "),
+            ties.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn spec(yaml: &str) -> AnalyticsTestSpec {
+        serde_yaml_ng::from_str(yaml).unwrap()
+    }
+
+    fn fail_reason(result: TestResult) -> String {
+        match result {
+            TestResult::Fail { reason, .. } => reason,
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn two_way_failure_names_the_cell() {
+        let test = spec("analysis_type: two_way\nvary: [price, volume]");
+        let r = parse_sensitivity_results(&json!({
+            "values1": [80, 100, 120],
+            "values2": [800, 1200],
+            "grid": [[160, 200], [180, 220], [200, 240]]
+        }))
+        .unwrap();
+        let forge = parse_sensitivity_results(&json!({
+            "sensitivity_results": {
+                "grid": [[160, 200], [180, 225], [200, 240]]
+            }
+        }))
+        .unwrap();
+
+        assert!(compare_sensitivity_results(&test, &r, &r).is_pass());
+        let reason = fail_reason(compare_sensitivity_results(&test, &forge, &r));
+        assert!(
+            reason.starts_with("grid[1][1] (price=100, volume=1200) mismatch"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn one_way_failure_names_the_input_value() {
+        let test = spec("analysis_type: one_way\nvary: [price]");
+        let r = parse_sensitivity_results(&json!({
+            "vary_values": [80, 100, 120],
+            "outputs": [80, 100, 120],
+            "elasticity": 1.0
+        }))
+        .unwrap();
+        let forge = parse_sensitivity_results(&json!({
+            "vary_values": [80, 100, 120],
+            "outputs": [80, 100, 121],
+            "elasticity": 1.0
+        }))
+        .unwrap();
+
+        let reason = fail_reason(compare_sensitivity_results(&test, &forge, &r));
+        assert!(
+            reason.starts_with("outputs[2] (price=120) mismatch"),
+            "{reason}"
+        );
+    }
+}
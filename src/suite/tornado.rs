@@ -0,0 +1,274 @@
+//! Tornado rankings and swings validated against `tornado_validator.R`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    describe_ties, one_or_many, parse_both, parse_output, ranking_mismatch, tie_groups, to_payload,
+    ForgeInvocation, Output, Suite,
+};
+use crate::stats::{compare, relative_difference};
+use crate::types::{AnalyticsTestSpec, RExpected, TestResult, TornadoVariable};
+
+/// Runs `forge tornado` and compares the ranking exactly (ties reported, not
+/// failed) and every swing within tolerance against one-at-a-time swings in R.
+pub struct TornadoSuite;
+
+impl Suite for TornadoSuite {
+    fn name(&self) -> &'static str {
+        "tornado"
+    }
+
+    fn validator(&self) -> &'static str {
+        "tornado_validator.R"
+    }
+
+    fn parse_spec(&self, spec: &AnalyticsTestSpec) -> Result<(), String> {
+        if spec.variables.is_empty() {
+            return Err("No variables specified (not a tornado test)".to_string());
+        }
+        Ok(())
+    }
+
+    fn forge_invocation(&self, spec: &AnalyticsTestSpec) -> Result<ForgeInvocation, String> {
+        let model = spec.model.as_deref().unwrap_or("linear");
+        let base_value = spec.base_value.unwrap_or(0.0);
+
+        let mut fixture = format!(
+            "_forge_version: \"5.0.0\"\ntornado:\n  model: {model}\n  base_value: {base_value}\n  variables:\n"
+        );
+        for var in &spec.variables {
+            let coefficient = spec.coefficients.get(&var.name).copied().unwrap_or(0.0);
+            let _ = write!(
+                fixture,
+                "    - name: \"{}\"\n      low: {}\n      high: {}\n      base: {}\n      coefficient: {coefficient}\n",
+                var.name, var.low, var.high, var.base
+            );
+        }
+        Ok(ForgeInvocation::new("tornado", fixture))
+    }
+
+    fn r_params(&self, spec: &AnalyticsTestSpec) -> Result<Value, String> {
+        to_payload(&TornadoParams {
+            base_value: spec.base_value.unwrap_or(0.0),
+            variables: &spec.variables,
+            model: spec.model.as_deref().unwrap_or("linear"),
+            coefficients: &spec.coefficients,
+        })
+    }
+
+    fn compare(&self, spec: &AnalyticsTestSpec, forge: &Value, r: &Value) -> TestResult {
+        let (forge, r) =
+            match parse_both(spec, forge, r, parse_tornado_results, parse_tornado_results) {
+                Ok(parsed) => parsed,
+                Err(result) => return result,
+            };
+
+        compare_tornado_results(&spec.name, &forge, &r, tolerance(spec))
+    }
+
+    fn compare_recorded(
+        &self,
+        spec: &AnalyticsTestSpec,
+        output: Output<'_>,
+        expected: &RExpected,
+    ) -> TestResult {
+        let actual = match parse_output(spec, output, parse_tornado_results, parse_tornado_results)
+        {
+            Ok(actual) => actual,
+            Err(result) => return result,
+        };
+        let rankings = if expected.rankings.is_empty() {
+            actual.rankings.clone()
+        } else {
+            expected.rankings.clone()
+        };
+        let variables = actual
+            .variables
+            .iter()
+            .map(|bar| TornadoBar {
+                name: bar.name.clone(),
+                swing: expected.swings.get(&bar.name).copied().unwrap_or(bar.swing),
+            })
+            .collect();
+        let recorded = TornadoResults {
+            rankings,
+            variables,
+        };
+
+        compare_tornado_results(&spec.name, &actual, &recorded, tolerance(spec))
+    }
+
+    fn record(&self, _: &AnalyticsTestSpec, r: &Value) -> Result<RExpected, String> {
+        let results = parse_tornado_results(r)?;
+        Ok(RExpected {
+            swings: results
+                .variables
+                .iter()
+                .map(|bar| (bar.name.clone(), bar.swing))
+                .collect(),
+            rankings: results.rankings,
+            ..Default::default()
+        })
+    }
+}
+
+fn tolerance(spec: &AnalyticsTestSpec) -> f64 {
+    spec.tolerance
+        .as_ref()
+        .and_then(|t| t.swing)
+        .unwrap_or(0.001)
+}
+
+/// JSON payload for `tornado_validator.R`.
+#[derive(Serialize)]
+struct TornadoParams<'a> {
+    base_value: f64,
+    variables: &'a [TornadoVariable],
+    model: &'a str,
+    coefficients: &'a HashMap<String, f64>,
+}
+
+/// One tornado bar.
+#[derive(Debug, Deserialize)]
+struct TornadoBar {
+    name: String,
+    swing: f64,
+}
+
+/// Parsed tornado results from either forge or R.
+#[derive(Debug, Deserialize)]
+struct TornadoResults {
+    #[serde(deserialize_with = "one_or_many")]
+    rankings: Vec<String>,
+    variables: Vec<TornadoBar>,
+}
+
+impl TornadoResults {
+    fn swing(&self, name: &str) -> Option<f64> {
+        self.variables
+            .iter()
+            .find(|v| v.name == name)
+            .map(|v| v.swing)
+    }
+}
+
+/// Parses tornado results (forge output is nested under `tornado_results`).
+fn parse_tornado_results(json: &Value) -> Result<TornadoResults, String> {
+    let results = json.get("tornado_results").unwrap_or(json);
+    serde_json::from_value(results.clone()).map_err(|e| format!("Malformed tornado results: {e}"))
+}
+
+/// Compares forge and R tornado results, returning Pass or Fail.
+///
+/// Variables whose R swings are equal within tolerance form a tie group;
+/// any order inside a group is accepted and reported on its own line of
+/// the details.
+fn compare_tornado_results(
+    test_name: &str,
+    forge: &TornadoResults,
+    r: &TornadoResults,
+    tolerance: f64,
+) -> TestResult {
+    let fail = |reason: String| TestResult::Fail {
+        name: test_name.to_string(),
+        reason,
+    };
+
+    for name in &r.rankings {
+        let Some(r_swing) = r.swing(name) else {
+            continue;
+        };
+        let Some(forge_swing) = forge.swing(name) else {
+            return fail(format!("Variable '{name}' missing from forge output"));
+        };
+        if !compare(format!("{name} swing"), forge_swing, r_swing, tolerance) {
+            return fail(format!(
+                "Swing mismatch for {name}: forge={forge_swing:.4}, R={r_swing:.4} (diff={:.3}%, tol={:.2}%)",
+                relative_difference(forge_swing, r_swing) * 100.0,
+                tolerance * 100.0
+            ));
+        }
+    }
+
+    // Group R's ranking into runs of equal swings
+    let ranked: Vec<(&str, f64)> = r
+        .rankings
+        .iter()
+        .map(|name| (name.as_str(), r.swing(name).unwrap_or(f64::NAN)))
+        .collect();
+    let groups = tie_groups(&ranked, tolerance);
+
+    if let Some(rank) = ranking_mismatch(&forge.rankings, &groups) {
+        return fail(format!(
+            "Ranking mismatch at rank {rank}: forge=[{}], R=[{}]",
+            forge.rankings.join(", "),
+            r.rankings.join(", ")
+        ));
+    }
+
+    let ties = describe_ties(&groups);
+
+    TestResult::Pass {
+        name: test_name.to_string(),
+        details: if ties.is_empty() {
+            format!("rankings=[{}]", forge.rankings.join(", "))
+        } else {
+            format!(
+                "rankings=[{}]\nTie in R: {}",
+                forge.rankings.join(", "),
+                ties.join(", ")
+            )
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(bars: &[(&str, f64)]) -> TornadoResults {
+        TornadoResults {
+            rankings: bars.iter().map(|(name, _)| name.to_string()).collect(),
+            variables: bars
+                .iter()
+                .map(|&(name, swing)| TornadoBar {
+                    name: name.to_string(),
+                    swing,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rankings_and_swings_must_match() {
+        let r = results(&[("price", 400.0), ("volume", 200.0), ("cost", 50.0)]);
+
+        let swapped = results(&[("volume", 200.0), ("price", 400.0), ("cost", 50.0)]);
+        let TestResult::Fail { reason, .. } = compare_tornado_results("t", &swapped, &r, 0.001)
+        else {
+            panic!("expected a failure");
+        };
+        assert!(reason.starts_with("Ranking mismatch at rank 1"), "{reason}");
+
+        let off = results(&[("price", 400.0), ("volume", 205.0), ("cost", 50.0)]);
+        let TestResult::Fail { reason, .. } = compare_tornado_results("t", &off, &r, 0.001) else {
+            panic!("expected a failure");
+        };
+        assert!(reason.starts_with("Swing mismatch for volume"), "{reason}");
+    }
+
+    #[test]
+    fn tied_swings_pass_in_either_order_with_a_note() {
+        let r = results(&[("price", 400.0), ("volume", 200.0), ("cost", 200.0)]);
+        let forge = results(&[("price", 400.0), ("cost", 200.0), ("volume", 200.0)]);
+        let TestResult::Pass { details, .. } = compare_tornado_results("t", &forge, &r, 0.001)
+        else {
+            panic!("expected a pass");
+        };
+        assert_eq!(details.lines().nth(1), Some("Tie in R: volume = cost"));
+    }
+}
@@ -4,13 +4,18 @@
 
 #![allow(dead_code)]
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::stats::equivalence::Margin;
+use crate::stats::gof::{GofKind, GofTest};
+use crate::stats::{Comparison, Samples, ToleranceMode};
 
 /// Analytics test specification.
 #[derive(Debug, Clone, Deserialize)]
 pub struct AnalyticsTestSpec {
-    /// Test name (populated from `HashMap` key).
+    /// Test name (populated from its key in the file).
     #[serde(default)]
     pub name: String,
     /// Distribution type (for Monte Carlo).
@@ -24,12 +29,185 @@ pub struct AnalyticsTestSpec {
     /// Number of iterations.
     #[serde(default = "default_iterations")]
     pub iterations: usize,
+    /// Iteration counts for a convergence run (Monte Carlo).
+    #[serde(default)]
+    pub iteration_counts: Vec<usize>,
+    /// Require the error to shrink at the 1/sqrt(N) rate (convergence).
+    #[serde(default)]
+    pub expected_error_reduction: bool,
+    /// Input data (for bootstrap).
+    #[serde(default)]
+    pub data: Vec<f64>,
+    /// Statistic to bootstrap (mean, median, std, var).
+    pub statistic: Option<String>,
+    /// Confidence interval method (percentile, bca, basic).
+    pub method: Option<String>,
+    /// Confidence levels for interval estimates.
+    #[serde(default)]
+    pub confidence_levels: Vec<f64>,
+    /// Decision tree (for EMV analysis).
+    pub tree: Option<DecisionNode>,
+    /// Option pricing model (`black_scholes`, `binomial`).
+    pub model: Option<String>,
+    /// Option type (`call`, `put`).
+    pub option_type: Option<String>,
+    /// Current asset value (S).
+    #[serde(rename = "S")]
+    pub spot: Option<f64>,
+    /// Strike price or investment cost (K).
+    #[serde(rename = "K")]
+    pub strike: Option<f64>,
+    /// Risk-free rate (r).
+    #[serde(rename = "r")]
+    pub rate: Option<f64>,
+    /// Volatility (sigma).
+    pub sigma: Option<f64>,
+    /// Time to expiration in years (T).
+    #[serde(rename = "T")]
+    pub maturity: Option<f64>,
+    /// Dividend yield (q).
+    #[serde(rename = "q")]
+    pub dividend_yield: Option<f64>,
+    /// Binomial tree steps (n).
+    #[serde(rename = "n")]
+    pub steps: Option<usize>,
+    /// Allow early exercise (binomial).
+    #[serde(default)]
+    pub american: bool,
+    /// Baseline model output (tornado).
+    pub base_value: Option<f64>,
+    /// Input variables with low/base/high values (tornado).
+    #[serde(default)]
+    pub variables: Vec<TornadoVariable>,
+    /// Model coefficient per variable (tornado, sensitivity).
+    #[serde(default)]
+    pub coefficients: HashMap<String, f64>,
+    /// Sensitivity analysis type (`one_way`, `two_way`, `elasticity`).
+    pub analysis_type: Option<String>,
+    /// Base value per model input (sensitivity).
+    #[serde(default)]
+    pub base_values: HashMap<String, f64>,
+    /// Inputs to vary (sensitivity).
+    #[serde(default)]
+    pub vary: Vec<String>,
+    /// Range per varied input (sensitivity).
+    #[serde(default)]
+    pub range: HashMap<String, SensitivityRange>,
+    /// Goodness-of-fit tests of the samples (Monte Carlo; default `[ks]`).
+    pub gof: Option<Vec<GofKind>>,
+    /// Equivalence margins; moments with one are judged by TOST instead of
+    /// their tolerance (Monte Carlo).
+    pub equivalence: Option<EquivalenceSpec>,
+    /// Labels for selecting tests with `--tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Suite to run this test with (overrides selection by validator).
+    pub suite: Option<String>,
     /// R validator script to use.
     pub r_validator: Option<String>,
     /// Expected results from R.
     pub r_expected: Option<RExpected>,
+    /// R environment that produced `r_expected` (written by `record`).
+    pub r_provenance: Option<RProvenance>,
     /// Tolerance levels.
     pub tolerance: Option<ToleranceSpec>,
+    /// Keys no field above takes, parsed by the suite's own spec type (the
+    /// Bayesian `network` and `query`).
+    #[serde(flatten)]
+    pub extra: serde_yaml_ng::Mapping,
+}
+
+/// Node of a decision tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionNode {
+    /// Node name (not necessarily unique within the tree).
+    pub name: String,
+    /// Node kind.
+    #[serde(rename = "type")]
+    pub node_type: DecisionNodeType,
+    /// Probability of reaching this node from its chance parent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    /// Payoff (terminal nodes).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payoff: Option<f64>,
+    /// Cost subtracted from this node's EMV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    /// Child nodes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Self>,
+}
+
+/// Kind of decision tree node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecisionNodeType {
+    /// Choose the child with the highest EMV.
+    Decision,
+    /// Probability-weighted average of the children.
+    Chance,
+    /// Leaf with a payoff.
+    Terminal,
+}
+
+/// Option Greeks (theta per day, vega and rho per 1% change).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Greeks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vega: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rho: Option<f64>,
+}
+
+impl Greeks {
+    /// Returns each Greek with its name.
+    #[must_use]
+    pub const fn named(&self) -> [(&'static str, Option<f64>); 5] {
+        [
+            ("delta", self.delta),
+            ("gamma", self.gamma),
+            ("theta", self.theta),
+            ("vega", self.vega),
+            ("rho", self.rho),
+        ]
+    }
+}
+
+/// Tornado input variable, swung one at a time from `low` to `high`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TornadoVariable {
+    pub name: String,
+    pub low: f64,
+    pub high: f64,
+    pub base: f64,
+}
+
+/// Range over which a sensitivity input is varied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensitivityRange {
+    pub low: f64,
+    pub high: f64,
+    /// Number of evenly spaced points, including both ends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<usize>,
+}
+
+/// Ranked elasticity of one input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElasticityEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    pub variable: String,
+    pub elasticity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interpretation: Option<String>,
 }
 
 const fn default_seed() -> u64 {
@@ -41,12 +219,136 @@ const fn default_iterations() -> usize {
 }
 
 /// Expected values from R.
-#[derive(Debug, Clone, Deserialize)]
+///
+/// Serializes only the values that are set, maps in key order, as written
+/// back by `record`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RExpected {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mean: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub std: Option<f64>,
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
     pub percentiles: HashMap<String, f64>,
+    /// Statistic on the original data (bootstrap).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_estimate: Option<f64>,
+    /// Bootstrap bias (bootstrap).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bias: Option<f64>,
+    /// Confidence intervals keyed by level, e.g. "0.95" (bootstrap).
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub confidence_intervals: HashMap<String, ConfidenceInterval>,
+    /// Posterior probability per state (Bayesian).
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub posterior: HashMap<String, f64>,
+    /// EMV of the root node (decision tree).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_emv: Option<f64>,
+    /// Optimal first decision (decision tree).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimal_decision: Option<String>,
+    /// Optimal decisions from the root down (decision tree).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decision_path: Vec<String>,
+    /// EMV per node name (decision tree).
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub node_emvs: HashMap<String, f64>,
+    /// Option price (real options).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    /// Option Greeks (real options).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub greeks: Option<Greeks>,
+    /// Variables ordered by descending swing (tornado).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rankings: Vec<String>,
+    /// Output swing per variable (tornado).
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub swings: HashMap<String, f64>,
+    /// Input values of a one-way sweep (sensitivity).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vary_values: Vec<f64>,
+    /// Outputs of a one-way sweep (sensitivity).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<f64>,
+    /// Output at the base case (sensitivity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_output: Option<f64>,
+    /// Elasticity at the base case (one-way sensitivity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elasticity: Option<f64>,
+    /// Smallest output (sensitivity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_output: Option<f64>,
+    /// Largest output (sensitivity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output: Option<f64>,
+    /// Output range, max minus min (one-way sensitivity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<f64>,
+    /// Output grid, rows for the first input, columns for the second (two-way).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grid: Vec<Vec<f64>>,
+    /// Ranked elasticities (elasticity analysis).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub elasticities: Vec<ElasticityEntry>,
+}
+
+/// R environment that produced a test's `r_expected` values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RProvenance {
+    /// `R.version.string`, e.g. `R version 4.4.1 (2024-06-14)`.
+    pub r_version: String,
+    /// Version of each package the validator loads.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, String>,
+}
+
+/// Serializes a map in key order, numerically when every key is a number
+/// (percentiles, confidence levels), so rewritten YAML is stable.
+fn sorted_map<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries: Vec<_> = map.iter().collect();
+    let numeric: Option<Vec<f64>> = entries.iter().map(|(k, _)| k.parse().ok()).collect();
+    if numeric.is_some() {
+        entries.sort_by(|a, b| {
+            let key = |k: &str| k.parse::<f64>().unwrap_or_default();
+            key(a.0).total_cmp(&key(b.0))
+        });
+    } else {
+        entries.sort_by_key(|(k, _)| k.as_str());
+    }
+    serializer.collect_map(entries)
+}
+
+/// Lower and upper bound of a confidence interval.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
 }
 
 /// Tolerance specification from YAML.
@@ -55,6 +357,60 @@ pub struct ToleranceSpec {
     pub mean: Option<f64>,
     pub std: Option<f64>,
     pub percentiles: Option<f64>,
+    /// Smallest p-value accepted from the goodness-of-fit tests (`gof`).
+    #[serde(alias = "ks_pvalue")]
+    pub gof_pvalue: Option<f64>,
+    pub ci_bounds: Option<f64>,
+    /// Absolute tolerance for probabilities.
+    pub probability: Option<f64>,
+    /// Relative tolerance for expected monetary values.
+    pub emv: Option<f64>,
+    /// Relative tolerance for option prices.
+    pub price: Option<f64>,
+    /// Relative tolerance for option Greeks.
+    pub greeks: Option<f64>,
+    /// Ranking comparison mode (only `exact` is supported).
+    pub rankings: Option<String>,
+    /// Relative tolerance for tornado swings.
+    pub swing: Option<f64>,
+    /// Relative tolerance for model outputs (sensitivity).
+    pub output: Option<f64>,
+    /// Relative tolerance for elasticities (sensitivity).
+    pub elasticity: Option<f64>,
+    /// `statistical` derives the Monte Carlo tolerances from sampling error
+    /// instead of `mean`, `std` and `percentiles`.
+    pub mode: Option<ToleranceMode>,
+    /// Chance of a statistical-mode test failing when forge is correct.
+    pub false_failure_rate: Option<f64>,
+}
+
+/// Equivalence margins from YAML (`equivalence:`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct EquivalenceSpec {
+    /// Margin for the mean, as a fraction of R's mean.
+    pub mean: Option<f64>,
+    /// Margin for the mean in its own units; overrides `mean`. For outputs
+    /// whose mean is near zero, where a fraction of it is no margin at all.
+    pub mean_absolute: Option<f64>,
+    /// Margin for the variance ratio (e.g. 0.1 for 1 ± 10%).
+    pub variance: Option<f64>,
+    /// Level of each one-sided test.
+    #[serde(default = "default_alpha")]
+    pub alpha: f64,
+}
+
+const fn default_alpha() -> f64 {
+    0.05
+}
+
+impl EquivalenceSpec {
+    /// Margin for the mean, if one is declared.
+    #[must_use]
+    pub fn mean_margin(&self) -> Option<Margin> {
+        self.mean_absolute
+            .map(Margin::Absolute)
+            .or_else(|| self.mean.map(Margin::Relative))
+    }
 }
 
 /// Result of running a test.
@@ -69,6 +425,9 @@ pub enum TestResult {
     Error { name: String, error: String },
     /// Test was skipped.
     Skip { name: String, reason: String },
+    /// An equivalence test could neither show forge equivalent to R nor
+    /// show it different; not a pass.
+    Inconclusive { name: String, reason: String },
 }
 
 impl TestResult {
@@ -88,34 +447,106 @@ impl TestResult {
             Self::Pass { name, .. }
             | Self::Fail { name, .. }
             | Self::Error { name, .. }
-            | Self::Skip { name, .. } => name,
+            | Self::Skip { name, .. }
+            | Self::Inconclusive { name, .. } => name,
         }
     }
 }
 
+/// A test result with its timing and the values compared to reach it.
+#[derive(Debug, Serialize)]
+pub struct TestReport {
+    /// Outcome.
+    #[serde(flatten)]
+    pub result: TestResult,
+    /// Suite the test ran with (`formula/<file>` for formula assertions,
+    /// empty if none matched).
+    pub suite: String,
+    /// Time spent on the test.
+    #[serde(rename = "duration_secs", serialize_with = "duration_secs")]
+    pub duration: Duration,
+    /// Values checked against their tolerance, up to the first failure.
+    pub comparisons: Vec<Comparison>,
+    /// Goodness-of-fit tests of forge's samples against R's.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gof: Vec<GofTest>,
+    /// Forge and R samples of a stochastic test (HTML plots only).
+    #[serde(skip)]
+    pub samples: Option<Samples>,
+}
+
+fn duration_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
 /// Analytics test file structure.
 #[derive(Debug, Deserialize)]
 pub struct AnalyticsTestFile {
+    /// Suite for this file (defaults to the suite of `_r_validator`).
+    #[serde(rename = "_suite")]
+    pub suite: Option<String>,
+
     /// R validator for this file.
     #[serde(rename = "_r_validator")]
     pub r_validator: Option<String>,
 
-    /// Tests in this file.
+    /// Tests in this file, in file order.
+    #[serde(default)]
+    pub tests: serde_yaml_ng::Mapping,
+
+    /// Convergence tests in this file (Monte Carlo), in file order.
+    #[serde(default)]
+    pub convergence_tests: serde_yaml_ng::Mapping,
+
+    /// Formula assertions in this file, in file order.
     #[serde(default)]
-    pub tests: HashMap<String, AnalyticsTestSpec>,
+    pub assumptions: serde_yaml_ng::Mapping,
 }
 
-/// Loads analytics test specs from a YAML file.
+/// A formula and the scalar it must evaluate to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FormulaAssertion {
+    /// Assertion name (key in the `assumptions:` map).
+    #[serde(skip)]
+    pub name: String,
+    /// Forge formula, e.g. `=BREAKEVEN_UNITS(50000, 100, 60)`.
+    pub formula: String,
+    /// Expected value.
+    pub expected: f64,
+}
+
+/// Formula assertions from one file, evaluated together in one forge model.
+#[derive(Debug, Clone)]
+pub struct FormulaSuite {
+    /// Suite name (file stem).
+    pub name: String,
+    /// R validator used as the second oracle.
+    pub r_validator: Option<String>,
+    /// Assertions in file order.
+    pub assertions: Vec<FormulaAssertion>,
+}
+
+/// Loads analytics test specs from a YAML file, `tests:` then
+/// `convergence_tests:`, each in file order.
 ///
 /// # Errors
 ///
-/// Returns an error if the YAML content cannot be parsed.
+/// Returns an error if the YAML content or any test cannot be parsed.
 pub fn load_analytics_tests(content: &str) -> anyhow::Result<Vec<AnalyticsTestSpec>> {
     let file: AnalyticsTestFile = serde_yaml_ng::from_str(content)?;
     let mut tests = Vec::new();
 
-    for (name, mut spec) in file.tests {
+    for (key, value) in file.tests.into_iter().chain(file.convergence_tests) {
+        let name = key
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Test names must be strings"))?
+            .to_string();
+        let mut spec: AnalyticsTestSpec = serde_yaml_ng::from_value(value)
+            .map_err(|e| anyhow::anyhow!("Invalid test '{name}': {e}"))?;
         spec.name = name;
+        if spec.suite.is_none() {
+            spec.suite.clone_from(&file.suite);
+        }
         if spec.r_validator.is_none() {
             spec.r_validator.clone_from(&file.r_validator);
         }
@@ -125,6 +556,38 @@ pub fn load_analytics_tests(content: &str) -> anyhow::Result<Vec<AnalyticsTestSp
     Ok(tests)
 }
 
+/// Loads the formula assertion suite from a YAML file's `assumptions:` map.
+///
+/// Returns `None` if the file has no assumptions.
+///
+/// # Errors
+///
+/// Returns an error if the YAML content or any assertion cannot be parsed.
+pub fn load_formula_suite(name: &str, content: &str) -> anyhow::Result<Option<FormulaSuite>> {
+    let file: AnalyticsTestFile = serde_yaml_ng::from_str(content)?;
+    if file.assumptions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut assertions = Vec::new();
+    for (key, value) in file.assumptions {
+        let key = key
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Assumption names must be strings"))?
+            .to_string();
+        let mut assertion: FormulaAssertion = serde_yaml_ng::from_value(value)
+            .map_err(|e| anyhow::anyhow!("Invalid assumption '{key}': {e}"))?;
+        assertion.name = key;
+        assertions.push(assertion);
+    }
+
+    Ok(Some(FormulaSuite {
+        name: name.to_string(),
+        r_validator: file.r_validator,
+        assertions,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +609,51 @@ tests:
         assert_eq!(tests.len(), 1);
         assert_eq!(tests[0].distribution, Some("normal".to_string()));
     }
+
+    #[test]
+    fn tests_load_in_file_order() {
+        let yaml = r#"
+_r_validator: "monte_carlo_validator.R"
+tests:
+  zeta_normal:
+    distribution: normal
+    params:
+      mean: 100
+      sd: 15
+    seed: 42
+    iterations: 10000
+  alpha_uniform:
+    distribution: uniform
+  mid_triangular:
+    distribution: triangular
+    r_validator: "other_validator.R"
+convergence_tests:
+  normal_convergence:
+    distribution: normal
+    iteration_counts: [1000, 10000, 100000]
+    expected_error_reduction: true
+"#;
+        let tests = load_analytics_tests(yaml).unwrap();
+        let names: Vec<&str> = tests.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "zeta_normal",
+                "alpha_uniform",
+                "mid_triangular",
+                "normal_convergence"
+            ]
+        );
+        assert_eq!(tests[0].distribution.as_deref(), Some("normal"));
+        assert_eq!(
+            tests[0].r_validator.as_deref(),
+            Some("monte_carlo_validator.R")
+        );
+        assert_eq!(tests[2].r_validator.as_deref(), Some("other_validator.R"));
+        assert_eq!(tests[3].iteration_counts, vec![1_000, 10_000, 100_000]);
+        assert!(tests[3].expected_error_reduction);
+
+        let err = load_analytics_tests("tests:\n  bad:\n    seed: many\n").unwrap_err();
+        assert!(err.to_string().starts_with("Invalid test 'bad'"), "{err}");
+    }
 }